@@ -11,6 +11,7 @@ import { LineMaterial } from 'three/examples/jsm/lines/LineMaterial';
 import * as BufferGeometryUtils from 'three/examples/jsm/utils/BufferGeometryUtils';
 import '../global.css'
 import { generate_safe_toolpath_js } from '../utils/safe_toolpath.js';
+import { generate_flatten_toolpath_js } from '../utils/flatten_toolpath.js';
 declare global {
   interface Window {
     current_heightmap?: any;
@@ -56,6 +57,8 @@ type FlattenOperation = {
   type: 'flatten',
   params: {
     flatten_depth: number,
+    overshoot: number, // mm the tool center runs past the stock edges
+    stepdown: number, // mm per level, 0 for a single level
     tool: {
       cutter_diameter: number,
       shank_diameter: number,
@@ -105,7 +108,6 @@ const StartPage: React.FC = () => {
 
   // Flatten operation states
   const [flatten_depth, set_flatten_depth] = React.useState(1); // mm, default flatten depth
-  const [generating_flatten, set_generating_flatten] = React.useState(false);
   const [flatten_toolpath, set_flatten_toolpath] = React.useState<any>(null);
 
   // Operation-driven workflow state
@@ -336,6 +338,18 @@ const StartPage: React.FC = () => {
   };
 
 
+  // What simulate_material_removal records in the stock's attribute layers for the cells an
+  // operation cuts: 1-based operation index, 1-based index of the first operation using the
+  // same tool (0 if none), and seconds since the page loaded
+  function cut_stamp_for(operation: Operation | undefined, tool: any) {
+    const same_tool = (o: Operation) => o.type !== 'stock' && JSON.stringify(o.params.tool) === JSON.stringify(tool);
+    return {
+      operation_id: operation ? operations.indexOf(operation) + 1 : 0,
+      tool_id: operations.findIndex(same_tool) + 1,
+      time: performance.now() / 1000
+    };
+  }
+
   function run_simulation(geometry: THREE.BufferGeometry, tool: any, box_bounds: { min: THREE.Vector3, max: THREE.Vector3 }) {
     // Assumes geometry has boundingBox computed, tool is a flat object, box_bounds is {min, max}
     const box = new THREE.Box3(box_bounds.min.clone(), box_bounds.max.clone());
@@ -475,6 +489,7 @@ const StartPage: React.FC = () => {
 
     // 4. Simulate material removal after toolpath is generated (all in STL coordinates)
     // Flatten all tool operations for simulation
+    stock.cut_stamp = cut_stamp_for(operations.find(o => o.type === 'carve'), tool);
     simulate_material_removal(stock, tool, toolpath_points_ref.current.flat());
 
     // 5. Assign to window.current_heightmap for visualization effect
@@ -622,6 +637,7 @@ const StartPage: React.FC = () => {
       } else {
         console.log('Toolpath is empty after generation!');
       }
+      stock.cut_stamp = cut_stamp_for(selected_op, tool);
       simulate_material_removal(stock, tool, toolpath_points_ref.current.flat());
       window.current_heightmap = stock;
       set_stock_update_counter((c: number) => c + 1);
@@ -630,32 +646,41 @@ const StartPage: React.FC = () => {
     } else if (selected_op.type === 'flatten') {
       // Flatten operation: do NOT use heightmap_from_mesh or stl_geometry
       tool = selected_op.params.tool;
-      step_over = tool.cutter_diameter * selected_op.params.step_over_percent / 100;
       // Remove all existing toolpath lines from the scene
       scene_ref.current!.children
         .filter(obj => obj.userData.is_tool_path && obj.userData.operation === 'flatten')
         .forEach(obj => scene_ref.current!.remove(obj));
-      // Generate a raster toolpath for flattening the top face
-      const min_x = box_bounds.min.x;
-      const max_x = box_bounds.max.x;
-      const min_y = box_bounds.min.y;
-      const max_y = box_bounds.max.y;
-      const z = box_bounds.max.z - selected_op.params.flatten_depth;
-      const toolpath_lines: { x: number; y: number; z: number }[][] = [];
-      let reverse = false;
-      for (let y = min_y; y <= max_y; y += step_over) {
-        const line: { x: number; y: number; z: number }[] = [];
-        for (let x = min_x; x <= max_x; x += step_over / 2) {
-          line.push({ x, y, z });
+      // Simulation stock over the current bounds; rows are sampled at its cell size
+      const size = new THREE.Vector3();
+      size.subVectors(box_bounds.max, box_bounds.min);
+      const grid_cells_x = 100;
+      const grid_cells_y = 100;
+      const stock = create_heightmap_stock(
+        size.x,
+        size.y,
+        grid_cells_x,
+        grid_cells_y,
+        box_bounds.max.z,
+        box_bounds.min.x,
+        box_bounds.min.y
+      );
+      // Raster facing passes from the Rust generator, stepping down to the flatten depth
+      const { passes: toolpath_lines, linked: flat_toolpath } = generate_flatten_toolpath_js(
+        { min_x: box_bounds.min.x, min_y: box_bounds.min.y, max_x: box_bounds.max.x, max_y: box_bounds.max.y },
+        tool,
+        {
+          step_over_percent: selected_op.params.step_over_percent,
+          overshoot: selected_op.params.overshoot,
+          top_z: box_bounds.max.z,
+          target_z: box_bounds.max.z - selected_op.params.flatten_depth,
+          stepdown: selected_op.params.stepdown,
+          sample_step: Math.min(size.x / grid_cells_x, size.y / grid_cells_y),
+          clearance_z: box_bounds.max.z + 5
         }
-        if (reverse) line.reverse();
-        toolpath_lines.push(line);
-        reverse = !reverse;
-      }
-      // Assign to toolpath_points_ref for simulation and visualization
+      );
+      // Assign to toolpath_points_ref for visualization
       toolpath_points_ref.current = toolpath_lines;
-      // Flatten for simulation and for UI visualization (single array for flatten_toolpath)
-      const flat_toolpath = toolpath_lines.flat();
+      // Linked passes (with retracts) for simulation, G-code export and the UI
       set_flatten_toolpath(flat_toolpath);
       // Add toolpath lines to the scene for visualization
       const toolpath_box = new THREE.Box3(
@@ -709,19 +734,7 @@ const StartPage: React.FC = () => {
         console.log('Toolpath is empty after generation!');
       }
       // Simulate material removal for flatten toolpath and update carved result
-      const size = new THREE.Vector3();
-      size.subVectors(box_bounds.max, box_bounds.min);
-      const grid_cells_x = 100;
-      const grid_cells_y = 100;
-      const stock = create_heightmap_stock(
-        size.x,
-        size.y,
-        grid_cells_x,
-        grid_cells_y,
-        box_bounds.max.z,
-        box_bounds.min.x,
-        box_bounds.min.y
-      );
+      stock.cut_stamp = cut_stamp_for(selected_op, tool);
       simulate_material_removal(stock, tool, flat_toolpath);
       window.current_heightmap = stock;
       set_stock_update_counter((c: number) => c + 1);
@@ -887,58 +900,6 @@ const StartPage: React.FC = () => {
     }
   }, [flatten_toolpath, scene_ref.current, box_bounds]);
 
-  // Generate a raster flatten toolpath for the current stock bounds
-  function handle_flatten_generate() {
-    if (!box_bounds || !operations[selected_operation_index]) return;
-    const op = operations[selected_operation_index];
-    if (op.type !== 'flatten') return;
-    set_generating_flatten(true);
-    // For now, flatten the entire top face of the stock
-    const min_x = box_bounds.min.x;
-    const max_x = box_bounds.max.x;
-    const min_y = box_bounds.min.y;
-    const max_y = box_bounds.max.y;
-    const z = box_bounds.max.z - op.params.flatten_depth;
-    const step_over = op.params.tool.cutter_diameter * op.params.step_over_percent;
-    const toolpath: { x: number; y: number; z: number }[] = [];
-    let reverse = false;
-    for (
-      let y = min_y; y <= max_y; y += step_over
-    ) {
-      const line: { x: number; y: number; z: number }[] = [];
-      for (
-        let x = min_x; x <= max_x; x += step_over / 2
-      ) {
-        line.push({ x, y, z });
-      }
-      if (reverse) line.reverse();
-      toolpath.push(...line);
-      reverse = !reverse;
-    }
-    set_flatten_toolpath(toolpath);
-    // Simulate material removal for flatten toolpath and update carved result
-    // Create a stock object matching the current box_bounds
-    const size = new THREE.Vector3();
-    size.subVectors(box_bounds.max, box_bounds.min);
-    const grid_cells_x = 100; // reasonable default for flatten
-    const grid_cells_y = 100;
-    const stock = create_heightmap_stock(
-      size.x,
-      size.y,
-      grid_cells_x,
-      grid_cells_y,
-      box_bounds.max.z,
-      box_bounds.min.x,
-      box_bounds.min.y
-    );
-    simulate_material_removal(stock, op.params.tool, toolpath);
-    window.current_heightmap = stock;
-    set_stock_update_counter((c: number) => c + 1);
-    set_show_stock(false);
-    setTimeout(() => set_show_stock(true), 0);
-    set_generating_flatten(false);
-  }
-
   // Export flatten toolpath as G-code
   function handle_export_flatten_gcode() {
     if (!flatten_toolpath || flatten_toolpath.length === 0 || !box_bounds) return;
@@ -1195,6 +1156,8 @@ const StartPage: React.FC = () => {
                   type: 'flatten',
                   params: {
                     flatten_depth: 1,
+                    overshoot: 2,
+                    stepdown: 0,
                     tool: operations[selected_operation_index]?.type === 'carve' || operations[selected_operation_index]?.type === 'flatten'
                       ? { ...operations[selected_operation_index].params.tool }
                       : { cutter_diameter: 3.175, shank_diameter: 3.175, overall_length: 38.0, length_of_cut: 17.0, type: 'flat', v_angle: 60 },
@@ -1387,7 +1350,7 @@ const StartPage: React.FC = () => {
                     </>
                   );
                 })()}
-                {/* Flatten operation parameters (flatten_depth, tool, step_over_percent) */}
+                {/* Flatten operation parameters (flatten_depth, overshoot, stepdown, tool, step_over_percent) */}
                 {operations[selected_operation_index]?.type === 'flatten' && (() => {
                   const op = operations[selected_operation_index] as FlattenOperation;
                   return (
@@ -1411,6 +1374,48 @@ const StartPage: React.FC = () => {
                         />
                         mm
                       </label>
+                      <label>
+                        Overshoot
+                        <input
+                          type="number"
+                          min={0}
+                          step="any"
+                          value={op.params.overshoot}
+                          onChange={e => {
+                            const v = parseFloat(e.target.value);
+                            // Ignore emptied or negative fields, the generator needs finite values
+                            if (!Number.isFinite(v) || v < 0) return;
+                            set_operations(ops => ops.map((op2, idx) =>
+                              idx === selected_operation_index && op2.type === 'flatten'
+                                ? { ...op2, params: { ...op2.params, overshoot: v } }
+                                : op2
+                            ));
+                            set_simulation_dirty(true);
+                          }}
+                        />
+                        mm
+                      </label>
+                      <label>
+                        Stepdown (0 = single pass)
+                        <input
+                          type="number"
+                          min={0}
+                          step="any"
+                          value={op.params.stepdown}
+                          onChange={e => {
+                            const v = parseFloat(e.target.value);
+                            // Ignore emptied or negative fields, the generator needs finite values
+                            if (!Number.isFinite(v) || v < 0) return;
+                            set_operations(ops => ops.map((op2, idx) =>
+                              idx === selected_operation_index && op2.type === 'flatten'
+                                ? { ...op2, params: { ...op2.params, stepdown: v } }
+                                : op2
+                            ));
+                            set_simulation_dirty(true);
+                          }}
+                        />
+                        mm
+                      </label>
                       <label>
                         Cutter Diameter
                         <input
@@ -1596,6 +1601,7 @@ const StartPage: React.FC = () => {
                         0,
                         0
                       );
+                      stock.cut_stamp = cut_stamp_for(carve_op, carve_op.params.tool);
                       simulate_material_removal(stock, carve_op.params.tool, toolpath_points_ref.current.flat());
                       window.current_heightmap = stock;
                       set_stock_update_counter((c: number) => c + 1);
@@ -25,6 +25,7 @@ if (typeof require !== 'undefined' && require.main === module) {
     nx,
     ny,
     grid_size,
+    grid_size,
     0.0,
     0.0,
     tool.type,
@@ -0,0 +1,15 @@
+// TypeScript declaration for flatten_toolpath.js
+type ToolpathPoint = { x: number; y: number; z: number };
+export function generate_flatten_toolpath_js(
+  bounds: { min_x: number; min_y: number; max_x: number; max_y: number },
+  tool: { cutter_diameter: number },
+  params: {
+    step_over_percent: number;
+    overshoot: number;
+    top_z: number;
+    target_z: number;
+    stepdown: number;
+    sample_step?: number;
+    clearance_z: number;
+  }
+): { passes: ToolpathPoint[][]; linked: ToolpathPoint[] };
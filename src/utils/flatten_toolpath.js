@@ -0,0 +1,80 @@
+// Utility to call the WASM flatten (facing) toolpath generator
+// and return its passes and the linked toolpath as {x, y, z} points
+
+let wasm_mod = null;
+let wasm_ready = false;
+const is_jest = typeof process !== 'undefined' && process.env && process.env.JEST_WORKER_ID !== undefined;
+if (!is_jest) {
+  (async () => {
+    try {
+      wasm_mod = await import('../../wasm_kernel/pkg/wasm_kernel.js');
+      if (wasm_mod && wasm_mod.default) {
+        await wasm_mod.default();
+      }
+      wasm_ready = true;
+      console.log('[flatten_toolpath] WASM kernel loaded');
+    } catch (e) {
+      console.warn('[flatten_toolpath] Failed to load WASM kernel:', e);
+      wasm_mod = null;
+      wasm_ready = false;
+    }
+  })();
+} else {
+  try {
+    wasm_mod = require('../../wasm_kernel/pkg/wasm_kernel.js');
+    if (wasm_mod && wasm_mod.default) {
+      wasm_mod.default();
+    }
+    wasm_ready = true;
+  } catch (e) {
+    console.warn('[flatten_toolpath] Failed to load WASM kernel in Jest:', e);
+    wasm_mod = null;
+    wasm_ready = false;
+  }
+}
+
+// Flat x0, y0, z0, x1, ... to [{x, y, z}, ...]
+function to_points(flat) {
+  const points = [];
+  for (let i = 0; i + 2 < flat.length; i += 3) {
+    points.push({ x: flat[i], y: flat[i + 1], z: flat[i + 2] });
+  }
+  return points;
+}
+
+/**
+ * Generate a raster facing toolpath over a rectangle, stepping down from top_z to target_z.
+ * @param {Object} bounds - { min_x, min_y, max_x, max_y } of the area to face (e.g. the stock)
+ * @param {Object} tool - { cutter_diameter }
+ * @param {Object} params - { step_over_percent, overshoot, top_z, target_z, stepdown, sample_step, clearance_z }
+ *   overshoot: how far the tool center runs past the area edges (mm)
+ *   stepdown: max depth per level (mm), <= 0 for a single level
+ *   sample_step: distance between points along a row (mm), <= 0 for half the stepover
+ * @returns {{ passes: Array<Array<{x:number,y:number,z:number}>>, linked: Array<{x:number,y:number,z:number}> }}
+ *   passes: one cutting pass each; linked: all passes joined by moves at clearance_z
+ */
+function generate_flatten_toolpath_js(bounds, tool, params) {
+  if (!wasm_mod || !wasm_ready || !wasm_mod.generate_flatten_toolpath_wasm) {
+    throw new Error('WASM kernel not loaded');
+  }
+  // A rectangle is its two corners with no loop counts
+  const area = new Float32Array([bounds.min_x, bounds.min_y, bounds.max_x, bounds.max_y]);
+  const result = wasm_mod.generate_flatten_toolpath_wasm(
+    area,
+    new Uint32Array(0),
+    tool.cutter_diameter,
+    params.step_over_percent,
+    params.overshoot,
+    params.top_z,
+    params.target_z,
+    params.stepdown,
+    params.sample_step || 0,
+    params.clearance_z
+  );
+  return {
+    passes: Array.from(result.passes, to_points),
+    linked: to_points(result.linked),
+  };
+}
+
+module.exports = { generate_flatten_toolpath_js };
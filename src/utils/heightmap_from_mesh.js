@@ -62,12 +62,12 @@ function heightmap_from_mesh(geometry, grid) {
         grid.res_x,
         grid.res_y
       );
-      // Convert flat Float32Array to 2D JS array [iy][ix]
+      // Convert the flat Float32Array (ix * res_y + iy) to a 2D JS array [iy][ix]
       const heightmap = [];
       for (let iy = 0; iy < grid.res_y; iy++) {
         heightmap[iy] = [];
         for (let ix = 0; ix < grid.res_x; ix++) {
-          heightmap[iy][ix] = result[iy * grid.res_x + ix];
+          heightmap[iy][ix] = result[ix * grid.res_y + iy];
         }
       }
       return heightmap;
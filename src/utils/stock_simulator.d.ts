@@ -12,4 +12,5 @@ export function create_heightmap_stock(
 ): any;
 export function simulate_material_removal(stock: any, tool: any, toolpath: any[]): void;
 // min_z is required for correct geometry (prevents NaN in bottom/side faces)
+// The mesh is cached on the stock: after more cuts only the dirty tiles are rewritten and the same mesh comes back
 export function heightmap_to_solid_mesh(stock: any, min_z: number): THREE.Mesh;
\ No newline at end of file
@@ -24,6 +24,14 @@ function create_heightmap_stock(width, height, grid_cells_x, grid_cells_y, initi
     return heights[ix][iy];
   }
 
+  // Per-node attribute layers (heightmap layout) that simulate_material_removal stamps with
+  // stock.cut_stamp ({ operation_id, tool_id, time }) wherever it cuts; 0 / -1 until first cut
+  const attributes = {
+    operation_id: new Uint32Array(nx * ny),
+    tool_id: new Uint32Array(nx * ny),
+    cut_time: new Float32Array(nx * ny).fill(-1)
+  };
+
   // Set the height at world (x, y)
   function set_height(x, y, z) {
     const ix = Math.round((x - origin_x) / grid_size_x);
@@ -42,7 +50,9 @@ function create_heightmap_stock(width, height, grid_cells_x, grid_cells_y, initi
     grid_size_x,
     grid_size_y,
     origin_x,
-    origin_y
+    origin_y,
+    attributes,
+    cut_stamp: null
   };
 }
 
@@ -67,21 +77,86 @@ if (!is_jest) {
   })();
 }
 
-// WASM-backed mesh generator
+// Columns per mesh patch when only the dirty part of a stock mesh is regenerated
+const PATCH_TILE_COLUMNS = 16;
+
+// Attribute layers shown on the stock mesh as per-vertex attributes of the same name
+const ATTRIBUTE_LAYERS = ['operation_id', 'tool_id', 'cut_time'];
+
+// Refresh the per-vertex layer attributes from the stock's node layers. The mesh has three
+// blocks of nx * ny vertices (top, bottom, side copies of the top), each in node order.
+function copy_attribute_layers(geometry, stock) {
+  for (const name of ATTRIBUTE_LAYERS) {
+    const attribute = geometry.getAttribute(name);
+    const layer = stock.attributes && stock.attributes[name];
+    if (!attribute || !layer) continue;
+    for (let block = 0; block < 3; block++) {
+      attribute.array.set(layer, block * layer.length);
+    }
+    attribute.needsUpdate = true;
+  }
+}
+
+// Rewrite the vertex ranges of the cached stock mesh under the accumulated dirty rects.
+// Returns false when there is no cached mesh to patch (the caller builds a new one).
+function patch_solid_mesh(stock, min_z) {
+  const mesh = stock.mesh;
+  if (!mesh || stock.mesh_min_z !== min_z || !stock.heightmap || !wasm_mod.heightmap_to_solid_mesh_patches_wasm) {
+    return false;
+  }
+  if (stock.dirty_rects && stock.dirty_rects.length > 0) {
+    const patches = wasm_mod.heightmap_to_solid_mesh_patches_wasm(
+      stock.heightmap,
+      stock.width,
+      stock.height,
+      stock.grid_cells_x,
+      stock.grid_cells_y,
+      stock.origin_x,
+      stock.origin_y,
+      min_z,
+      stock.dirty_rects,
+      PATCH_TILE_COLUMNS
+    );
+    const position = mesh.geometry.getAttribute('position');
+    const normal = mesh.geometry.getAttribute('normal');
+    for (const patch of patches) {
+      position.array.set(patch.positions, patch.first_vertex * 3);
+      normal.array.set(patch.normals, patch.first_vertex * 3);
+      position.addUpdateRange(patch.first_vertex * 3, patch.positions.length);
+      normal.addUpdateRange(patch.first_vertex * 3, patch.normals.length);
+    }
+    position.needsUpdate = true;
+    normal.needsUpdate = true;
+    copy_attribute_layers(mesh.geometry, stock);
+    mesh.geometry.computeBoundingBox();
+    mesh.geometry.computeBoundingSphere();
+  }
+  stock.dirty_rects = new Uint32Array(0);
+  return true;
+}
+
+// WASM-backed mesh generator. The mesh is cached on the stock; after more cuts only the
+// tiles under stock.dirty_rects are regenerated and the same mesh is returned.
 function heightmap_to_solid_mesh(stock, min_z) {
   // Use WASM if available and ready
   if (wasm_mod && wasm_ready && wasm_mod.heightmap_to_solid_mesh_wasm) {
     try {
+      if (patch_solid_mesh(stock, min_z)) {
+        return stock.mesh;
+      }
       // Convert 2D heights to flat Float32Array (row-major)
       const nx = stock.grid_cells_x;
       const ny = stock.grid_cells_y;
-      let heights = new Float32Array(nx * ny);
-      for (let ix = 0; ix < nx; ix++) {
-        for (let iy = 0; iy < ny; iy++) {
-          heights[ix * ny + iy] = stock.get_height(
-            stock.origin_x + ix * stock.grid_size_x,
-            stock.origin_y + iy * stock.grid_size_y
-          );
+      let heights = stock.heightmap;
+      if (!heights) {
+        heights = new Float32Array(nx * ny);
+        for (let ix = 0; ix < nx; ix++) {
+          for (let iy = 0; iy < ny; iy++) {
+            heights[ix * ny + iy] = stock.get_height(
+              stock.origin_x + ix * stock.grid_size_x,
+              stock.origin_y + iy * stock.grid_size_y
+            );
+          }
         }
       }
       const result = wasm_mod.heightmap_to_solid_mesh_wasm(
@@ -92,7 +167,11 @@ function heightmap_to_solid_mesh(stock, min_z) {
         ny,
         stock.origin_x,
         stock.origin_y,
-        min_z
+        min_z,
+        // Node layers come back as per-vertex arrays of the same name
+        stock.attributes ? stock.attributes.operation_id : undefined,
+        stock.attributes ? stock.attributes.tool_id : undefined,
+        stock.attributes ? stock.attributes.cut_time : undefined
       );
       const THREE = require('three');
       const positions = result.positions;
@@ -102,6 +181,9 @@ function heightmap_to_solid_mesh(stock, min_z) {
       geometry.setAttribute('position', new THREE.Float32BufferAttribute(positions, 3));
       geometry.setAttribute('normal', new THREE.Float32BufferAttribute(normals, 3));
       geometry.setIndex(new THREE.BufferAttribute(indices, 1));
+      if (result.operation_id) geometry.setAttribute('operation_id', new THREE.Uint32BufferAttribute(result.operation_id, 1));
+      if (result.tool_id) geometry.setAttribute('tool_id', new THREE.Uint32BufferAttribute(result.tool_id, 1));
+      if (result.cut_time) geometry.setAttribute('cut_time', new THREE.Float32BufferAttribute(result.cut_time, 1));
       const material = new THREE.MeshPhongMaterial({
         color: 0x229922,
         shininess: 80,
@@ -109,7 +191,14 @@ function heightmap_to_solid_mesh(stock, min_z) {
         side: THREE.DoubleSide,
         transparent: false
       });
-      return new THREE.Mesh(geometry, material);
+      const mesh = new THREE.Mesh(geometry, material);
+      if (stock.heightmap) {
+        // Later cuts patch this mesh through the dirty rects they report
+        stock.mesh = mesh;
+        stock.mesh_min_z = min_z;
+        stock.dirty_rects = new Uint32Array(0);
+      }
+      return mesh;
     } catch (e) {
       console.warn('[stock_simulator] WASM mesh generation failed, falling back to JS:', e);
       alert('[stock_simulator] WASM mesh generation failed, falling back to JS. Check console for details.');
@@ -284,18 +373,34 @@ function simulate_material_removal(stock, tool, toolpath) {
       } else {
         flat_toolpath = new Float32Array(toolpath);
       }
-      wasm_mod.simulate_material_removal_wasm(
+      const stamp = stock.cut_stamp && stock.attributes;
+      // Lowered node rects (flat ix0, iy0, ix1, iy1), accumulated until the next
+      // heightmap_to_solid_mesh patches the cached mesh with them
+      const dirty_rects = wasm_mod.simulate_material_removal_wasm(
         heightmap,
         Math.round(stock.width / stock.grid_size_x) + 1,
         Math.round(stock.height / stock.grid_size_y) + 1,
         stock.grid_size_x,
+        stock.grid_size_y,
         stock.origin_x,
         stock.origin_y,
         tool.type,
         tool.cutter_diameter,
         tool.v_angle || 0,
-        flat_toolpath
+        flat_toolpath,
+        // Per-node layers (Uint32Array / Float32Array, heightmap layout), stamped in place with
+        // stock.cut_stamp; without a stamp nothing is recorded
+        stamp ? stock.cut_stamp.operation_id : 0,
+        stamp ? stock.cut_stamp.tool_id : 0,
+        stamp ? stock.cut_stamp.time : 0,
+        stamp ? stock.attributes.operation_id : undefined,
+        stamp ? stock.attributes.tool_id : undefined,
+        stamp ? stock.attributes.cut_time : undefined
       );
+      const previous_rects = stock.dirty_rects || new Uint32Array(0);
+      stock.dirty_rects = new Uint32Array(previous_rects.length + dirty_rects.length);
+      stock.dirty_rects.set(previous_rects);
+      stock.dirty_rects.set(dirty_rects, previous_rects.length);
       // WASM mutates heightmap in-place; update stock.get_height/set_height if needed
       stock.get_height = function(x, y) {
         const ix = Math.round((x - stock.origin_x) / stock.grid_size_x);
@@ -0,0 +1,344 @@
+// Closed-contour finishing passes with tangential lead-in/lead-out moves
+// All units are mm unless otherwise noted
+//
+// Engaging the wall head-on leaves a dwell mark, so each pass instead approaches
+// along a tangent line and an arc that blends into the contour, and leaves the
+// same way. Leads are placed on the side of the contour chosen by lead_side.
+//
+// Assumptions:
+// - contours are closed loops of (x, y, z) points, the closing point is not repeated
+// - heightmap is the safe surface source, same as generate_safe_toolpath
+// - A lead is only used if the tool at the contour's entry z never dips below the safe
+//   surface along it; otherwise the lead is shrunk, then moved to another entry point,
+//   and finally dropped (plain plunge onto the contour)
+// - Loops are reversed to follow the global cut direction; for Outside leads the wall is inside the loop
+// - Links between passes traverse at max(clearance_z, highest surface point)
+
+use crate::cut_direction::loop_needs_reverse;
+use crate::geometry_2d::{direction, rotate, signed_area, Point2};
+use crate::heightmap::Heightmap;
+use crate::simulate_material_removal::generate_safe_toolpath;
+use crate::toolpath::{densify_path, passes_to_js, split_polylines, surface_max, Pass};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LeadSide {
+  Outside, // leads approach from outside the loop (profiles around a part)
+  Inside,  // leads approach from inside the loop (pockets, holes)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LeadParams {
+  pub lead_in_radius: f32,   // 0 disables the lead-in arc
+  pub lead_in_length: f32,   // straight tangent line before the arc, 0 disables
+  pub lead_out_radius: f32,  // 0 disables the lead-out arc
+  pub lead_out_length: f32,  // straight tangent line after the arc, 0 disables
+  pub arc_angle_deg: f32,    // sweep of each arc, typically 90
+}
+
+pub struct ContourToolpathParams<'a> {
+  pub contours: &'a [Vec<[f32; 3]>],
+  pub heightmap: &'a Heightmap,
+  pub tool_type: &'a str,
+  pub cutter_diameter: f32,
+  pub v_angle_deg: f32,
+  pub lead: LeadParams,
+  pub lead_side: LeadSide,
+  pub clearance_z: f32,
+}
+
+// Chord tolerance for arc flattening
+const ARC_TOLERANCE: f32 = 0.01;
+// Allowed dip of the tool below the safe surface along a lead (grid rounding)
+const COLLISION_TOLERANCE: f32 = 1e-3;
+// How many times a colliding lead is halved before trying another entry point
+const LEAD_SHRINK_STEPS: usize = 3;
+// Entry points tried per contour, evenly spaced around the loop
+const ENTRY_CANDIDATES: usize = 8;
+
+/// Build one pass per contour (lead-in, full loop back to the entry point, lead-out).
+/// Returns the passes and the clearance height used for links.
+pub fn generate_contour_toolpath(params: &ContourToolpathParams) -> (Vec<Pass>, f32) {
+  let clearance_z = params.clearance_z.max(surface_max(&params.heightmap.heights));
+  let mut passes = Vec::with_capacity(params.contours.len());
+  for contour in params.contours {
+    if contour.len() < 2 {
+      continue;
+    }
+    passes.push(contour_pass(params, contour));
+  }
+  (passes, clearance_z)
+}
+
+fn contour_pass(params: &ContourToolpathParams, contour: &[[f32; 3]]) -> Pass {
+  let n = contour.len();
+  let mut xy: Vec<Point2> = contour.iter().map(|p| [p[0], p[1]]).collect();
+  // Outside leads go around a part, so the wall being cut is inside the loop
+  let mut contour = contour.to_vec();
+  if loop_needs_reverse(&xy, params.lead_side == LeadSide::Outside) {
+    contour.reverse();
+    xy.reverse();
+  }
+  let contour = contour.as_slice();
+  // Left of travel is inside for a counter-clockwise loop
+  let ccw = signed_area(&xy) >= 0.0;
+  let side_is_left = ccw == (params.lead_side == LeadSide::Inside);
+
+  let candidates = ENTRY_CANDIDATES.min(n);
+  let mut scale = 1.0;
+  for _ in 0..=LEAD_SHRINK_STEPS {
+    for c in 0..candidates {
+      let start = c * n / candidates;
+      if let Some(pass) = try_pass(params, contour, start, side_is_left, scale) {
+        return pass;
+      }
+    }
+    scale *= 0.5;
+  }
+  // No lead fits anywhere: enter the contour directly
+  let mut pass: Pass = contour.to_vec();
+  pass.push(contour[0]);
+  pass
+}
+
+// Build the pass starting at vertex `start` with leads scaled by `scale`, or None if a lead collides
+fn try_pass(
+  params: &ContourToolpathParams,
+  contour: &[[f32; 3]],
+  start: usize,
+  side_is_left: bool,
+  scale: f32,
+) -> Option<Pass> {
+  let n = contour.len();
+  let entry = contour[start];
+  let entry_xy = [entry[0], entry[1]];
+  // Tangent at the entry point, and at the exit (arriving back at the entry point)
+  let next = contour[(start + 1) % n];
+  let prev = contour[(start + n - 1) % n];
+  let t_in = direction(entry_xy, [next[0], next[1]])?;
+  let t_out = direction([prev[0], prev[1]], entry_xy).unwrap_or(t_in);
+
+  let lead = &params.lead;
+  let lead_in = lead_points(entry_xy, t_in, side_is_left, lead.lead_in_radius * scale, lead.lead_in_length * scale, lead.arc_angle_deg, true);
+  let lead_out = lead_points(entry_xy, t_out, side_is_left, lead.lead_out_radius * scale, lead.lead_out_length * scale, lead.arc_angle_deg, false);
+
+  let z = entry[2];
+  if !lead_is_clear(params, &lead_in, entry_xy, true, z) || !lead_is_clear(params, &lead_out, entry_xy, false, z) {
+    return None;
+  }
+
+  let mut pass: Pass = Vec::with_capacity(n + lead_in.len() + lead_out.len() + 1);
+  pass.extend(lead_in.iter().map(|p| [p[0], p[1], z]));
+  for i in 0..=n {
+    pass.push(contour[(start + i) % n]);
+  }
+  pass.extend(lead_out.iter().map(|p| [p[0], p[1], z]));
+  Some(pass)
+}
+
+// Lead points, excluding the contour point itself.
+// Lead-in: line start -> arc -> (contour point). Lead-out: (contour point) -> arc -> line end.
+fn lead_points(
+  p: Point2,
+  tangent: Point2,
+  side_is_left: bool,
+  radius: f32,
+  length: f32,
+  arc_angle_deg: f32,
+  is_lead_in: bool,
+) -> Vec<Point2> {
+  let mut points = Vec::new();
+  let sweep = if radius > 1e-6 { arc_angle_deg.to_radians().clamp(0.0, std::f32::consts::PI) } else { 0.0 };
+  // Normal toward the lead side; the arc center sits there and the arc turns away from the wall
+  let normal = if side_is_left { [-tangent[1], tangent[0]] } else { [tangent[1], -tangent[0]] };
+  // Rotation sense of travel around the center (+1 = counter-clockwise)
+  let sense = if side_is_left { 1.0 } else { -1.0 };
+  let center = [p[0] + normal[0] * radius, p[1] + normal[1] * radius];
+  let spoke = [-normal[0], -normal[1]];
+  let segments = if sweep > 0.0 {
+    let max_step = 2.0 * (1.0 - ARC_TOLERANCE / radius).clamp(-1.0, 1.0).acos();
+    ((sweep / max_step.max(1e-3)).ceil() as usize).max(2)
+  } else {
+    0
+  };
+  let arc_point = |phi: f32| -> Point2 {
+    let s = rotate(spoke, phi);
+    [center[0] + s[0] * radius, center[1] + s[1] * radius]
+  };
+  // Direction of travel at the far end of the arc
+  let end_tangent = rotate(tangent, if is_lead_in { -sense * sweep } else { sense * sweep });
+
+  if is_lead_in {
+    let arc_start = arc_point(-sense * sweep);
+    if length > 1e-6 {
+      points.push([arc_start[0] - end_tangent[0] * length, arc_start[1] - end_tangent[1] * length]);
+    }
+    for i in 0..segments {
+      let phi = sweep * (1.0 - i as f32 / segments as f32);
+      points.push(arc_point(-sense * phi));
+    }
+  } else {
+    for i in 1..=segments {
+      let phi = sweep * i as f32 / segments as f32;
+      points.push(arc_point(sense * phi));
+    }
+    let arc_end = if segments > 0 { arc_point(sense * sweep) } else { p };
+    if length > 1e-6 {
+      points.push([arc_end[0] + end_tangent[0] * length, arc_end[1] + end_tangent[1] * length]);
+    }
+  }
+  points
+}
+
+// True if the tool at height z stays on or above the safe surface all along the lead, including
+// the move between the lead and the entry point; the path is checked at the grid spacing so a
+// rib between two lead points is not missed
+fn lead_is_clear(params: &ContourToolpathParams, lead: &[Point2], entry: Point2, is_lead_in: bool, z: f32) -> bool {
+  if lead.is_empty() {
+    return true;
+  }
+  let mut path: Vec<[f32; 3]> = lead.iter().map(|p| [p[0], p[1], z]).collect();
+  if is_lead_in {
+    path.push([entry[0], entry[1], z]);
+  } else {
+    path.insert(0, [entry[0], entry[1], z]);
+  }
+  let step = params.heightmap.grid_size_x.min(params.heightmap.grid_size_y);
+  let xy: Vec<(f32, f32)> = densify_path(&path, step).iter().map(|p| (p[0], p[1])).collect();
+  let safe_zs = generate_safe_toolpath(
+    params.heightmap,
+    params.tool_type,
+    params.cutter_diameter,
+    params.v_angle_deg,
+    &xy,
+  );
+  safe_zs.iter().all(|&safe_z| safe_z <= z + COLLISION_TOLERANCE)
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Float32Array, Object, Uint32Array};
+
+#[wasm_bindgen]
+pub fn generate_contour_toolpath_wasm(
+  contour_points_js: &Float32Array, // flat x, y, z for all contours
+  contour_counts_js: &Uint32Array,  // number of points in each contour
+  heightmap_js: &Float32Array,
+  nx: usize,
+  ny: usize,
+  grid_size_x: f32,
+  grid_size_y: f32,
+  origin_x: f32,
+  origin_y: f32,
+  tool_type: &str,
+  cutter_diameter: f32,
+  v_angle_deg: f32,
+  lead_in_radius: f32,
+  lead_in_length: f32,
+  lead_out_radius: f32,
+  lead_out_length: f32,
+  arc_angle_deg: f32,
+  lead_outside: bool,
+  clearance_z: f32,
+) -> Object {
+  let contours = split_polylines::<3>(&contour_points_js.to_vec(), &contour_counts_js.to_vec());
+  let heightmap = Heightmap::from_vec(nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, heightmap_js.to_vec());
+  let (passes, clearance_z) = generate_contour_toolpath(&ContourToolpathParams {
+    contours: &contours,
+    heightmap: &heightmap,
+    tool_type,
+    cutter_diameter,
+    v_angle_deg,
+    lead: LeadParams { lead_in_radius, lead_in_length, lead_out_radius, lead_out_length, arc_angle_deg },
+    lead_side: if lead_outside { LeadSide::Outside } else { LeadSide::Inside },
+    clearance_z,
+  });
+  passes_to_js(&passes, clearance_z)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn square_contour(z: f32) -> Vec<[f32; 3]> {
+    // counter-clockwise 10x10 square centered at (10, 10)
+    vec![[5.0, 5.0, z], [15.0, 5.0, z], [15.0, 15.0, z], [5.0, 15.0, z]]
+  }
+
+  fn params<'a>(contours: &'a [Vec<[f32; 3]>], heightmap: &'a Heightmap) -> ContourToolpathParams<'a> {
+    ContourToolpathParams {
+      contours,
+      heightmap,
+      tool_type: "flat",
+      cutter_diameter: 2.0,
+      v_angle_deg: 0.0,
+      lead: LeadParams { lead_in_radius: 2.0, lead_in_length: 1.0, lead_out_radius: 2.0, lead_out_length: 1.0, arc_angle_deg: 90.0 },
+      lead_side: LeadSide::Outside,
+      clearance_z: 5.0,
+    }
+  }
+
+  #[test]
+  fn lead_in_arc_is_tangent_and_outside() {
+    let contours = vec![square_contour(0.0)];
+    let n = 41;
+    let heightmap = Heightmap::new(n, n, 0.5, 0.5, 0.0, 0.0, -1.0);
+    let (passes, clearance_z) = generate_contour_toolpath(&params(&contours, &heightmap));
+    assert_eq!(passes.len(), 1);
+    assert_eq!(clearance_z, 5.0);
+    let pass = &passes[0];
+    // Entry at (5, 5) travelling +x; outside of a CCW loop is -y
+    let entry = pass.iter().position(|p| p[0] == 5.0 && p[1] == 5.0).unwrap();
+    assert!(entry > 2);
+    for p in &pass[..entry] {
+      assert!(p[1] < 5.0 + 1e-4, "lead-in point {:?} is inside the contour", p);
+    }
+    // The last lead-in point approaches along +x
+    let before = pass[entry - 1];
+    let dir = direction([before[0], before[1]], [5.0, 5.0]).unwrap();
+    assert!(dir[0] > 0.95, "lead-in is not tangent: {:?}", dir);
+    // Lead-in starts 2 mm out from the wall (radius) plus the tangent line, which runs along +y
+    assert!((pass[0][1] - (5.0 - 2.0 - 1.0)).abs() < 1e-3);
+    assert!((pass[0][0] - (5.0 - 2.0)).abs() < 1e-3);
+  }
+
+  #[test]
+  fn colliding_lead_is_shrunk_or_dropped() {
+    let contours = vec![square_contour(0.0)];
+    let n = 41;
+    // Tall wall all around the outside of the square, hugging the contour
+    let mut heightmap = Heightmap::new(n, n, 0.5, 0.5, 0.0, 0.0, -1.0);
+    for ix in 0..n {
+      for iy in 0..n {
+        let x = ix as f32 * 0.5;
+        let y = iy as f32 * 0.5;
+        if !(3.5..=16.5).contains(&x) || !(3.5..=16.5).contains(&y) {
+          heightmap.set(ix, iy, 10.0);
+        }
+      }
+    }
+    let p = params(&contours, &heightmap);
+    let (passes, clearance_z) = generate_contour_toolpath(&p);
+    assert!(clearance_z >= 10.0);
+    let xy: Vec<(f32, f32)> = passes[0].iter().map(|q| (q[0], q[1])).collect();
+    let safe_zs = generate_safe_toolpath(&heightmap, "flat", 2.0, 0.0, &xy);
+    for (q, safe_z) in passes[0].iter().zip(safe_zs) {
+      if !(5.0..=15.0).contains(&q[0]) || !(5.0..=15.0).contains(&q[1]) {
+        assert!(safe_z <= q[2] + COLLISION_TOLERANCE, "lead point {:?} gouges the wall", q);
+      }
+    }
+  }
+
+  #[test]
+  fn rib_between_lead_points_blocks_the_lead() {
+    // A thin rib across y = 10, crossed by a straight lead from (2, 2) to the entry at (2, 18)
+    let mut heightmap = Heightmap::new(41, 41, 0.5, 0.5, 0.0, 0.0, -1.0);
+    for ix in 0..41 {
+      heightmap.set(ix, 20, 10.0);
+    }
+    let contours = vec![square_contour(0.0)];
+    let mut p = params(&contours, &heightmap);
+    p.cutter_diameter = 1.0;
+    assert!(!lead_is_clear(&p, &[[2.0, 2.0]], [2.0, 18.0], true, 0.0));
+    assert!(!lead_is_clear(&p, &[[2.0, 2.0]], [2.0, 18.0], false, 0.0));
+    assert!(lead_is_clear(&p, &[[2.0, 2.0]], [2.0, 8.0], true, 0.0));
+  }
+}
@@ -0,0 +1,248 @@
+// Climb vs conventional cut direction: a global setting for the generators, and
+// classification of existing moves against the stock
+// All units are mm unless otherwise noted
+//
+// With the spindle turning clockwise (M3), a move is climb milling when the material it cuts
+// is on the right of the direction of travel, and conventional when it is on the left. Side
+// cutting generators read the global setting and orient their loops and raster rows to match;
+// moves with material on both sides (slots) or neither (air) have no direction to choose.
+//
+// Assumptions:
+// - The setting is per thread (one kernel instance per wasm module), default Either, which
+//   leaves every generator's natural order untouched
+// - Classification walks the toolpath in order on a copy of the stock, checking material just
+//   inside the cutter edge ahead of each move on both sides, then cuts the move, so each move
+//   sees the stock left by the moves before it
+// - The stock is any StockModel; points off the stock have no material
+// - Vertical moves (plunges, retracts) are not classified
+
+use std::cell::Cell;
+
+use crate::geometry_2d::{signed_area, Point2};
+use crate::stock_model::{StockModel, ToolShape};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CutDirection {
+  Climb,
+  Conventional,
+  Either,
+}
+
+thread_local! {
+  static CUT_DIRECTION: Cell<CutDirection> = const { Cell::new(CutDirection::Either) };
+}
+
+/// Set the cut direction every side cutting generator honors
+pub fn set_cut_direction(direction: CutDirection) {
+  CUT_DIRECTION.with(|d| d.set(direction));
+}
+
+/// Current global cut direction
+pub fn cut_direction() -> CutDirection {
+  CUT_DIRECTION.with(|d| d.get())
+}
+
+/// Whether a closed loop must be reversed to match the global cut direction.
+/// material_inside: the material being cut (or the wall being finished) is inside the loop.
+pub fn loop_needs_reverse(xy: &[Point2], material_inside: bool) -> bool {
+  // Climb keeps material on the right: clockwise around it, counter-clockwise inside a pocket wall
+  let want_ccw = match cut_direction() {
+    CutDirection::Climb => !material_inside,
+    CutDirection::Conventional => material_inside,
+    CutDirection::Either => return false,
+  };
+  (signed_area(xy) >= 0.0) != want_ccw
+}
+
+/// Row travel for a raster that advances in +y into uncut material: Some(true) for rows run
+/// toward -x, Some(false) toward +x, None to zig-zag
+pub fn raster_row_reversed() -> Option<bool> {
+  match cut_direction() {
+    // Uncut material at +y is on the right when moving toward -x
+    CutDirection::Climb => Some(true),
+    CutDirection::Conventional => Some(false),
+    CutDirection::Either => None,
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CutKind {
+  Air,
+  Climb,
+  Conventional,
+  Slot,
+  Vertical,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CutMix {
+  pub kinds: Vec<CutKind>, // one per move (toolpath.len() - 1)
+  pub climb: f32,          // length of each kind of move
+  pub conventional: f32,
+  pub slot: f32,
+  pub air: f32,
+}
+
+/// Classify each move as climb, conventional, slot or air against the stock, cutting as it goes
+pub fn classify_cuts<S: StockModel + Clone>(stock: &S, tool: &ToolShape, toolpath: &[[f32; 3]]) -> CutMix {
+  let mut stock = stock.clone();
+  let r = tool.cutter_diameter / 2.0;
+  let mut mix = CutMix::default();
+  for w in toolpath.windows(2) {
+    let (a, b) = (w[0], w[1]);
+    let length = (b[0] - a[0]).hypot(b[1] - a[1]);
+    if length < 1e-6 {
+      mix.kinds.push(CutKind::Vertical);
+      continue;
+    }
+    // Material on each side of the travel direction, where the cutter edge at the end point
+    // reaches past the swept circle at the start point
+    let (fx, fy) = ((b[0] - a[0]) / length, (b[1] - a[1]) / length);
+    let z = a[2].max(b[2]);
+    let d = r * 0.95;
+    let surface = z + tool.profile(d).unwrap_or(0.0) + 1e-2;
+    let side = |stock: &S, sign: f32| {
+      [30.0f32, 60.0].iter().any(|&angle| {
+        let (c, s) = (angle.to_radians().cos(), -sign * angle.to_radians().sin());
+        let (x, y) = (b[0] + d * (fx * c - fy * s), b[1] + d * (fx * s + fy * c));
+        (x - a[0]).hypot(y - a[1]) > r && stock.height_at(x, y).is_some_and(|h| h > surface)
+      })
+    };
+    let kind = match (side(&stock, 1.0), side(&stock, -1.0)) {
+      (true, true) => CutKind::Slot,
+      (true, false) => CutKind::Climb,
+      (false, true) => CutKind::Conventional,
+      (false, false) => CutKind::Air,
+    };
+    match kind {
+      CutKind::Climb => mix.climb += length,
+      CutKind::Conventional => mix.conventional += length,
+      CutKind::Slot => mix.slot += length,
+      _ => mix.air += length,
+    }
+    mix.kinds.push(kind);
+    stock.remove_swept_tool(tool, &[a, b]);
+  }
+  mix
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Float32Array, Object, Uint8Array};
+use crate::heightmap::Heightmap;
+
+/// direction: "climb", "conventional" or anything else for either
+#[wasm_bindgen]
+pub fn set_cut_direction_wasm(direction: &str) {
+  set_cut_direction(match direction {
+    "climb" => CutDirection::Climb,
+    "conventional" => CutDirection::Conventional,
+    _ => CutDirection::Either,
+  });
+}
+
+/// Returns { kinds: Uint8Array (0 air, 1 climb, 2 conventional, 3 slot, 4 vertical), climb,
+/// conventional, slot, air } with lengths in mm
+#[wasm_bindgen]
+pub fn classify_cuts_wasm(
+  stock_js: &Float32Array,
+  nx: usize,
+  ny: usize,
+  grid_size_x: f32,
+  grid_size_y: f32,
+  origin_x: f32,
+  origin_y: f32,
+  tool_type: &str,
+  cutter_diameter: f32,
+  v_angle_deg: f32,
+  toolpath_js: &Float32Array, // flat x, y, z
+) -> Object {
+  let stock = Heightmap::from_vec(nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, stock_js.to_vec());
+  let toolpath: Vec<[f32; 3]> = toolpath_js.to_vec().chunks_exact(3).map(|p| [p[0], p[1], p[2]]).collect();
+  let mix = classify_cuts(&stock, &ToolShape { tool_type, cutter_diameter, v_angle_deg }, &toolpath);
+  let kinds: Vec<u8> = mix.kinds.iter().map(|&k| k as u8).collect();
+  let result = Object::new();
+  js_sys::Reflect::set(&result, &"kinds".into(), &Uint8Array::from(kinds.as_slice())).unwrap();
+  js_sys::Reflect::set(&result, &"climb".into(), &mix.climb.into()).unwrap();
+  js_sys::Reflect::set(&result, &"conventional".into(), &mix.conventional.into()).unwrap();
+  js_sys::Reflect::set(&result, &"slot".into(), &mix.slot.into()).unwrap();
+  js_sys::Reflect::set(&result, &"air".into(), &mix.air.into()).unwrap();
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cutout_toolpath::{generate_cutout_toolpath, CutoutParams, TabParams, TabShape};
+  use crate::flatten_toolpath::{generate_flatten_toolpath, FlattenArea, FlattenParams};
+  use crate::heightmap_to_solid_mesh::create_heightmap_stock;
+  use crate::toolpath::link_passes;
+
+  // 41 x 41 stock of 0.5 mm cells over [0, 20]^2, top at 0, 6 mm flat end mill
+  fn classify(toolpath: &[[f32; 3]]) -> CutMix {
+    let stock = create_heightmap_stock(20.0, 20.0, 41, 41, 0.0, 0.0, 0.0);
+    classify_cuts(&stock, &ToolShape { tool_type: "flat", cutter_diameter: 6.0, v_angle_deg: 0.0 }, toolpath)
+  }
+
+  #[test]
+  fn classifies_slot_then_side_cuts() {
+    // Slot along y = 6, then a stepover into the material at +y, once each way
+    let forward = classify(&[[2.0, 6.0, -1.0], [18.0, 6.0, -1.0], [18.0, 8.0, -1.0], [2.0, 8.0, -1.0]]);
+    assert_eq!(forward.kinds[0], CutKind::Slot);
+    assert_eq!(forward.kinds[2], CutKind::Climb);
+    // The stepover move pushes straight into the material
+    assert_eq!(forward.kinds[1], CutKind::Slot);
+    assert!((forward.slot - 18.0).abs() < 1e-4 && (forward.climb - 16.0).abs() < 1e-4, "{:?}", forward);
+    let reverse = classify(&[[18.0, 6.0, -1.0], [2.0, 6.0, -1.0], [2.0, 8.0, -1.0], [18.0, 8.0, -1.0]]);
+    assert_eq!(reverse.kinds[2], CutKind::Conventional);
+    // Above the stock nothing is cut
+    assert_eq!(classify(&[[2.0, 6.0, 1.0], [18.0, 6.0, 1.0]]).kinds, vec![CutKind::Air]);
+  }
+
+  #[test]
+  fn facing_rows_follow_setting() {
+    let params = FlattenParams {
+      area: FlattenArea::Rect { min: [0.0, 0.0], max: [20.0, 20.0] },
+      cutter_diameter: 6.0,
+      stepover_percent: 50.0,
+      overshoot: 3.0,
+      top_z: 0.0,
+      target_z: -1.0,
+      stepdown: 0.0,
+      sample_step: 0.5,
+    };
+    for (direction, climb) in [(CutDirection::Climb, true), (CutDirection::Conventional, false)] {
+      set_cut_direction(direction);
+      let passes = generate_flatten_toolpath(&params);
+      let mix = classify(&link_passes(&passes, 5.0));
+      let (wanted, other) = if climb { (mix.climb, mix.conventional) } else { (mix.conventional, mix.climb) };
+      assert!(wanted > 100.0 && other < 1.0, "{:?}: {:?}", direction, (mix.climb, mix.conventional, mix.slot));
+    }
+    // Either keeps the zig-zag, which mixes both
+    set_cut_direction(CutDirection::Either);
+    let mix = classify(&link_passes(&generate_flatten_toolpath(&params), 5.0));
+    assert!(mix.climb > 50.0 && mix.conventional > 50.0);
+  }
+
+  #[test]
+  fn cutout_loops_climb_around_part() {
+    let outlines = vec![
+      vec![[5.0, 5.0], [15.0, 5.0], [15.0, 15.0], [5.0, 15.0]],
+      vec![[8.0, 8.0], [12.0, 8.0], [12.0, 12.0], [8.0, 12.0]],
+    ];
+    set_cut_direction(CutDirection::Climb);
+    let passes = generate_cutout_toolpath(&CutoutParams {
+      outlines: &outlines,
+      cutter_diameter: 1.0,
+      top_z: 0.0,
+      bottom_z: -1.0,
+      max_stepdown: 0.0,
+      tabs: TabParams { count: 0, width: 0.0, height: 0.0, shape: TabShape::Rectangular },
+      resolution: 0.1,
+    });
+    set_cut_direction(CutDirection::Either);
+    let area = |p: &Vec<[f32; 3]>| signed_area(&p.iter().map(|q| [q[0], q[1]]).collect::<Vec<Point2>>());
+    // Hole first: counter-clockwise inside the hole, then clockwise around the outside
+    assert!(area(&passes[0]) > 0.0);
+    assert!(area(&passes[1]) < 0.0);
+  }
+}
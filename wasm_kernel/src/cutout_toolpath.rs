@@ -0,0 +1,298 @@
+// Profile cutout of a part silhouette, with holding tabs on the final passes
+// All units are mm unless otherwise noted
+//
+// The silhouette is offset outward by the tool radius (holes shrink by the same amount),
+// then each loop is cut in equal step-downs from top_z to bottom_z. Once a pass goes
+// below the top of the tabs, the tool lifts over each tab.
+//
+// Assumptions:
+// - outlines are closed (x, y) loops, even-odd filled, so holes are loops nested inside the outer loop
+// - Holes are cut before outer loops so the part stays held while they are machined
+// - Each loop is one pass: the tool steps down at the loop start without retracting
+// - Loops are reversed to follow the global cut direction
+// - Tab width is measured at the base of the tab material; the tool center path is
+//   widened by the tool radius so the cutter edge clears the tab
+
+use crate::cut_direction::loop_needs_reverse;
+use crate::geometry_2d::{loop_length, offset_loops, point_in_loops, Point2};
+use crate::toolpath::{passes_to_js, split_polylines, Pass};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TabShape {
+  Rectangular, // vertical sides, flat top
+  Triangular,  // ramps up to a peak, easier on the tool than a vertical climb
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TabParams {
+  pub count: usize,  // tabs per loop, 0 disables tabs
+  pub width: f32,    // width of the tab material along the loop
+  pub height: f32,   // height of the tab above bottom_z
+  pub shape: TabShape,
+}
+
+pub struct CutoutParams<'a> {
+  pub outlines: &'a [Vec<Point2>],
+  pub cutter_diameter: f32,
+  pub top_z: f32,        // stock top, the first pass cuts one step below this
+  pub bottom_z: f32,     // final depth, usually a little below the stock bottom
+  pub max_stepdown: f32, // maximum depth of cut per pass
+  pub tabs: TabParams,
+  pub resolution: f32,   // sampling resolution for the outline offset
+}
+
+/// Generate one pass per offset loop, holes first
+pub fn generate_cutout_toolpath(params: &CutoutParams) -> Vec<Pass> {
+  let r = params.cutter_diameter / 2.0;
+  let mut loops = offset_loops(params.outlines, r, params.resolution);
+  // Degenerate loops have nowhere to put tabs (and nothing to cut)
+  loops.retain(|l| loop_length(l) > 0.0);
+  // Holes are nested inside another loop: cut them first
+  let mut order: Vec<usize> = (0..loops.len()).collect();
+  order.sort_by_key(|&i| !is_nested(i, &loops));
+
+  let depth = (params.top_z - params.bottom_z).max(0.0);
+  let steps = if params.max_stepdown > 0.0 { (depth / params.max_stepdown).ceil().max(1.0) as usize } else { 1 };
+  let depths: Vec<f32> = (1..=steps).map(|k| params.top_z - depth * k as f32 / steps as f32).collect();
+
+  order
+    .iter()
+    .map(|&i| {
+      // The part is inside outer loops and outside holes
+      let mut l = loops[i].clone();
+      if loop_needs_reverse(&l, !is_nested(i, &loops)) {
+        l.reverse();
+      }
+      let l = &l;
+      let mut pass = Pass::new();
+      for &z in &depths {
+        pass.extend(loop_at_depth(l, z, params, r));
+      }
+      pass
+    })
+    .collect()
+}
+
+// Odd nesting depth: loop i is a hole
+fn is_nested(i: usize, loops: &[Vec<Point2>]) -> bool {
+  let others: Vec<Vec<Point2>> = loops.iter().enumerate().filter(|(j, _)| *j != i).map(|(_, l)| l.clone()).collect();
+  point_in_loops(loops[i][0], &others)
+}
+
+// Tab profile along the loop: height of the tool tip above which it must stay at arc length s
+fn tab_floor(s: f32, centers: &[f32], perimeter: f32, params: &CutoutParams, r: f32) -> f32 {
+  let tabs = &params.tabs;
+  let top = params.bottom_z + tabs.height;
+  let mut floor = f32::NEG_INFINITY;
+  for &c in centers {
+    // Distance along the loop, wrapping around the start point
+    let d = (s - c).abs();
+    let d = d.min(perimeter - d);
+    let z = match tabs.shape {
+      TabShape::Rectangular => if d <= tabs.width / 2.0 + r { top } else { f32::NEG_INFINITY },
+      TabShape::Triangular => {
+        // Flat for the tool radius so the peak is left at full height, then a ramp to the base
+        let ramp = tabs.width / 2.0;
+        if d <= r {
+          top
+        } else if d <= r + ramp && ramp > 0.0 {
+          top - tabs.height * (d - r) / ramp
+        } else {
+          f32::NEG_INFINITY
+        }
+      }
+    };
+    floor = floor.max(z);
+  }
+  floor
+}
+
+// One lap of the loop at depth z (closing back at the first point), lifted over tabs
+fn loop_at_depth(points: &[Point2], z: f32, params: &CutoutParams, r: f32) -> Pass {
+  let n = points.len();
+  let tabs = &params.tabs;
+  let tab_top = params.bottom_z + tabs.height;
+  if tabs.count == 0 || tabs.height <= 0.0 || z >= tab_top {
+    let mut lap: Pass = points.iter().map(|p| [p[0], p[1], z]).collect();
+    lap.push([points[0][0], points[0][1], z]);
+    return lap;
+  }
+  let perimeter = loop_length(points);
+  if perimeter <= 0.0 || !perimeter.is_finite() {
+    let mut lap: Pass = points.iter().map(|p| [p[0], p[1], z]).collect();
+    lap.extend(points.first().map(|p| [p[0], p[1], z]));
+    return lap;
+  }
+  let spacing = perimeter / tabs.count as f32;
+  // Tabs are evenly spaced, half a spacing from the loop start
+  let centers: Vec<f32> = (0..tabs.count).map(|i| (i as f32 + 0.5) * spacing).collect();
+  // Arc lengths where the profile changes: tab edges and ramp ends
+  let half = tabs.width / 2.0 + r;
+  let mut breaks: Vec<f32> = Vec::new();
+  for &c in &centers {
+    let mut edges = vec![c - half, c + half];
+    if tabs.shape == TabShape::Triangular {
+      edges.extend_from_slice(&[c - r, c + r]);
+      // Where the ramp meets the pass depth
+      if tabs.height > 0.0 {
+        let d = r + tabs.width / 2.0 * (tab_top - z) / tabs.height;
+        edges.extend_from_slice(&[c - d, c + d]);
+      }
+    }
+    for e in edges {
+      breaks.push(e.rem_euclid(perimeter));
+    }
+  }
+  breaks.sort_by(f32::total_cmp);
+
+  let height_at = |s: f32| z.max(tab_floor(s, &centers, perimeter, params, r));
+  let mut lap = Pass::new();
+  let mut push = |p: Point2, z: f32| {
+    if lap.last() != Some(&[p[0], p[1], z]) {
+      lap.push([p[0], p[1], z]);
+    }
+  };
+  let eps = 1e-4;
+  let mut s0 = 0.0;
+  let mut b = 0;
+  for i in 0..n {
+    let a = points[i];
+    let c = points[(i + 1) % n];
+    let len = ((c[0] - a[0]).powi(2) + (c[1] - a[1]).powi(2)).sqrt();
+    push(a, height_at(s0));
+    while b < breaks.len() && breaks[b] < s0 + len {
+      let s = breaks[b];
+      if s > s0 {
+        let t = (s - s0) / len;
+        let p = [a[0] + (c[0] - a[0]) * t, a[1] + (c[1] - a[1]) * t];
+        // Both sides of a break, so rectangular tabs get vertical steps
+        push(p, height_at(s - eps));
+        push(p, height_at(s + eps));
+      }
+      b += 1;
+    }
+    s0 += len;
+  }
+  push(points[0], height_at(perimeter));
+  lap
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Float32Array, Object, Uint32Array};
+
+#[wasm_bindgen]
+pub fn generate_cutout_toolpath_wasm(
+  outline_points_js: &Float32Array, // flat x, y for all loops
+  outline_counts_js: &Uint32Array,  // number of points in each loop
+  cutter_diameter: f32,
+  top_z: f32,
+  bottom_z: f32,
+  max_stepdown: f32,
+  tab_count: usize,
+  tab_width: f32,
+  tab_height: f32,
+  tab_shape: &str, // "rectangular" or "triangular"
+  resolution: f32,
+  clearance_z: f32,
+) -> Object {
+  let outlines = split_polylines::<2>(&outline_points_js.to_vec(), &outline_counts_js.to_vec());
+  let passes = generate_cutout_toolpath(&CutoutParams {
+    outlines: &outlines,
+    cutter_diameter,
+    top_z,
+    bottom_z,
+    max_stepdown,
+    tabs: TabParams {
+      count: tab_count,
+      width: tab_width,
+      height: tab_height,
+      shape: if tab_shape == "triangular" { TabShape::Triangular } else { TabShape::Rectangular },
+    },
+    resolution,
+  });
+  passes_to_js(&passes, clearance_z.max(top_z))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn params(outlines: &[Vec<Point2>], shape: TabShape) -> CutoutParams<'_> {
+    CutoutParams {
+      outlines,
+      cutter_diameter: 3.175,
+      top_z: 0.0,
+      bottom_z: -6.5,
+      max_stepdown: 2.0,
+      tabs: TabParams { count: 4, width: 5.0, height: 2.0, shape },
+      resolution: 0.05,
+    }
+  }
+
+  #[test]
+  fn cutout_offsets_by_tool_radius_and_steps_down() {
+    let outlines = vec![vec![[0.0, 0.0], [40.0, 0.0], [40.0, 20.0], [0.0, 20.0]]];
+    let passes = generate_cutout_toolpath(&params(&outlines, TabShape::Rectangular));
+    assert_eq!(passes.len(), 1);
+    let pass = &passes[0];
+    let r = 3.175 / 2.0;
+    // Tool center never comes closer than the radius to the part
+    for p in pass {
+      let dx = (-p[0]).max(p[0] - 40.0).max(0.0);
+      let dy = (-p[1]).max(p[1] - 20.0).max(0.0);
+      let d = (dx * dx + dy * dy).sqrt();
+      assert!((d - r).abs() < 0.02, "tool center {:?} is {} from the part", p, d);
+    }
+    // 6.5 mm at 2 mm max stepdown takes 4 passes of 1.625 mm
+    let mut zs: Vec<f32> = pass.iter().map(|p| p[2]).filter(|&z| z < -4.5 + 1e-3).collect();
+    zs.sort_by(f32::total_cmp);
+    assert!((zs[0] + 6.5).abs() < 1e-4);
+    assert!(pass.iter().all(|p| p[2] < 0.0));
+  }
+
+  #[test]
+  fn final_passes_lift_over_tabs() {
+    let outlines = vec![vec![[0.0, 0.0], [40.0, 0.0], [40.0, 20.0], [0.0, 20.0]]];
+    for shape in [TabShape::Rectangular, TabShape::Triangular] {
+      let passes = generate_cutout_toolpath(&params(&outlines, shape));
+      let tab_top = -6.5 + 2.0;
+      // Four tab peaks on the final lap
+      let final_lap: Vec<&[f32; 3]> = passes[0].iter().skip_while(|p| p[2] > -6.5 + 1e-4).collect();
+      let mut peaks = 0;
+      let mut on_tab = false;
+      for p in final_lap {
+        let at_top = (p[2] - tab_top).abs() < 1e-4;
+        if at_top && !on_tab {
+          peaks += 1;
+        }
+        on_tab = at_top;
+      }
+      assert_eq!(peaks, 4, "{:?} tabs", shape);
+    }
+  }
+
+  #[test]
+  fn holes_are_cut_first() {
+    let outlines = vec![
+      vec![[0.0, 0.0], [40.0, 0.0], [40.0, 40.0], [0.0, 40.0]],
+      vec![[15.0, 15.0], [15.0, 25.0], [25.0, 25.0], [25.0, 15.0]],
+    ];
+    let mut p = params(&outlines, TabShape::Rectangular);
+    p.tabs.count = 0;
+    let passes = generate_cutout_toolpath(&p);
+    assert_eq!(passes.len(), 2);
+    let first = passes[0][0];
+    assert!(first[0] > 15.0 && first[0] < 25.0 && first[1] > 15.0 && first[1] < 25.0);
+  }
+
+  #[test]
+  fn degenerate_loops_do_not_panic() {
+    let outlines = vec![vec![[5.0, 5.0], [5.0, 5.0], [5.0, 5.0]]];
+    let p = params(&outlines, TabShape::Triangular);
+    assert!(generate_cutout_toolpath(&p).iter().all(|pass| pass.iter().all(|q| q.iter().all(|v| v.is_finite()))));
+    // A zero-length lap at tab depth is just the point, no tab breaks
+    let lap = loop_at_depth(&outlines[0], -6.0, &p, 1.0);
+    assert_eq!(lap.len(), 4);
+    assert!(lap.iter().all(|q| q[2] == -6.0));
+  }
+}
@@ -0,0 +1,424 @@
+// Multi-segment dexel stock: material as z intervals on a grid of vertical rays
+// All units are mm unless otherwise noted
+//
+// Each grid node holds a sorted list of (bottom, top) segments where its vertical ray is
+// inside material. Unlike a heightmap, a ray can hold several segments, so overhangs and
+// undercuts left by cuts from the side (or from below, after a flip) are represented.
+// A tool can point along any axis; each tool position is a solid (cylinder, ball end or
+// cone plus shank) whose intersection with every ray is found exactly and subtracted.
+//
+// Assumptions:
+// - Node (ix, iy) is at origin + (ix * cell_x, iy * cell_y) and stands for the cell around it
+// - Tool axis points from the tip toward the shank; the solid is cut off at `length` from the tip
+// - Sweeps are sampled at positions no more than half a cell apart; each sample is subtracted
+// - The mesh is blocky: each segment becomes a box over its cell, with side walls only where
+//   the neighboring ray has no material, so the mesh is closed and its volume matches volume()
+
+use crate::heightmap::grid_index;
+use crate::heightmap_to_solid_mesh::Mesh;
+use crate::stock_model::{StockModel, ToolShape};
+use crate::toolpath::densify_path;
+
+#[derive(Clone, Debug)]
+pub struct DexelStock {
+  pub nx: usize,
+  pub ny: usize,
+  pub cell_x: f32,
+  pub cell_y: f32,
+  pub origin_x: f32,
+  pub origin_y: f32,
+  pub segments: Vec<Vec<(f32, f32)>>, // per node (ix * ny + iy), sorted bottom to top
+}
+
+// Solution of a z^2 + b z + c <= 0 as up to two intervals
+pub fn quadratic_le_zero(a: f32, b: f32, c: f32) -> Vec<(f32, f32)> {
+  const INF: f32 = f32::INFINITY;
+  if a.abs() < 1e-9 {
+    if b.abs() < 1e-9 {
+      return if c <= 0.0 { vec![(-INF, INF)] } else { Vec::new() };
+    }
+    let z = -c / b;
+    return if b > 0.0 { vec![(-INF, z)] } else { vec![(z, INF)] };
+  }
+  let disc = b * b - 4.0 * a * c;
+  if disc < 0.0 {
+    return if a < 0.0 { vec![(-INF, INF)] } else { Vec::new() };
+  }
+  let s = disc.sqrt();
+  let (z0, z1) = ((-b - s) / (2.0 * a), (-b + s) / (2.0 * a));
+  let (z0, z1) = (z0.min(z1), z0.max(z1));
+  if a > 0.0 { vec![(z0, z1)] } else { vec![(-INF, z0), (z1, INF)] }
+}
+
+// Hull of the parts of `pieces` inside `within`; the solids are convex, so this is exact
+pub fn clip_hull(pieces: Vec<(f32, f32)>, within: (f32, f32)) -> Option<(f32, f32)> {
+  pieces
+    .into_iter()
+    .map(|(a, b)| (a.max(within.0), b.min(within.1)))
+    .filter(|(a, b)| a <= b)
+    .reduce(|x, y| (x.0.min(y.0), x.1.max(y.1)))
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+  a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+// One convex piece of a tool: points at axial distance t in [t0, t1] from `base` along `axis`,
+// within radius r0 + (t - t0) * slope of the axis
+struct Frustum {
+  base: [f32; 3],
+  axis: [f32; 3],
+  t0: f32,
+  t1: f32,
+  r0: f32,
+  slope: f32,
+}
+
+impl Frustum {
+  // z interval where the vertical ray at (x, y) is inside the piece
+  fn ray(&self, x: f32, y: f32) -> Option<(f32, f32)> {
+    let w = [x - self.base[0], y - self.base[1], -self.base[2]];
+    let a = self.axis;
+    // Axial distance t(z) = wa + z * a[2]; radial offset u + z v
+    let wa = dot(w, a);
+    let u = [w[0] - wa * a[0], w[1] - wa * a[1], w[2] - wa * a[2]];
+    let v = [-a[2] * a[0], -a[2] * a[1], 1.0 - a[2] * a[2]];
+    // |u + z v|^2 <= (r0 + (t(z) - t0) slope)^2
+    let k = self.r0 + (wa - self.t0) * self.slope; // radius at z = 0
+    let m = a[2] * self.slope; // change of radius with z
+    let pieces = quadratic_le_zero(dot(v, v) - m * m, 2.0 * (dot(u, v) - k * m), dot(u, u) - k * k);
+    // t0 <= t(z) <= t1, and a non-negative radius
+    let mut within = (f32::NEG_INFINITY, f32::INFINITY);
+    let mut bound = |coef: f32, rhs_lo: f32, rhs_hi: f32| {
+      if coef.abs() < 1e-9 {
+        if rhs_lo > 0.0 || rhs_hi < 0.0 {
+          within = (1.0, 0.0);
+        }
+      } else {
+        let (p, q) = (rhs_lo / coef, rhs_hi / coef);
+        within = (within.0.max(p.min(q)), within.1.min(p.max(q)));
+      }
+    };
+    bound(a[2], self.t0 - wa, self.t1 - wa);
+    clip_hull(pieces, within)
+  }
+}
+
+// A ball end is a sphere at the tip plus a cylinder
+fn sphere_ray(center: [f32; 3], r: f32, x: f32, y: f32) -> Option<(f32, f32)> {
+  let d2 = (x - center[0]).powi(2) + (y - center[1]).powi(2);
+  if d2 > r * r {
+    return None;
+  }
+  let h = (r * r - d2).sqrt();
+  Some((center[2] - h, center[2] + h))
+}
+
+impl DexelStock {
+  /// Solid box of material from bottom_z to top_z over an nx by ny grid
+  pub fn new_box(origin_x: f32, origin_y: f32, nx: usize, ny: usize, cell_x: f32, cell_y: f32, bottom_z: f32, top_z: f32) -> DexelStock {
+    DexelStock { nx, ny, cell_x, cell_y, origin_x, origin_y, segments: vec![vec![(bottom_z, top_z)]; nx * ny] }
+  }
+
+  // Subtract the interval (lo, hi) from node k
+  fn subtract(&mut self, k: usize, lo: f32, hi: f32) {
+    let segments = &mut self.segments[k];
+    if !segments.iter().any(|&(a, b)| a < hi && b > lo) {
+      return;
+    }
+    let mut kept = Vec::with_capacity(segments.len() + 1);
+    for &(a, b) in segments.iter() {
+      if b <= lo || a >= hi {
+        kept.push((a, b));
+        continue;
+      }
+      if a < lo {
+        kept.push((a, lo));
+      }
+      if b > hi {
+        kept.push((hi, b));
+      }
+    }
+    *segments = kept;
+  }
+
+  /// Remove the material swept by a tool whose axis points along `axis` (tip toward shank),
+  /// with `length` of the tool below the holder able to cut
+  pub fn remove_swept_tool_along(&mut self, tool: &ToolShape, toolpath: &[[f32; 3]], axis: [f32; 3], length: f32) {
+    let n = dot(axis, axis).sqrt();
+    if n < 1e-9 || toolpath.is_empty() {
+      return;
+    }
+    let a = [axis[0] / n, axis[1] / n, axis[2] / n];
+    let r = tool.cutter_diameter / 2.0;
+    let tan_half = (tool.v_angle_deg.to_radians() / 2.0).tan().max(1e-6);
+    let dense = densify_path(toolpath, self.cell_x.min(self.cell_y) / 2.0);
+    for p in dense {
+      // Pieces of the tool at this position
+      let mut pieces = Vec::new();
+      let mut sphere = None;
+      let shank_start = match tool.tool_type {
+        "ball" => {
+          sphere = Some([p[0] + a[0] * r, p[1] + a[1] * r, p[2] + a[2] * r]);
+          r
+        }
+        "vbit" => {
+          let h = (r / tan_half).min(length);
+          pieces.push(Frustum { base: p, axis: a, t0: 0.0, t1: h, r0: 0.0, slope: tan_half });
+          h
+        }
+        _ => 0.0,
+      };
+      if length > shank_start {
+        pieces.push(Frustum { base: p, axis: a, t0: shank_start, t1: length, r0: r, slope: 0.0 });
+      }
+
+      // Nodes under the tool's bounding box
+      let tip_end = [p[0] + a[0] * length, p[1] + a[1] * length];
+      let (x0, x1) = (p[0].min(tip_end[0]) - r, p[0].max(tip_end[0]) + r);
+      let (y0, y1) = (p[1].min(tip_end[1]) - r, p[1].max(tip_end[1]) + r);
+      let ix0 = (((x0 - self.origin_x) / self.cell_x).floor().max(0.0)) as usize;
+      let iy0 = (((y0 - self.origin_y) / self.cell_y).floor().max(0.0)) as usize;
+      let ix1 = ((x1 - self.origin_x) / self.cell_x).ceil();
+      let iy1 = ((y1 - self.origin_y) / self.cell_y).ceil();
+      if ix1 < 0.0 || iy1 < 0.0 {
+        continue;
+      }
+      let ix1 = (ix1 as usize).min(self.nx.saturating_sub(1));
+      let iy1 = (iy1 as usize).min(self.ny.saturating_sub(1));
+      for ix in ix0..=ix1 {
+        for iy in iy0..=iy1 {
+          let (x, y) = (self.origin_x + ix as f32 * self.cell_x, self.origin_y + iy as f32 * self.cell_y);
+          let k = grid_index(self.ny, ix, iy);
+          for piece in &pieces {
+            if let Some((lo, hi)) = piece.ray(x, y) {
+              self.subtract(k, lo, hi);
+            }
+          }
+          if let Some(c) = sphere {
+            if let Some((lo, hi)) = sphere_ray(c, r, x, y) {
+              self.subtract(k, lo, hi);
+            }
+          }
+        }
+      }
+    }
+  }
+
+  fn node(&self, x: f32, y: f32) -> Option<usize> {
+    let ix = ((x - self.origin_x) / self.cell_x).round();
+    let iy = ((y - self.origin_y) / self.cell_y).round();
+    if ix < 0.0 || iy < 0.0 || ix as usize >= self.nx || iy as usize >= self.ny {
+      return None;
+    }
+    Some(grid_index(self.ny, ix as usize, iy as usize))
+  }
+
+  /// Material segments on the ray through (x, y), bottom to top
+  pub fn segments_at(&self, x: f32, y: f32) -> &[(f32, f32)] {
+    self.node(x, y).map(|k| self.segments[k].as_slice()).unwrap_or(&[])
+  }
+
+  // Segments of node (ix, iy) clipped to z >= min_z, empty off the grid
+  fn clipped(&self, ix: isize, iy: isize, min_z: f32) -> Vec<(f32, f32)> {
+    if ix < 0 || iy < 0 || ix as usize >= self.nx || iy as usize >= self.ny {
+      return Vec::new();
+    }
+    self.segments[grid_index(self.ny, ix as usize, iy as usize)]
+      .iter()
+      .map(|&(a, b)| (a.max(min_z), b))
+      .filter(|(a, b)| b > a)
+      .collect()
+  }
+}
+
+impl StockModel for DexelStock {
+  fn remove_swept_tool(&mut self, tool: &ToolShape, toolpath: &[[f32; 3]]) {
+    // Vertical tool, long enough to reach through any stock
+    let top = self.segments.iter().flatten().map(|s| s.1).fold(f32::NEG_INFINITY, f32::max);
+    let low = toolpath.iter().map(|p| p[2]).fold(f32::INFINITY, f32::min);
+    if top > low {
+      self.remove_swept_tool_along(tool, toolpath, [0.0, 0.0, 1.0], top - low + 1.0);
+    }
+  }
+
+  fn height_at(&self, x: f32, y: f32) -> Option<f32> {
+    self.segments_at(x, y).last().map(|s| s.1)
+  }
+
+  fn to_mesh(&self, min_z: f32) -> Mesh {
+    let mut positions = Vec::new();
+    let mut indices = Vec::new();
+    let mut normals = Vec::new();
+    let mut quad = |corners: [[f32; 3]; 4], normal: [f32; 3]| {
+      let base = (positions.len() / 3) as u32;
+      for c in corners {
+        positions.extend_from_slice(&c);
+        normals.extend_from_slice(&normal);
+      }
+      indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    };
+    let (hx, hy) = (self.cell_x / 2.0, self.cell_y / 2.0);
+    for ix in 0..self.nx as isize {
+      for iy in 0..self.ny as isize {
+        let (cx, cy) = (self.origin_x + ix as f32 * self.cell_x, self.origin_y + iy as f32 * self.cell_y);
+        let (x0, x1, y0, y1) = (cx - hx, cx + hx, cy - hy, cy + hy);
+        let own = self.clipped(ix, iy, min_z);
+        for &(lo, hi) in &own {
+          quad([[x0, y0, hi], [x1, y0, hi], [x1, y1, hi], [x0, y1, hi]], [0.0, 0.0, 1.0]);
+          quad([[x0, y0, lo], [x0, y1, lo], [x1, y1, lo], [x1, y0, lo]], [0.0, 0.0, -1.0]);
+        }
+        // Side walls where the neighbor has no material next to ours
+        let sides = [
+          ((1, 0), [[x1, y0], [x1, y1]], [1.0, 0.0, 0.0]),
+          ((-1, 0), [[x0, y1], [x0, y0]], [-1.0, 0.0, 0.0]),
+          ((0, 1), [[x1, y1], [x0, y1]], [0.0, 1.0, 0.0]),
+          ((0, -1), [[x0, y0], [x1, y0]], [0.0, -1.0, 0.0]),
+        ];
+        for ((dx, dy), [p, q], normal) in sides {
+          let neighbor = self.clipped(ix + dx, iy + dy, min_z);
+          for &(lo, hi) in &own {
+            // Parts of (lo, hi) not covered by the neighbor's segments
+            let mut z = lo;
+            for &(a, b) in neighbor.iter().filter(|s| s.1 > lo && s.0 < hi) {
+              if a > z {
+                quad([[p[0], p[1], z], [q[0], q[1], z], [q[0], q[1], a], [p[0], p[1], a]], normal);
+              }
+              z = z.max(b);
+            }
+            if z < hi {
+              quad([[p[0], p[1], z], [q[0], q[1], z], [q[0], q[1], hi], [p[0], p[1], hi]], normal);
+            }
+          }
+        }
+      }
+    }
+    Mesh { positions, indices, normals }
+  }
+
+  fn volume(&self, min_z: f32) -> f32 {
+    let length: f32 = self.segments.iter().flatten().map(|&(a, b)| (b - a.max(min_z)).max(0.0)).sum();
+    length * self.cell_x * self.cell_y
+  }
+
+  fn cell_size(&self) -> (f32, f32) {
+    (self.cell_x, self.cell_y)
+  }
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Float32Array, Object, Uint32Array};
+
+/// Dexel stock kept on the Rust side between calls
+#[wasm_bindgen]
+pub struct DexelStockHandle {
+  stock: DexelStock,
+}
+
+#[wasm_bindgen]
+impl DexelStockHandle {
+  #[wasm_bindgen(constructor)]
+  pub fn new(origin_x: f32, origin_y: f32, nx: usize, ny: usize, cell_x: f32, cell_y: f32, bottom_z: f32, top_z: f32) -> DexelStockHandle {
+    DexelStockHandle { stock: DexelStock::new_box(origin_x, origin_y, nx, ny, cell_x, cell_y, bottom_z, top_z) }
+  }
+
+  /// toolpath: flat x, y, z tip positions; axis: tip toward shank, (0, 0, 1) for a vertical tool
+  pub fn remove_swept_tool(
+    &mut self,
+    tool_type: &str,
+    cutter_diameter: f32,
+    v_angle_deg: f32,
+    toolpath_js: &Float32Array,
+    axis_x: f32,
+    axis_y: f32,
+    axis_z: f32,
+    length: f32,
+  ) {
+    let toolpath: Vec<[f32; 3]> = toolpath_js.to_vec().chunks_exact(3).map(|p| [p[0], p[1], p[2]]).collect();
+    let tool = ToolShape { tool_type, cutter_diameter, v_angle_deg };
+    self.stock.remove_swept_tool_along(&tool, &toolpath, [axis_x, axis_y, axis_z], length);
+  }
+
+  pub fn volume(&self, min_z: f32) -> f32 {
+    self.stock.volume(min_z)
+  }
+
+  /// Returns { positions, indices, normals }
+  pub fn to_mesh(&self, min_z: f32) -> Object {
+    let mesh = self.stock.to_mesh(min_z);
+    let result = Object::new();
+    js_sys::Reflect::set(&result, &"positions".into(), &Float32Array::from(mesh.positions.as_slice())).unwrap();
+    js_sys::Reflect::set(&result, &"indices".into(), &Uint32Array::from(mesh.indices.as_slice())).unwrap();
+    js_sys::Reflect::set(&result, &"normals".into(), &Float32Array::from(mesh.normals.as_slice())).unwrap();
+    result
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::heightmap_to_solid_mesh::create_heightmap_stock;
+
+  // 20 x 20 x 10 box, 0.5 mm cells
+  fn block() -> DexelStock {
+    DexelStock::new_box(0.25, 0.25, 40, 40, 0.5, 0.5, 0.0, 10.0)
+  }
+
+  #[test]
+  fn side_cut_leaves_overhang() {
+    let mut stock = block();
+    let tool = ToolShape { tool_type: "flat", cutter_diameter: 4.0, v_angle_deg: 0.0 };
+    // Tool lying along x, shank toward -x, tip at x = 10, swept along y at mid height
+    stock.remove_swept_tool_along(&tool, &[[10.0, -5.0, 5.0], [10.0, 25.0, 5.0]], [-1.0, 0.0, 0.0], 40.0);
+    assert_eq!(stock.segments_at(5.0, 10.0), &[(0.0, 3.0), (7.0, 10.0)]);
+    assert_eq!(stock.segments_at(15.0, 10.0), &[(0.0, 10.0)]);
+    // The top is untouched: a heightmap couldn't show this cut at all
+    assert_eq!(stock.height_at(5.0, 10.0), Some(10.0));
+    let removed = 20.0 * 20.0 * 10.0 - stock.volume(0.0);
+    assert!((removed - 10.0 * 20.0 * 4.0).abs() < 800.0 * 0.05, "removed {}", removed);
+  }
+
+  #[test]
+  fn vertical_cuts_match_heightmap() {
+    let mut dexel = block();
+    let mut heightmap = create_heightmap_stock(19.5, 19.5, 40, 40, 10.0, 0.25, 0.25);
+    let path = [[3.0, 3.0, 6.0], [17.0, 9.0, 6.0], [8.0, 16.0, 4.0]];
+    // The dexel columns cover whole cells, the heightmap trapezoids half a cell less per side
+    let (dexel_start, heightmap_start) = (dexel.volume(0.0), heightmap.volume(0.0));
+    for tool_type in ["flat", "ball", "vbit"] {
+      let tool = ToolShape { tool_type, cutter_diameter: 3.0, v_angle_deg: 60.0 };
+      dexel.remove_swept_tool(&tool, &path);
+      heightmap.remove_swept_tool(&tool, &path);
+    }
+    // The heightmap snaps tool positions to its nodes, so compare overall rather than node by node
+    let (d, h) = (dexel_start - dexel.volume(0.0), heightmap_start - heightmap.volume(0.0));
+    assert!((d - h).abs() < h * 0.1, "dexel removed {} heightmap {}", d, h);
+    let lowest = |s: &dyn StockModel| {
+      (0..1600).map(|k| s.height_at(0.25 + (k / 40) as f32 * 0.5, 0.25 + (k % 40) as f32 * 0.5).unwrap()).fold(f32::INFINITY, f32::min)
+    };
+    assert!((lowest(&dexel) - 4.0).abs() < 1e-4 && (lowest(&heightmap) - 4.0).abs() < 1e-4);
+  }
+
+  #[test]
+  fn mesh_encloses_volume() {
+    let mut stock = block();
+    let tool = ToolShape { tool_type: "ball", cutter_diameter: 4.0, v_angle_deg: 0.0 };
+    stock.remove_swept_tool_along(&tool, &[[10.0, -5.0, 5.0], [10.0, 25.0, 5.0]], [-1.0, 0.0, 0.0], 40.0);
+    stock.remove_swept_tool(&tool, &[[2.0, 2.0, 8.0], [18.0, 18.0, 8.0]]);
+    let mesh = stock.to_mesh(0.0);
+    // Divergence theorem: a closed, consistently wound mesh gives back the volume
+    let p = |i: u32| {
+      let i = i as usize * 3;
+      [mesh.positions[i], mesh.positions[i + 1], mesh.positions[i + 2]]
+    };
+    let enclosed: f32 = mesh
+      .indices
+      .chunks(3)
+      .map(|t| {
+        let (a, b, c) = (p(t[0]), p(t[1]), p(t[2]));
+        dot(a, [b[1] * c[2] - b[2] * c[1], b[2] * c[0] - b[0] * c[2], b[0] * c[1] - b[1] * c[0]]) / 6.0
+      })
+      .sum();
+    assert!((enclosed - stock.volume(0.0)).abs() < 1.0, "mesh {} dexels {}", enclosed, stock.volume(0.0));
+  }
+}
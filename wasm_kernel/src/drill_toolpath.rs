@@ -0,0 +1,337 @@
+// Drilling: hole detection from vector circles, peck cycles, G-code and simulation
+// All units are mm unless otherwise noted
+//
+// Holes come from picked points or from circles found in imported outlines. Each hole is
+// drilled with a simple (G81), full-retract peck (G83) or chip-breaking peck (G73) cycle,
+// written either as canned cycles or expanded to G0/G1 moves for controllers without them.
+//
+// Assumptions:
+// - The drill starts every hole from retract_z (the R plane) and returns to clearance_z
+//   between holes, like G98
+// - The first peck goes peck_depth below retract_z, as canned cycles do
+// - Expanded pecks rapid back down to peck_clearance above the previous depth (G83), or
+//   back off by peck_clearance without leaving the hole (G73)
+// - A drill is simulated as a V-bit with its point angle, so the hole bottom has the drill point;
+//   any StockModel can be drilled, and holes off the stock report NaN as the height left
+// - Peck cycles with peck_depth <= 0 (or not a number) drill straight to depth: G81 in canned
+//   output, since a zero or negative Q is rejected or loops on many controllers
+
+use crate::geometry_2d::{signed_area, Point2};
+use crate::heightmap::Heightmap;
+use crate::stock_model::{StockModel, ToolShape};
+use crate::toolpath::Pass;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hole {
+  pub x: f32,
+  pub y: f32,
+  pub diameter: f32, // detected diameter, 0 for picked points
+  pub bottom_z: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DrillCycle {
+  Drill,     // G81: straight to depth
+  Peck,      // G83: full retract out of the hole after each peck
+  ChipBreak, // G73: short back-off after each peck
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct DrillParams {
+  pub cycle: DrillCycle,
+  pub retract_z: f32,       // R plane, a little above the stock top
+  pub clearance_z: f32,     // travel height between holes
+  pub peck_depth: f32,      // Q, depth per peck
+  pub peck_clearance: f32,  // expanded pecks: stop above the last depth / back-off distance
+  pub feed: f32,            // plunge feed, mm/min
+}
+
+// Cycle actually run: pecking needs a positive peck depth
+fn effective_cycle(params: &DrillParams) -> DrillCycle {
+  if params.peck_depth > 0.0 { params.cycle } else { DrillCycle::Drill }
+}
+
+/// Find circles among closed loops: all points within tolerance of one radius around the centroid.
+/// Returns holes with bottom_z 0, to be set from the job depth.
+pub fn detect_holes(loops: &[Vec<Point2>], min_diameter: f32, max_diameter: f32, tolerance: f32) -> Vec<Hole> {
+  let mut holes = Vec::new();
+  for l in loops {
+    let n = l.len();
+    if n < 8 {
+      continue;
+    }
+    let area = signed_area(l);
+    if area.abs() < 1e-9 {
+      continue;
+    }
+    // Area centroid, so uneven point spacing doesn't pull the center
+    let (mut cx, mut cy) = (0.0, 0.0);
+    for i in 0..n {
+      let (a, b) = (l[i], l[(i + 1) % n]);
+      let cross = a[0] * b[1] - b[0] * a[1];
+      cx += (a[0] + b[0]) * cross;
+      cy += (a[1] + b[1]) * cross;
+    }
+    let (cx, cy) = (cx / (6.0 * area), cy / (6.0 * area));
+    let radii: Vec<f32> = l.iter().map(|p| (p[0] - cx).hypot(p[1] - cy)).collect();
+    let r = radii.iter().sum::<f32>() / n as f32;
+    let round = radii.iter().all(|&ri| (ri - r).abs() <= tolerance);
+    let d = 2.0 * r;
+    if round && d >= min_diameter && d <= max_diameter {
+      holes.push(Hole { x: cx, y: cy, diameter: d, bottom_z: 0.0 });
+    }
+  }
+  holes
+}
+
+/// Order holes nearest-first from the first one to shorten travel
+pub fn order_holes(holes: &[Hole]) -> Vec<Hole> {
+  let mut rest = holes.to_vec();
+  let mut ordered = Vec::with_capacity(rest.len());
+  let mut at = match rest.first() {
+    Some(h) => [h.x, h.y],
+    None => return ordered,
+  };
+  while !rest.is_empty() {
+    let i = (0..rest.len())
+      .min_by(|&i, &j| {
+        let di = (rest[i].x - at[0]).hypot(rest[i].y - at[1]);
+        let dj = (rest[j].x - at[0]).hypot(rest[j].y - at[1]);
+        di.total_cmp(&dj)
+      })
+      .unwrap();
+    let h = rest.swap_remove(i);
+    at = [h.x, h.y];
+    ordered.push(h);
+  }
+  ordered
+}
+
+// Expanded moves of one hole as (rapid, point), from the R plane back to the R plane
+fn hole_moves(hole: &Hole, params: &DrillParams) -> Vec<(bool, [f32; 3])> {
+  let at = |z: f32| [hole.x, hole.y, z];
+  let mut moves = vec![(true, at(params.retract_z))];
+  let cycle = effective_cycle(params);
+  let mut depths = Vec::new();
+  if cycle == DrillCycle::Drill {
+    depths.push(hole.bottom_z);
+  } else {
+    let mut z = params.retract_z;
+    while z > hole.bottom_z {
+      z = (z - params.peck_depth).max(hole.bottom_z);
+      depths.push(z);
+    }
+  }
+  let mut reached = params.retract_z;
+  for (i, &z) in depths.iter().enumerate() {
+    if i > 0 {
+      match cycle {
+        DrillCycle::Peck => {
+          moves.push((true, at(params.retract_z)));
+          moves.push((true, at((reached + params.peck_clearance).min(params.retract_z))));
+        }
+        _ => moves.push((true, at((reached + params.peck_clearance).min(params.retract_z)))),
+      }
+    }
+    moves.push((false, at(z)));
+    reached = z;
+  }
+  moves.push((true, at(params.retract_z)));
+  moves
+}
+
+/// Expanded drilling moves, one pass per hole (for simulation and preview)
+pub fn drill_passes(holes: &[Hole], params: &DrillParams) -> Vec<Pass> {
+  holes.iter().map(|h| hole_moves(h, params).into_iter().map(|(_, p)| p).collect()).collect()
+}
+
+/// G-code block for the holes, as canned cycles or expanded G0/G1 moves.
+/// Only the drilling moves: units, spindle and program end are left to the caller.
+pub fn drill_gcode(holes: &[Hole], params: &DrillParams, canned: bool) -> String {
+  let cycle = effective_cycle(params);
+  let (code, name) = match cycle {
+    DrillCycle::Drill => ("G81", "drill"),
+    DrillCycle::Peck => ("G83", "peck drill"),
+    DrillCycle::ChipBreak => ("G73", "chip break drill"),
+  };
+  let mut lines = vec![format!("; {} {} holes", name, holes.len())];
+  lines.push(format!("G0 Z{:.3}", params.clearance_z));
+  if canned {
+    for (i, h) in holes.iter().enumerate() {
+      if i == 0 {
+        lines.push(format!("G0 X{:.3} Y{:.3}", h.x, h.y));
+        let q = if cycle == DrillCycle::Drill { String::new() } else { format!(" Q{:.3}", params.peck_depth) };
+        lines.push(format!("G98 {} X{:.3} Y{:.3} Z{:.3} R{:.3}{} F{:.0}", code, h.x, h.y, h.bottom_z, params.retract_z, q, params.feed));
+      } else {
+        lines.push(format!("X{:.3} Y{:.3} Z{:.3}", h.x, h.y, h.bottom_z));
+      }
+    }
+    lines.push("G80".to_string());
+  } else {
+    lines.push(format!("G1 F{:.0}", params.feed));
+    for h in holes {
+      lines.push(format!("G0 X{:.3} Y{:.3}", h.x, h.y));
+      for (rapid, p) in hole_moves(h, params) {
+        lines.push(format!("{} Z{:.3}", if rapid { "G0" } else { "G1" }, p[2]));
+      }
+      lines.push(format!("G0 Z{:.3}", params.clearance_z));
+    }
+  }
+  lines.push(format!("G0 Z{:.3}", params.clearance_z));
+  lines.join("\n")
+}
+
+/// Drill each hole into the stock and return the height left at each hole center
+pub fn simulate_drilling<S: StockModel + ?Sized>(stock: &mut S, drill_diameter: f32, point_angle_deg: f32, holes: &[Hole]) -> Vec<f32> {
+  let drill = ToolShape { tool_type: "vbit", cutter_diameter: drill_diameter, v_angle_deg: point_angle_deg };
+  holes
+    .iter()
+    .map(|h| {
+      stock.remove_swept_tool(&drill, &[[h.x, h.y, h.bottom_z]]);
+      stock.height_at(h.x, h.y).unwrap_or(f32::NAN)
+    })
+    .collect()
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Float32Array, Object, Uint32Array};
+use crate::toolpath::{passes_to_js, split_polylines};
+
+/// Circles among closed loops, as flat x, y, diameter triples
+#[wasm_bindgen]
+pub fn detect_holes_wasm(
+  outline_points_js: &Float32Array, // flat x, y for all loops
+  outline_counts_js: &Uint32Array,  // number of points in each loop
+  min_diameter: f32,
+  max_diameter: f32,
+  tolerance: f32,
+) -> Float32Array {
+  let loops = split_polylines::<2>(&outline_points_js.to_vec(), &outline_counts_js.to_vec());
+  let holes = detect_holes(&loops, min_diameter, max_diameter, tolerance);
+  let flat: Vec<f32> = holes.iter().flat_map(|h| [h.x, h.y, h.diameter]).collect();
+  Float32Array::from(flat.as_slice())
+}
+
+/// Returns { gcode: string, toolpath: { passes, linked } }; holes are reordered to shorten travel
+#[wasm_bindgen]
+pub fn generate_drill_wasm(
+  holes_js: &Float32Array, // flat x, y, bottom_z per hole
+  cycle: &str,             // "drill" (G81), "peck" (G83) or "chipbreak" (G73)
+  retract_z: f32,
+  clearance_z: f32,
+  peck_depth: f32,
+  peck_clearance: f32,
+  feed: f32,
+  canned: bool,
+) -> Object {
+  let holes: Vec<Hole> = holes_js.to_vec().chunks_exact(3).map(|h| Hole { x: h[0], y: h[1], diameter: 0.0, bottom_z: h[2] }).collect();
+  let params = DrillParams {
+    cycle: match cycle {
+      "peck" => DrillCycle::Peck,
+      "chipbreak" => DrillCycle::ChipBreak,
+      _ => DrillCycle::Drill,
+    },
+    retract_z,
+    clearance_z: clearance_z.max(retract_z),
+    peck_depth,
+    peck_clearance,
+    feed,
+  };
+  let holes = order_holes(&holes);
+  let result = Object::new();
+  js_sys::Reflect::set(&result, &"gcode".into(), &drill_gcode(&holes, &params, canned).into()).unwrap();
+  js_sys::Reflect::set(&result, &"toolpath".into(), &passes_to_js(&drill_passes(&holes, &params), params.clearance_z)).unwrap();
+  result
+}
+
+/// Drill holes (flat x, y, bottom_z) into the heightmap in place; returns the height left at each hole center (NaN off the stock)
+#[wasm_bindgen]
+pub fn simulate_drilling_wasm(
+  heightmap_js: &Float32Array,
+  nx: usize,
+  ny: usize,
+  grid_size_x: f32,
+  grid_size_y: f32,
+  origin_x: f32,
+  origin_y: f32,
+  drill_diameter: f32,
+  point_angle_deg: f32,
+  holes_js: &Float32Array,
+) -> Float32Array {
+  let mut heightmap = Heightmap::from_vec(nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, heightmap_js.to_vec());
+  let holes: Vec<Hole> = holes_js.to_vec().chunks_exact(3).map(|h| Hole { x: h[0], y: h[1], diameter: 0.0, bottom_z: h[2] }).collect();
+  let depths = simulate_drilling(&mut heightmap, drill_diameter, point_angle_deg, &holes);
+  heightmap_js.copy_from(&heightmap.heights);
+  Float32Array::from(depths.as_slice())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn circle(cx: f32, cy: f32, r: f32, n: usize) -> Vec<Point2> {
+    (0..n).map(|i| {
+      let a = i as f32 / n as f32 * std::f32::consts::TAU;
+      [cx + r * a.cos(), cy + r * a.sin()]
+    }).collect()
+  }
+
+  fn params(cycle: DrillCycle) -> DrillParams {
+    DrillParams { cycle, retract_z: 2.0, clearance_z: 10.0, peck_depth: 2.5, peck_clearance: 0.5, feed: 120.0 }
+  }
+
+  #[test]
+  fn detects_circles_in_range() {
+    let loops = vec![
+      circle(10.0, 5.0, 3.0, 36),
+      vec![[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0], [0.0, 3.0], [0.0, 2.0], [0.0, 1.0], [0.0, 0.5]],
+      circle(0.0, 0.0, 20.0, 64),
+    ];
+    let holes = detect_holes(&loops, 1.0, 10.0, 0.05);
+    assert_eq!(holes.len(), 1);
+    assert!((holes[0].x - 10.0).abs() < 1e-3 && (holes[0].y - 5.0).abs() < 1e-3);
+    // Mean vertex radius of the inscribed polygon
+    assert!((holes[0].diameter - 6.0).abs() < 0.05, "diameter {}", holes[0].diameter);
+  }
+
+  #[test]
+  fn canned_and_expanded_gcode() {
+    let holes = [Hole { x: 5.0, y: 5.0, diameter: 0.0, bottom_z: -6.0 }, Hole { x: 15.0, y: 5.0, diameter: 0.0, bottom_z: -4.0 }];
+    let canned = drill_gcode(&holes, &params(DrillCycle::Peck), true);
+    assert!(canned.contains("G98 G83 X5.000 Y5.000 Z-6.000 R2.000 Q2.500 F120"), "{}", canned);
+    assert!(canned.contains("\nX15.000 Y5.000 Z-4.000\nG80"));
+
+    let expanded = drill_gcode(&holes, &params(DrillCycle::Peck), false);
+    assert!(!expanded.contains("G83"));
+    // 8 mm from the R plane at 2.5 mm per peck is 4 pecks, then 6 mm is 3 pecks
+    assert_eq!(expanded.matches("G1 Z").count(), 7);
+    assert!(expanded.contains("G1 Z-0.500\nG0 Z2.000\nG0 Z0.000\nG1 Z-3.000"), "{}", expanded);
+
+    let chip = drill_gcode(&holes[..1], &params(DrillCycle::ChipBreak), false);
+    // Backs off without leaving the hole
+    assert!(chip.contains("G1 Z-0.500\nG0 Z0.000\nG1 Z-3.000"), "{}", chip);
+    let drill = drill_passes(&holes, &params(DrillCycle::Drill));
+    assert_eq!(drill[0], vec![[5.0, 5.0, 2.0], [5.0, 5.0, -6.0], [5.0, 5.0, 2.0]]);
+
+    // No peck depth: plain G81, never a Q0
+    for peck_depth in [0.0, -1.0, f32::NAN] {
+      let canned = drill_gcode(&holes, &DrillParams { peck_depth, ..params(DrillCycle::ChipBreak) }, true);
+      assert!(canned.contains("G98 G81 X5.000 Y5.000 Z-6.000 R2.000 F120") && !canned.contains('Q'), "{}", canned);
+    }
+  }
+
+  #[test]
+  fn simulate_holes_in_heightmap() {
+    let mut stock = Heightmap::new(101, 51, 0.2, 0.2, 0.0, 0.0, 0.0);
+    let holes = detect_holes(&[circle(5.0, 5.0, 1.5, 24), circle(15.0, 5.0, 1.5, 24)], 1.0, 10.0, 0.05);
+    let holes: Vec<Hole> = holes.iter().map(|h| Hole { bottom_z: -5.0, ..*h }).collect();
+    let depths = simulate_drilling(&mut stock, 3.0, 118.0, &holes);
+    assert!(depths.iter().all(|&d| (d + 5.0).abs() < 1e-4));
+    // Drill point: shallower toward the hole edge, nothing cut outside the drill
+    let z = |x: f32, y: f32| stock.height_at(x, y).unwrap();
+    let edge = -5.0 + 1.4 / (59.0f32).to_radians().tan();
+    assert!((z(6.4, 5.0) - edge).abs() < 1e-3);
+    assert_eq!(z(10.0, 5.0), 0.0);
+    assert_eq!(z(5.0, 7.0), 0.0);
+  }
+}
@@ -0,0 +1,619 @@
+// DXF import of 2D geometry (ASCII DXF)
+// All output units are mm
+//
+// Supported entities: LINE, ARC, CIRCLE, LWPOLYLINE (with bulges), POLYLINE/VERTEX/SEQEND,
+// SPLINE (NURBS control points, or fit points when there are none) and ELLIPSE.
+// Loose pieces (lines, arcs, open polylines...) are chained end to end within join_tolerance;
+// chains that don't close are kept as open outlines and reported as warnings.
+//
+// Assumptions:
+// - Geometry is projected onto XY; Z is ignored
+// - An extrusion direction of (0, 0, -1) mirrors the X axis of entities drawn in their own
+//   coordinate system (CIRCLE, ARC, LWPOLYLINE, 2D POLYLINE; the ELLIPSE minor axis). LINE,
+//   SPLINE, 3D POLYLINE and the ELLIPSE center and major axis are in world coordinates, so a
+//   SPLINE's 210/220/230 plane normal leaves it as drawn
+// - $INSUNITS sets the drawing unit; unitless drawings are taken as mm
+// - Only the ENTITIES section is read (block INSERTs are not expanded)
+// - Closed loops are filled with the even-odd rule, so nested loops become holes
+
+use std::collections::HashMap;
+
+use crate::geometry_2d::{fill_regions, FillRule, Point2, Polyline2};
+use crate::svg_import::VectorGeometry;
+
+#[derive(Clone, Debug, Default)]
+pub struct DxfImport {
+  pub geometry: VectorGeometry,
+  pub layers: Vec<String>,   // every layer that has supported entities, in first-seen order
+  pub warnings: Vec<String>, // open chains, skipped entities
+}
+
+struct Entity {
+  kind: String,
+  pairs: Vec<(i32, String)>,
+}
+
+impl Entity {
+  fn value(&self, code: i32) -> Option<&str> {
+    self.pairs.iter().find(|(c, _)| *c == code).map(|(_, v)| v.as_str())
+  }
+
+  fn number(&self, code: i32, default: f32) -> f32 {
+    self.value(code).and_then(|v| v.trim().parse().ok()).unwrap_or(default)
+  }
+
+  fn int(&self, code: i32) -> i32 {
+    self.value(code).and_then(|v| v.trim().parse().ok()).unwrap_or(0)
+  }
+
+  fn numbers(&self, code: i32) -> Vec<f32> {
+    self.pairs.iter().filter(|(c, _)| *c == code).filter_map(|(_, v)| v.trim().parse().ok()).collect()
+  }
+
+  fn layer(&self) -> String {
+    self.value(8).unwrap_or("0").trim().to_string()
+  }
+
+  // X axis mirrored by a (0, 0, -1) extrusion direction
+  fn mirror_x(&self) -> f32 {
+    if self.number(230, 1.0) < 0.0 { -1.0 } else { 1.0 }
+  }
+}
+
+// A piece of geometry before chaining
+struct Piece {
+  points: Vec<Point2>,
+  closed: bool,
+  layer: String,
+}
+
+fn parse_pairs(text: &str) -> Result<Vec<(i32, String)>, String> {
+  if text.starts_with("AutoCAD Binary DXF") {
+    return Err("binary DXF is not supported".to_string());
+  }
+  let lines: Vec<&str> = text.lines().collect();
+  let mut pairs = Vec::with_capacity(lines.len() / 2);
+  for chunk in lines.chunks_exact(2) {
+    let code = chunk[0].trim().parse::<i32>().map_err(|_| format!("bad group code '{}'", chunk[0].trim()))?;
+    pairs.push((code, chunk[1].trim_end_matches('\r').to_string()));
+  }
+  Ok(pairs)
+}
+
+// mm per drawing unit from the $INSUNITS header variable
+fn unit_scale(insunits: i32) -> f32 {
+  match insunits {
+    1 => 25.4,      // inches
+    2 => 304.8,     // feet
+    5 => 10.0,      // centimeters
+    6 => 1000.0,    // meters
+    8 => 25.4e-6,   // microinches
+    9 => 0.0254,    // mils
+    10 => 914.4,    // yards
+    13 => 1e-3,     // microns
+    14 => 100.0,    // decimeters
+    _ => 1.0,       // unitless or mm
+  }
+}
+
+// Flatten a parametric curve so the chord error stays below tolerance
+fn sample_curve(f: &dyn Fn(f32) -> Point2, t0: f32, t1: f32, segments: usize, tolerance: f32) -> Vec<Point2> {
+  fn refine(f: &dyn Fn(f32) -> Point2, ta: f32, tb: f32, a: Point2, b: Point2, tolerance: f32, depth: usize, out: &mut Vec<Point2>) {
+    let tm = (ta + tb) / 2.0;
+    let m = f(tm);
+    if depth > 0 && crate::geometry_2d::point_segment_distance(m, a, b) > tolerance {
+      refine(f, ta, tm, a, m, tolerance, depth - 1, out);
+      refine(f, tm, tb, m, b, tolerance, depth - 1, out);
+    } else {
+      out.push(b);
+    }
+  }
+  let segments = segments.max(1);
+  let mut points = vec![f(t0)];
+  for i in 0..segments {
+    let ta = t0 + (t1 - t0) * i as f32 / segments as f32;
+    let tb = t0 + (t1 - t0) * (i + 1) as f32 / segments as f32;
+    let a = *points.last().unwrap();
+    refine(f, ta, tb, a, f(tb), tolerance, 12, &mut points);
+  }
+  points
+}
+
+fn arc_points(center: Point2, radius: f32, start: f32, sweep: f32, tolerance: f32) -> Vec<Point2> {
+  let segments = ((sweep.abs() / std::f32::consts::FRAC_PI_4).ceil() as usize).max(1);
+  sample_curve(&|t: f32| [center[0] + radius * t.cos(), center[1] + radius * t.sin()], start, start + sweep, segments, tolerance)
+}
+
+// Polyline vertices with bulges (tan of a quarter of the included arc angle, + is counter-clockwise)
+fn bulge_polyline(vertices: &[(Point2, f32)], closed: bool, tolerance: f32) -> Vec<Point2> {
+  let n = vertices.len();
+  let mut points = Vec::new();
+  let edges = if closed { n } else { n.saturating_sub(1) };
+  if n > 0 {
+    points.push(vertices[0].0);
+  }
+  for i in 0..edges {
+    let (p1, bulge) = vertices[i];
+    let p2 = vertices[(i + 1) % n].0;
+    if bulge.abs() < 1e-9 {
+      points.push(p2);
+      continue;
+    }
+    let d = [p2[0] - p1[0], p2[1] - p1[1]];
+    let c = d[0].hypot(d[1]);
+    if c < 1e-12 {
+      continue;
+    }
+    let theta = 4.0 * bulge.atan();
+    // Signed distance from the chord midpoint to the center, toward the chord's left
+    let h = c * (1.0 - bulge * bulge) / (4.0 * bulge);
+    let center = [(p1[0] + p2[0]) / 2.0 - d[1] / c * h, (p1[1] + p2[1]) / 2.0 + d[0] / c * h];
+    let radius = (p1[0] - center[0]).hypot(p1[1] - center[1]);
+    let start = (p1[1] - center[1]).atan2(p1[0] - center[0]);
+    let mut arc = arc_points(center, radius, start, theta, tolerance);
+    arc.remove(0);
+    arc.pop();
+    points.extend(arc);
+    points.push(p2);
+  }
+  if closed && points.len() > 1 && points.first() == points.last() {
+    points.pop();
+  }
+  points
+}
+
+// Rational B-spline point by de Boor's algorithm
+fn nurbs_point(u: f32, degree: usize, knots: &[f32], ctrl: &[Point2], weights: &[f32]) -> Point2 {
+  let n = ctrl.len();
+  let mut k = degree;
+  while k + 1 < n && knots[k + 1] <= u {
+    k += 1;
+  }
+  let mut d: Vec<[f32; 3]> = (0..=degree)
+    .map(|j| {
+      let i = k - degree + j;
+      let w = weights.get(i).cloned().unwrap_or(1.0);
+      [ctrl[i][0] * w, ctrl[i][1] * w, w]
+    })
+    .collect();
+  for r in 1..=degree {
+    for j in (r..=degree).rev() {
+      let lo = knots[j + k - degree];
+      let hi = knots[j + 1 + k - r];
+      let alpha = if hi - lo > 1e-12 { (u - lo) / (hi - lo) } else { 0.0 };
+      let prev = d[j - 1];
+      for (c, v) in d[j].iter_mut().enumerate() {
+        *v = (1.0 - alpha) * prev[c] + alpha * *v;
+      }
+    }
+  }
+  let p = d[degree];
+  [p[0] / p[2], p[1] / p[2]]
+}
+
+// Convert one entity to a piece, in drawing units
+fn entity_piece(e: &Entity, vertices: &[Entity], tolerance: f32, warnings: &mut Vec<String>) -> Option<Piece> {
+  let layer = e.layer();
+  let mx = e.mirror_x();
+  let tau = std::f32::consts::TAU;
+  let (points, closed) = match e.kind.as_str() {
+    "LINE" => (vec![[e.number(10, 0.0), e.number(20, 0.0)], [e.number(11, 0.0), e.number(21, 0.0)]], false),
+    "CIRCLE" => {
+      let center = [e.number(10, 0.0) * mx, e.number(20, 0.0)];
+      let mut points = arc_points(center, e.number(40, 0.0), 0.0, tau, tolerance);
+      points.pop();
+      (points, true)
+    }
+    "ARC" => {
+      let center = [e.number(10, 0.0), e.number(20, 0.0)];
+      let start = e.number(50, 0.0).to_radians();
+      let mut end = e.number(51, 360.0).to_radians();
+      if end <= start {
+        end += tau;
+      }
+      let mut points = arc_points(center, e.number(40, 0.0), start, end - start, tolerance);
+      // Angles are counter-clockwise in the entity's own (possibly mirrored) coordinates
+      for p in &mut points {
+        p[0] *= mx;
+      }
+      (points, false)
+    }
+    "LWPOLYLINE" => {
+      let mut vs: Vec<(Point2, f32)> = Vec::new();
+      for (code, value) in &e.pairs {
+        let v: f32 = value.trim().parse().unwrap_or(0.0);
+        match code {
+          10 => vs.push(([v * mx, 0.0], 0.0)),
+          20 => if let Some(last) = vs.last_mut() { last.0[1] = v },
+          42 => if let Some(last) = vs.last_mut() { last.1 = v * mx },
+          _ => {}
+        }
+      }
+      let closed = e.int(70) & 1 != 0;
+      (bulge_polyline(&vs, closed, tolerance), closed)
+    }
+    "POLYLINE" => {
+      let flags = e.int(70);
+      if flags & (16 | 64) != 0 {
+        warnings.push(format!("skipped polygon mesh POLYLINE on layer {}", layer));
+        return None;
+      }
+      // 3D polylines have world coordinate vertices
+      let mx = if flags & 8 != 0 { 1.0 } else { mx };
+      let vs: Vec<(Point2, f32)> = vertices
+        .iter()
+        .filter(|v| v.int(70) & 128 == 0)
+        .map(|v| ([v.number(10, 0.0) * mx, v.number(20, 0.0)], v.number(42, 0.0) * mx))
+        .collect();
+      let closed = flags & 1 != 0;
+      (bulge_polyline(&vs, closed, tolerance), closed)
+    }
+    "ELLIPSE" => {
+      let center = [e.number(10, 0.0), e.number(20, 0.0)];
+      let major = [e.number(11, 0.0), e.number(21, 0.0)];
+      let ratio = e.number(40, 1.0);
+      // Minor axis is the major axis turned a quarter turn about the extrusion direction
+      let minor = [-major[1] * ratio * mx, major[0] * ratio * mx];
+      let start = e.number(41, 0.0);
+      let mut end = e.number(42, tau);
+      if end <= start {
+        end += tau;
+      }
+      let full = (end - start - tau).abs() < 1e-4;
+      let f = |t: f32| {
+        let (s, c) = t.sin_cos();
+        [center[0] + major[0] * c + minor[0] * s, center[1] + major[1] * c + minor[1] * s]
+      };
+      let mut points = sample_curve(&f, start, end, 8, tolerance);
+      if full {
+        points.pop();
+      }
+      (points, full)
+    }
+    "SPLINE" => {
+      let flags = e.int(70);
+      let degree = e.int(71).max(1) as usize;
+      let knots = e.numbers(40);
+      let weights = e.numbers(41);
+      let xs = e.numbers(10);
+      let ys = e.numbers(20);
+      // Control and fit points are in world coordinates, whatever the plane normal
+      let ctrl: Vec<Point2> = xs.iter().zip(ys.iter()).map(|(&x, &y)| [x, y]).collect();
+      let closed_flag = flags & 1 != 0;
+      let mut points = if ctrl.len() > degree && knots.len() == ctrl.len() + degree + 1 {
+        let (u0, u1) = (knots[degree], knots[ctrl.len()]);
+        let f = |u: f32| nurbs_point(u, degree, &knots, &ctrl, &weights);
+        sample_curve(&f, u0, u1, ctrl.len() * 4, tolerance)
+      } else {
+        // No usable control net: fall back to straight lines through the fit points
+        let fit: Vec<Point2> = e.numbers(11).iter().zip(e.numbers(21).iter()).map(|(&x, &y)| [x, y]).collect();
+        if fit.len() < 2 {
+          warnings.push(format!("skipped SPLINE without control or fit points on layer {}", layer));
+          return None;
+        }
+        warnings.push(format!("SPLINE on layer {} has no control points, using straight lines through its fit points", layer));
+        fit
+      };
+      let ends_meet = points.len() > 2 && {
+        let (a, b) = (points[0], points[points.len() - 1]);
+        (a[0] - b[0]).hypot(a[1] - b[1]) < tolerance
+      };
+      if closed_flag && ends_meet {
+        points.pop();
+      }
+      (points, closed_flag && ends_meet)
+    }
+    _ => return None,
+  };
+  if points.len() < 2 {
+    return None;
+  }
+  Some(Piece { points, closed, layer })
+}
+
+// Chain open pieces whose ends meet within tolerance. Returns (polyline, closed, layer)
+fn chain_pieces(pieces: Vec<Piece>, tolerance: f32) -> Vec<(Vec<Point2>, bool, String)> {
+  let mut result = Vec::new();
+  let mut open: Vec<Option<Piece>> = Vec::new();
+  for piece in pieces {
+    if piece.closed {
+      result.push((piece.points, true, piece.layer));
+    } else {
+      open.push(Some(piece));
+    }
+  }
+  // Endpoint buckets at the join tolerance; (piece, is_end)
+  let cell = tolerance.max(1e-9);
+  let key = |p: Point2| ((p[0] / cell).floor() as i64, (p[1] / cell).floor() as i64);
+  let mut buckets: HashMap<(i64, i64), Vec<(usize, bool)>> = HashMap::new();
+  for (i, piece) in open.iter().enumerate() {
+    let points = &piece.as_ref().unwrap().points;
+    buckets.entry(key(points[0])).or_default().push((i, false));
+    buckets.entry(key(*points.last().unwrap())).or_default().push((i, true));
+  }
+  let find = |open: &Vec<Option<Piece>>, p: Point2| -> Option<(usize, bool)> {
+    let (kx, ky) = key(p);
+    let mut best: Option<(usize, bool, f32)> = None;
+    for dx in -1..=1 {
+      for dy in -1..=1 {
+        for &(i, is_end) in buckets.get(&(kx + dx, ky + dy)).map(|v| v.as_slice()).unwrap_or(&[]) {
+          let Some(piece) = &open[i] else { continue };
+          let q = if is_end { *piece.points.last().unwrap() } else { piece.points[0] };
+          let d = (p[0] - q[0]).hypot(p[1] - q[1]);
+          if d <= tolerance && best.is_none_or(|b| d < b.2) {
+            best = Some((i, is_end, d));
+          }
+        }
+      }
+    }
+    best.map(|(i, is_end, _)| (i, is_end))
+  };
+  for i in 0..open.len() {
+    let Some(first) = open[i].take() else { continue };
+    let layer = first.layer.clone();
+    let mut chain = first.points;
+    // Grow forward from the end, then backward from the start
+    for forward in [true, false] {
+      loop {
+        let tip = if forward { *chain.last().unwrap() } else { chain[0] };
+        let start = chain[0];
+        let end = *chain.last().unwrap();
+        if chain.len() > 2 && (start[0] - end[0]).hypot(start[1] - end[1]) <= tolerance {
+          break;
+        }
+        let Some((j, is_end)) = find(&open, tip) else { break };
+        let mut points = open[j].take().unwrap().points;
+        // Orient the piece so it continues from the tip
+        if forward == is_end {
+          points.reverse();
+        }
+        if forward {
+          chain.extend(points.into_iter().skip(1));
+        } else {
+          points.pop();
+          points.extend(chain);
+          chain = points;
+        }
+      }
+    }
+    let (start, end) = (chain[0], *chain.last().unwrap());
+    let closed = chain.len() > 2 && (start[0] - end[0]).hypot(start[1] - end[1]) <= tolerance;
+    if closed {
+      chain.pop();
+    }
+    result.push((chain, closed, layer));
+  }
+  result
+}
+
+/// Parse an ASCII DXF. layers limits the import to those layers (case-insensitive), all if empty.
+/// join_tolerance is in mm, as is tolerance (max chord error when flattening curves).
+pub fn import_dxf(text: &str, layers: &[String], join_tolerance: f32, tolerance: f32) -> Result<DxfImport, String> {
+  let pairs = parse_pairs(text)?;
+  // Header variables and entities of the ENTITIES section
+  let mut insunits = 0;
+  let mut entities: Vec<Entity> = Vec::new();
+  let mut section = String::new();
+  let mut i = 0;
+  while i < pairs.len() {
+    let (code, value) = (&pairs[i].0, pairs[i].1.trim());
+    if *code == 0 && value == "SECTION" {
+      section = pairs.get(i + 1).map(|p| p.1.trim().to_string()).unwrap_or_default();
+      i += 2;
+      continue;
+    }
+    if *code == 0 && value == "ENDSEC" {
+      section.clear();
+    }
+    if section == "HEADER" && *code == 9 && value == "$INSUNITS" {
+      insunits = pairs.get(i + 1).and_then(|p| p.1.trim().parse().ok()).unwrap_or(0);
+    }
+    if section == "ENTITIES" && *code == 0 {
+      let mut entity = Entity { kind: value.to_string(), pairs: Vec::new() };
+      i += 1;
+      while i < pairs.len() && pairs[i].0 != 0 {
+        entity.pairs.push(pairs[i].clone());
+        i += 1;
+      }
+      entities.push(entity);
+      continue;
+    }
+    i += 1;
+  }
+
+  let scale = unit_scale(insunits);
+  let wanted = |layer: &str| layers.is_empty() || layers.iter().any(|l| l.eq_ignore_ascii_case(layer));
+  let mut result = DxfImport::default();
+  let mut pieces = Vec::new();
+  let mut skipped: HashMap<String, usize> = HashMap::new();
+  let mut k = 0;
+  while k < entities.len() {
+    let e = &entities[k];
+    k += 1;
+    // POLYLINE owns the VERTEX entities up to SEQEND
+    let mut vertices: &[Entity] = &[];
+    if e.kind == "POLYLINE" {
+      let start = k;
+      while k < entities.len() && entities[k].kind == "VERTEX" {
+        k += 1;
+      }
+      vertices = &entities[start..k];
+      if k < entities.len() && entities[k].kind == "SEQEND" {
+        k += 1;
+      }
+    }
+    let supported = matches!(e.kind.as_str(), "LINE" | "ARC" | "CIRCLE" | "LWPOLYLINE" | "POLYLINE" | "SPLINE" | "ELLIPSE");
+    if !supported {
+      *skipped.entry(e.kind.clone()).or_default() += 1;
+      continue;
+    }
+    let layer = e.layer();
+    if !result.layers.contains(&layer) {
+      result.layers.push(layer.clone());
+    }
+    if !wanted(&layer) {
+      continue;
+    }
+    if let Some(mut piece) = entity_piece(e, vertices, tolerance / scale, &mut result.warnings) {
+      for p in &mut piece.points {
+        p[0] *= scale;
+        p[1] *= scale;
+      }
+      pieces.push(piece);
+    }
+  }
+  let mut skipped: Vec<(String, usize)> = skipped.into_iter().collect();
+  skipped.sort();
+  for (kind, count) in skipped {
+    result.warnings.push(format!("skipped {} unsupported {} entities", count, kind));
+  }
+
+  let mut loops = Vec::new();
+  for (points, closed, layer) in chain_pieces(pieces, join_tolerance) {
+    if closed {
+      loops.push(points.clone());
+    } else {
+      let (a, b) = (points[0], points[points.len() - 1]);
+      result.warnings.push(format!(
+        "open chain on layer {} from ({:.3}, {:.3}) to ({:.3}, {:.3}), gap {:.3} mm",
+        layer, a[0], a[1], b[0], b[1], (a[0] - b[0]).hypot(a[1] - b[1])
+      ));
+    }
+    result.geometry.outlines.push(Polyline2 { points, closed });
+  }
+  result.geometry.regions = fill_regions(&loops, FillRule::EvenOdd);
+  Ok(result)
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Array, Object};
+use crate::geometry_2d::{polylines_to_js, regions_to_js};
+
+/// layers_csv: comma-separated layer names to import, empty for all layers.
+/// Returns { outlines, regions, layers: string[], warnings: string[] }
+#[wasm_bindgen]
+pub fn import_dxf_wasm(text: &str, layers_csv: &str, join_tolerance: f32, tolerance: f32) -> Result<Object, JsValue> {
+  let layers: Vec<String> = layers_csv.split(',').map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect();
+  let dxf = import_dxf(text, &layers, join_tolerance, tolerance).map_err(|e| JsValue::from_str(&e))?;
+  let to_array = |items: &[String]| items.iter().map(|s| JsValue::from_str(s)).collect::<Array>();
+  let result = Object::new();
+  js_sys::Reflect::set(&result, &"outlines".into(), &polylines_to_js(&dxf.geometry.outlines)).unwrap();
+  js_sys::Reflect::set(&result, &"regions".into(), &regions_to_js(&dxf.geometry.regions)).unwrap();
+  js_sys::Reflect::set(&result, &"layers".into(), &to_array(&dxf.layers)).unwrap();
+  js_sys::Reflect::set(&result, &"warnings".into(), &to_array(&dxf.warnings)).unwrap();
+  Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::geometry_2d::signed_area;
+
+  // Build a DXF from (entity type, [(code, value)]) records
+  fn dxf(insunits: i32, entities: &[(&str, Vec<(i32, String)>)]) -> String {
+    let mut s = format!("0\nSECTION\n2\nHEADER\n9\n$INSUNITS\n70\n{}\n0\nENDSEC\n0\nSECTION\n2\nENTITIES\n", insunits);
+    for (kind, pairs) in entities {
+      s += &format!("0\n{}\n", kind);
+      for (code, value) in pairs {
+        s += &format!("{}\n{}\n", code, value);
+      }
+    }
+    s + "0\nENDSEC\n0\nEOF\n"
+  }
+
+  fn line(layer: &str, a: Point2, b: Point2) -> (&'static str, Vec<(i32, String)>) {
+    ("LINE", vec![(8, layer.into()), (10, a[0].to_string()), (20, a[1].to_string()), (11, b[0].to_string()), (21, b[1].to_string())])
+  }
+
+  #[test]
+  fn loose_lines_and_arc_chain_into_a_loop() {
+    // Slot: two lines joined by half-circle arcs, written in mixed directions with small gaps
+    let text = dxf(4, &[
+      line("CUT", [0.0, 0.0], [10.0, 0.0]),
+      line("CUT", [0.0, 4.0001], [10.0, 4.0]), // reversed relative to travel, tiny gap
+      ("ARC", vec![(8, "CUT".into()), (10, "10".into()), (20, "2".into()), (40, "2".into()), (50, "270".into()), (51, "90".into())]),
+      ("ARC", vec![(8, "CUT".into()), (10, "0".into()), (20, "2".into()), (40, "2".into()), (50, "90".into()), (51, "270".into())]),
+      line("NOTES", [50.0, 50.0], [60.0, 60.0]),
+    ]);
+    let all = import_dxf(&text, &[], 0.01, 0.001).unwrap();
+    assert_eq!(all.layers, vec!["CUT".to_string(), "NOTES".to_string()]);
+    assert_eq!(all.warnings.len(), 1, "{:?}", all.warnings);
+    assert!(all.warnings[0].contains("open chain on layer NOTES"));
+
+    let cut = import_dxf(&text, &["cut".to_string()], 0.01, 0.001).unwrap();
+    assert!(cut.warnings.is_empty(), "{:?}", cut.warnings);
+    assert_eq!(cut.geometry.outlines.len(), 1);
+    assert!(cut.geometry.outlines[0].closed);
+    let area = signed_area(&cut.geometry.regions[0].outer);
+    let expected = 40.0 + std::f32::consts::PI * 4.0;
+    assert!((area - expected).abs() < 0.01, "area {}", area);
+  }
+
+  #[test]
+  fn polylines_circles_and_units() {
+    let text = dxf(1, &[
+      // 2 x 1 inch rectangle with one bulged (semicircular) end
+      ("LWPOLYLINE", vec![
+        (90, "4".into()), (70, "1".into()),
+        (10, "0".into()), (20, "0".into()),
+        (10, "2".into()), (20, "0".into()), (42, "1".into()),
+        (10, "2".into()), (20, "1".into()),
+        (10, "0".into()), (20, "1".into()),
+      ]),
+      ("CIRCLE", vec![(10, "1".into()), (20, "0.5".into()), (40, "0.25".into())]),
+    ]);
+    let dxf = import_dxf(&text, &[], 0.01, 0.001).unwrap();
+    assert_eq!(dxf.geometry.regions.len(), 1);
+    let region = &dxf.geometry.regions[0];
+    assert_eq!(region.holes.len(), 1);
+    let inch2 = 25.4 * 25.4;
+    let outer = 2.0 + std::f32::consts::PI * 0.125;
+    assert!((signed_area(&region.outer) / inch2 - outer).abs() < 0.002, "area {}", signed_area(&region.outer) / inch2);
+    let hole = std::f32::consts::PI * 0.0625;
+    assert!((signed_area(&region.holes[0]) / inch2 + hole).abs() < 0.002);
+  }
+
+  #[test]
+  fn spline_and_ellipse() {
+    // Clamped quadratic B-spline whose control polygon is symmetric about x = 5
+    let text = dxf(4, &[
+      ("SPLINE", vec![
+        (70, "8".into()), (71, "2".into()), (72, "6".into()), (73, "3".into()),
+        (40, "0".into()), (40, "0".into()), (40, "0".into()), (40, "1".into()), (40, "1".into()), (40, "1".into()),
+        (10, "0".into()), (20, "0".into()), (10, "5".into()), (20, "10".into()), (10, "10".into()), (20, "0".into()),
+      ]),
+      ("ELLIPSE", vec![(10, "20".into()), (20, "0".into()), (11, "4".into()), (21, "0".into()), (40, "0.5".into()), (41, "0".into()), (42, "6.283185307".into())]),
+    ]);
+    let dxf = import_dxf(&text, &[], 0.01, 0.001).unwrap();
+    let spline = dxf.geometry.outlines.iter().find(|o| !o.closed).unwrap();
+    // Quadratic Bezier apex is halfway to the middle control point
+    let apex = spline.points.iter().map(|p| p[1]).fold(f32::MIN, f32::max);
+    assert!((apex - 5.0).abs() < 0.01);
+    assert_eq!(*spline.points.last().unwrap(), [10.0, 0.0]);
+    let ellipse = dxf.geometry.regions.iter().find(|r| r.outer[0][0] > 15.0).unwrap();
+    let expected = std::f32::consts::PI * 4.0 * 2.0;
+    assert!((signed_area(&ellipse.outer) - expected).abs() / expected < 1e-3);
+  }
+
+  #[test]
+  fn spline_normal_does_not_mirror() {
+    // SPLINE points are world coordinates: a (0, 0, -1) plane normal must leave them as drawn,
+    // while a CIRCLE with that extrusion is mirrored
+    let text = dxf(4, &[
+      ("SPLINE", vec![
+        (210, "0".into()), (220, "0".into()), (230, "-1".into()),
+        (70, "8".into()), (71, "2".into()), (72, "6".into()), (73, "3".into()),
+        (40, "0".into()), (40, "0".into()), (40, "0".into()), (40, "1".into()), (40, "1".into()), (40, "1".into()),
+        (10, "0".into()), (20, "0".into()), (10, "5".into()), (20, "10".into()), (10, "10".into()), (20, "0".into()),
+      ]),
+      ("CIRCLE", vec![(10, "20".into()), (20, "0".into()), (40, "1".into()), (230, "-1".into())]),
+    ]);
+    let dxf = import_dxf(&text, &[], 0.01, 0.001).unwrap();
+    let spline = dxf.geometry.outlines.iter().find(|o| !o.closed).unwrap();
+    assert_eq!(spline.points[0], [0.0, 0.0]);
+    assert_eq!(*spline.points.last().unwrap(), [10.0, 0.0]);
+    assert!(spline.points.iter().all(|p| p[0] >= 0.0));
+    let circle = dxf.geometry.outlines.iter().find(|o| o.closed).unwrap();
+    assert!(circle.points.iter().all(|p| (p[0] + 20.0).abs() <= 1.0 + 1e-4));
+  }
+}
@@ -0,0 +1,226 @@
+// Facing (flatten) toolpaths over a rectangular or polygonal area
+// All units are mm unless otherwise noted
+//
+// A flat end mill faces the area in a raster: rows along x, stepover apart in y, zig-zag
+// unless the global cut direction asks for one-way rows, clipped to the area grown by `overshoot`. The raster repeats at each stepdown level from
+// top_z down to target_z. The linked output (passes_to_js "linked") is the flat
+// x0, y0, z0, ... array that simulate_material_removal expects.
+//
+// Assumptions:
+// - overshoot is how far the tool center travels past the area edge: >= the tool radius
+//   clears right to the edge (and off the stock when the area is the whole stock), negative
+//   keeps the whole tool inside the area
+// - Polygon areas are even-odd loops, grown or shrunk with offset_loops; rectangles grow exactly
+// - The last level is exactly target_z; stepdown <= 0 faces in a single level
+// - Nothing is generated when overshoot, stepdown, top_z or target_z is not finite (e.g. an
+//   emptied UI field sent as NaN)
+// - Samples along a row are sample_step apart (<= 0 for half the stepover) and include both row ends
+
+use crate::cut_direction::raster_row_reversed;
+use crate::geometry_2d::{offset_loops, Point2};
+use crate::toolpath::Pass;
+
+#[derive(Clone, Copy, Debug)]
+pub enum FlattenArea<'a> {
+  Rect { min: Point2, max: Point2 },
+  Polygon(&'a [Vec<Point2>]),
+}
+
+pub struct FlattenParams<'a> {
+  pub area: FlattenArea<'a>,
+  pub cutter_diameter: f32,
+  pub stepover_percent: f32, // of the cutter diameter
+  pub overshoot: f32,
+  pub top_z: f32,    // current stock top
+  pub target_z: f32, // faced height
+  pub stepdown: f32, // <= 0 for a single level
+  pub sample_step: f32,
+}
+
+// x intervals where the row y is inside the loops (even-odd)
+fn row_intervals(loops: &[Vec<Point2>], y: f32) -> Vec<(f32, f32)> {
+  let mut xs = Vec::new();
+  for l in loops {
+    for i in 0..l.len() {
+      let (a, b) = (l[i], l[(i + 1) % l.len()]);
+      if (a[1] <= y) != (b[1] <= y) {
+        xs.push(a[0] + (y - a[1]) / (b[1] - a[1]) * (b[0] - a[0]));
+      }
+    }
+  }
+  xs.sort_by(f32::total_cmp);
+  xs.chunks_exact(2).map(|c| (c[0], c[1])).collect()
+}
+
+/// Generate raster facing passes, level by level
+pub fn generate_flatten_toolpath(params: &FlattenParams) -> Vec<Pass> {
+  let stepover = params.cutter_diameter * params.stepover_percent / 100.0;
+  let finite = [params.overshoot, params.stepdown, params.top_z, params.target_z].iter().all(|v| v.is_finite());
+  if !finite || stepover <= 0.0 || params.top_z < params.target_z {
+    return Vec::new();
+  }
+  let step = if params.sample_step > 0.0 { params.sample_step } else { stepover / 2.0 };
+  let loops: Vec<Vec<Point2>> = match params.area {
+    FlattenArea::Rect { min, max } => {
+      let (x0, y0) = (min[0] - params.overshoot, min[1] - params.overshoot);
+      let (x1, y1) = (max[0] + params.overshoot, max[1] + params.overshoot);
+      if x1 <= x0 || y1 <= y0 {
+        return Vec::new();
+      }
+      vec![vec![[x0, y0], [x1, y0], [x1, y1], [x0, y1]]]
+    }
+    FlattenArea::Polygon(loops) if params.overshoot != 0.0 => offset_loops(loops, params.overshoot, stepover / 4.0),
+    FlattenArea::Polygon(loops) => loops.to_vec(),
+  };
+  let points = loops.iter().flatten();
+  let y_min = points.clone().map(|p| p[1]).fold(f32::INFINITY, f32::min);
+  let y_max = points.map(|p| p[1]).fold(f32::NEG_INFINITY, f32::max);
+  if loops.is_empty() || y_max <= y_min {
+    return Vec::new();
+  }
+
+  // Rows spread evenly from edge to edge, at most a stepover apart
+  let rows = ((y_max - y_min) / stepover).ceil().max(1.0) as usize;
+  let row_step = (y_max - y_min) / rows as f32;
+  let mut layer: Vec<Vec<(f32, f32)>> = Vec::new();
+  for row in 0..=rows {
+    // Nudge the edge rows inside so they still intersect the area
+    let y = (y_min + row as f32 * row_step).clamp(y_min + 1e-4, y_max - 1e-4);
+    let mut segments = row_intervals(&loops, y);
+    let reversed = raster_row_reversed().unwrap_or(row % 2 == 1);
+    if reversed {
+      segments.reverse();
+    }
+    for (x0, x1) in segments {
+      let n = ((x1 - x0) / step).ceil().max(1.0) as usize;
+      let mut segment: Vec<(f32, f32)> = (0..=n).map(|i| (x0 + (x1 - x0) * i as f32 / n as f32, y)).collect();
+      if reversed {
+        segment.reverse();
+      }
+      layer.push(segment);
+    }
+  }
+
+  let levels = if params.stepdown > 0.0 { ((params.top_z - params.target_z) / params.stepdown).ceil().max(1.0) as usize } else { 1 };
+  (1..=levels)
+    .flat_map(|i| {
+      let z = if i == levels { params.target_z } else { params.top_z - i as f32 * params.stepdown };
+      layer.iter().map(move |segment| segment.iter().map(|&(x, y)| [x, y, z]).collect::<Pass>())
+    })
+    .collect()
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Float32Array, Object, Uint32Array};
+use crate::toolpath::{passes_to_js, split_polylines};
+
+/// Facing over a rectangle (area_counts empty, area_points = min_x, min_y, max_x, max_y)
+/// or polygon loops (flat x, y with per-loop counts). Returns { passes, linked }.
+#[wasm_bindgen]
+pub fn generate_flatten_toolpath_wasm(
+  area_points_js: &Float32Array,
+  area_counts_js: &Uint32Array,
+  cutter_diameter: f32,
+  stepover_percent: f32,
+  overshoot: f32,
+  top_z: f32,
+  target_z: f32,
+  stepdown: f32,
+  sample_step: f32,
+  clearance_z: f32,
+) -> Object {
+  let points = area_points_js.to_vec();
+  let counts = area_counts_js.to_vec();
+  let loops = split_polylines::<2>(&points, &counts);
+  let area = if counts.is_empty() && points.len() >= 4 {
+    FlattenArea::Rect { min: [points[0], points[1]], max: [points[2], points[3]] }
+  } else {
+    FlattenArea::Polygon(&loops)
+  };
+  let passes = generate_flatten_toolpath(&FlattenParams {
+    area,
+    cutter_diameter,
+    stepover_percent,
+    overshoot,
+    top_z,
+    target_z,
+    stepdown,
+    sample_step,
+  });
+  passes_to_js(&passes, clearance_z.max(top_z))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::geometry_2d::point_in_loops;
+  use crate::heightmap::Heightmap;
+  use crate::simulate_material_removal::{simulate_material_removal, SimulateMaterialRemovalParams};
+  use crate::toolpath::{flatten_points, link_passes};
+
+  // 41 x 41 stock of 0.5 mm cells over [0, 20]^2, top at 0
+  fn simulate(passes: &[Pass]) -> Heightmap {
+    let mut stock = Heightmap::new(41, 41, 0.5, 0.5, 0.0, 0.0, 0.0);
+    let toolpath = flatten_points(&link_passes(passes, 5.0));
+    simulate_material_removal(SimulateMaterialRemovalParams {
+      heightmap: &mut stock,
+      tool_type: "flat",
+      cutter_diameter: 6.0,
+      v_angle_deg: 0.0,
+      toolpath: &toolpath,
+      attributes: None,
+    });
+    stock
+  }
+
+  fn params(area: FlattenArea) -> FlattenParams {
+    FlattenParams {
+      area,
+      cutter_diameter: 6.0,
+      stepover_percent: 70.0,
+      overshoot: 3.0,
+      top_z: 0.0,
+      target_z: -2.5,
+      stepdown: 1.0,
+      sample_step: 0.5,
+    }
+  }
+
+  #[test]
+  fn rect_with_overshoot_faces_whole_stock_in_levels() {
+    let passes = generate_flatten_toolpath(&params(FlattenArea::Rect { min: [0.0, 0.0], max: [20.0, 20.0] }));
+    let mut levels: Vec<f32> = passes.iter().map(|p| p[0][2]).collect();
+    levels.dedup();
+    assert_eq!(levels, vec![-1.0, -2.0, -2.5]);
+    // Tool center runs past every edge by the overshoot
+    let xs = passes.iter().flatten().map(|p| p[0]);
+    assert!((xs.clone().fold(f32::INFINITY, f32::min) + 3.0).abs() < 1e-4);
+    assert!((xs.fold(f32::NEG_INFINITY, f32::max) - 23.0).abs() < 1e-4);
+    // Neighboring rows in a level alternate direction
+    assert!(passes[0][0][0] < passes[0][1][0] && passes[1][0][0] > passes[1][1][0]);
+    assert!(simulate(&passes).heights.iter().all(|&z| (z + 2.5).abs() < 1e-5));
+
+    // A NaN from an emptied UI field gives nothing instead of a panic
+    let rect = FlattenArea::Rect { min: [0.0, 0.0], max: [20.0, 20.0] };
+    assert!(generate_flatten_toolpath(&FlattenParams { overshoot: f32::NAN, ..params(rect) }).is_empty());
+    assert!(generate_flatten_toolpath(&FlattenParams { stepdown: f32::NAN, ..params(rect) }).is_empty());
+  }
+
+  #[test]
+  fn polygon_with_negative_overshoot_stays_inside() {
+    let triangle = vec![vec![[2.0, 2.0], [18.0, 2.0], [10.0, 18.0]]];
+    let passes = generate_flatten_toolpath(&FlattenParams { overshoot: -3.0, stepdown: 0.0, ..params(FlattenArea::Polygon(&triangle)) });
+    assert!(passes.iter().flatten().all(|p| p[2] == -2.5));
+    let stock = simulate(&passes);
+    // Nothing outside the triangle is cut (give or take a cell), its middle is faced
+    let margin = offset_loops(&triangle, 0.5, 0.1);
+    for (k, &z) in stock.heights.iter().enumerate() {
+      let (ix, iy) = stock.node(k);
+      let p = [stock.x(ix), stock.y(iy)];
+      if z < 0.0 {
+        assert!(point_in_loops(p, &margin), "cut outside at {:?}", p);
+      }
+    }
+    assert_eq!(stock.get(20, 14), -2.5);
+  }
+}
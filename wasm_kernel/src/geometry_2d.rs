@@ -0,0 +1,693 @@
+// 2D polygon helpers shared by the toolpath generators
+// All units are mm unless otherwise noted
+//
+// Assumptions:
+// - A closed loop is a list of (x, y) points, the closing point is NOT repeated
+// - Counter-clockwise loops have positive signed area
+
+use crate::heightmap::{grid_index, grid_node};
+
+pub type Point2 = [f32; 2];
+
+/// Signed area of a closed loop (positive for counter-clockwise)
+pub fn signed_area(points: &[Point2]) -> f32 {
+  let n = points.len();
+  if n < 3 {
+    return 0.0;
+  }
+  let mut area = 0.0;
+  for i in 0..n {
+    let a = points[i];
+    let b = points[(i + 1) % n];
+    area += a[0] * b[1] - b[0] * a[1];
+  }
+  area * 0.5
+}
+
+/// Unit direction from a to b, or None if the points coincide
+pub fn direction(a: Point2, b: Point2) -> Option<Point2> {
+  let dx = b[0] - a[0];
+  let dy = b[1] - a[1];
+  let len = (dx * dx + dy * dy).sqrt();
+  if len < 1e-9 {
+    None
+  } else {
+    Some([dx / len, dy / len])
+  }
+}
+
+/// Rotate a vector counter-clockwise by angle (radians)
+pub fn rotate(v: Point2, angle: f32) -> Point2 {
+  let (s, c) = angle.sin_cos();
+  [v[0] * c - v[1] * s, v[0] * s + v[1] * c]
+}
+
+/// Even-odd point in polygon test against a set of closed loops
+pub fn point_in_loops(p: Point2, loops: &[Vec<Point2>]) -> bool {
+  let mut inside = false;
+  for points in loops {
+    let n = points.len();
+    for i in 0..n {
+      let a = points[i];
+      let b = points[(i + 1) % n];
+      if (a[1] > p[1]) != (b[1] > p[1]) {
+        let x = a[0] + (p[1] - a[1]) / (b[1] - a[1]) * (b[0] - a[0]);
+        if p[0] < x {
+          inside = !inside;
+        }
+      }
+    }
+  }
+  inside
+}
+
+/// Distance from p to the segment a-b
+pub fn point_segment_distance(p: Point2, a: Point2, b: Point2) -> f32 {
+  let abx = b[0] - a[0];
+  let aby = b[1] - a[1];
+  let len2 = abx * abx + aby * aby;
+  let t = if len2 > 1e-12 {
+    (((p[0] - a[0]) * abx + (p[1] - a[1]) * aby) / len2).clamp(0.0, 1.0)
+  } else {
+    0.0
+  };
+  let dx = a[0] + abx * t - p[0];
+  let dy = a[1] + aby * t - p[1];
+  (dx * dx + dy * dy).sqrt()
+}
+
+/// Total length of a closed loop
+pub fn loop_length(points: &[Point2]) -> f32 {
+  let n = points.len();
+  (0..n).map(|i| {
+    let a = points[i];
+    let b = points[(i + 1) % n];
+    ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2)).sqrt()
+  }).sum()
+}
+
+/// Iso-contours of a sampled field as closed loops.
+/// values use the ix * ny + iy layout; sample (ix, iy) is at origin + (ix * cell_x, iy * cell_y).
+/// The region value > level is "inside"; loops run counter-clockwise around inside regions
+/// and clockwise around holes. Samples beyond the grid count as outside, so loops always close.
+pub fn marching_squares(
+  values: &[f32],
+  nx: usize,
+  ny: usize,
+  origin: Point2,
+  cell_x: f32,
+  cell_y: f32,
+  level: f32,
+) -> Vec<Vec<Point2>> {
+  use std::collections::HashMap;
+  // Edge keys: (0, ix, iy) is the horizontal edge from sample (ix, iy) to (ix + 1, iy),
+  // (1, ix, iy) is the vertical edge from sample (ix, iy) to (ix, iy + 1)
+  type EdgeKey = (u8, isize, isize);
+  let value = |ix: isize, iy: isize| -> f32 {
+    if ix < 0 || iy < 0 || ix as usize >= nx || iy as usize >= ny {
+      f32::NEG_INFINITY
+    } else {
+      values[grid_index(ny, ix as usize, iy as usize)]
+    }
+  };
+  let position = |ix: isize, iy: isize| -> Point2 {
+    [origin[0] + ix as f32 * cell_x, origin[1] + iy as f32 * cell_y]
+  };
+  let edge_point = |key: EdgeKey| -> Point2 {
+    let (kind, ix, iy) = key;
+    let (jx, jy) = if kind == 0 { (ix + 1, iy) } else { (ix, iy + 1) };
+    let va = value(ix, iy);
+    let vb = value(jx, jy);
+    let t = if va.is_finite() && vb.is_finite() && (vb - va).abs() > 1e-12 {
+      ((level - va) / (vb - va)).clamp(0.0, 1.0)
+    } else if va.is_finite() {
+      0.0
+    } else {
+      1.0
+    };
+    let pa = position(ix, iy);
+    let pb = position(jx, jy);
+    [pa[0] + (pb[0] - pa[0]) * t, pa[1] + (pb[1] - pa[1]) * t]
+  };
+
+  let mut next: HashMap<EdgeKey, EdgeKey> = HashMap::new();
+  for ix in -1..nx as isize {
+    for iy in -1..ny as isize {
+      let v = [value(ix, iy), value(ix + 1, iy), value(ix + 1, iy + 1), value(ix, iy + 1)];
+      let case = (v[0] > level) as u8 | ((v[1] > level) as u8) << 1 | ((v[2] > level) as u8) << 2 | ((v[3] > level) as u8) << 3;
+      // Cell edges: bottom, right, top, left
+      let e = [(0, ix, iy), (1, ix + 1, iy), (0, ix, iy + 1), (1, ix, iy)];
+      let center_inside = || v.iter().filter(|z| z.is_finite()).sum::<f32>() / 4.0 > level;
+      // Oriented segments (from, to) with the inside region on the left
+      let segments: &[(usize, usize)] = match case {
+        1 => &[(0, 3)],
+        2 => &[(1, 0)],
+        3 => &[(1, 3)],
+        4 => &[(2, 1)],
+        5 => if center_inside() { &[(0, 1), (2, 3)] } else { &[(0, 3), (2, 1)] },
+        6 => &[(2, 0)],
+        7 => &[(2, 3)],
+        8 => &[(3, 2)],
+        9 => &[(0, 2)],
+        10 => if center_inside() { &[(3, 0), (1, 2)] } else { &[(1, 0), (3, 2)] },
+        11 => &[(1, 2)],
+        12 => &[(3, 1)],
+        13 => &[(0, 1)],
+        14 => &[(3, 0)],
+        _ => &[],
+      };
+      for &(from, to) in segments {
+        next.insert(e[from], e[to]);
+      }
+    }
+  }
+
+  // Walk the segment graph into loops
+  let mut loops = Vec::new();
+  let mut keys: Vec<EdgeKey> = next.keys().cloned().collect();
+  keys.sort();
+  for start in keys {
+    if !next.contains_key(&start) {
+      continue;
+    }
+    let mut points: Vec<Point2> = Vec::new();
+    let mut key = start;
+    while let Some(following) = next.remove(&key) {
+      let p = edge_point(key);
+      if points.last().is_none_or(|q: &Point2| (q[0] - p[0]).abs() > 1e-7 || (q[1] - p[1]).abs() > 1e-7) {
+        points.push(p);
+      }
+      key = following;
+    }
+    if points.len() > 2 {
+      loops.push(points);
+    }
+  }
+  loops
+}
+
+/// Douglas-Peucker simplification of a closed loop
+pub fn simplify_loop(points: &[Point2], tolerance: f32) -> Vec<Point2> {
+  let n = points.len();
+  if n < 4 {
+    return points.to_vec();
+  }
+  // Split the loop at the point farthest from the first point
+  let first = points[0];
+  let far = (1..n).max_by(|&i, &j| {
+    let di = (points[i][0] - first[0]).powi(2) + (points[i][1] - first[1]).powi(2);
+    let dj = (points[j][0] - first[0]).powi(2) + (points[j][1] - first[1]).powi(2);
+    di.partial_cmp(&dj).unwrap()
+  }).unwrap();
+  let mut keep = vec![false; n];
+  keep[0] = true;
+  keep[far] = true;
+  let mut closed: Vec<Point2> = points.to_vec();
+  closed.push(first);
+  let mut stack = vec![(0, far), (far, n)];
+  while let Some((i0, i1)) = stack.pop() {
+    let mut max_d = 0.0;
+    let mut max_i = i0;
+    for i in i0 + 1..i1 {
+      let d = point_segment_distance(closed[i], closed[i0], closed[i1]);
+      if d > max_d {
+        max_d = d;
+        max_i = i;
+      }
+    }
+    if max_d > tolerance {
+      keep[max_i] = true;
+      stack.push((i0, max_i));
+      stack.push((max_i, i1));
+    }
+  }
+  let mut kept: Vec<Point2> = points.iter().zip(keep).filter(|(_, k)| *k).map(|(p, _)| *p).collect();
+  // The first point is always kept above; drop it too if it lies on the closing segment
+  let m = kept.len();
+  if m > 3 && point_segment_distance(kept[0], kept[m - 1], kept[1]) <= tolerance {
+    kept.remove(0);
+  }
+  kept
+}
+
+/// Even-odd inside test for every node of a grid (ix * ny + iy), scanning each column in y
+pub fn even_odd_grid(loops: &[Vec<Point2>], origin: Point2, cell_x: f32, cell_y: f32, nx: usize, ny: usize) -> Vec<bool> {
+  let mut inside = vec![false; nx * ny];
+  for ix in 0..nx {
+    let x = origin[0] + ix as f32 * cell_x;
+    let mut crossings = Vec::new();
+    for points in loops {
+      let n = points.len();
+      for i in 0..n {
+        let a = points[i];
+        let b = points[(i + 1) % n];
+        if (a[0] > x) != (b[0] > x) {
+          crossings.push(a[1] + (x - a[0]) / (b[0] - a[0]) * (b[1] - a[1]));
+        }
+      }
+    }
+    crossings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mut c = 0;
+    for iy in 0..ny {
+      let y = origin[1] + iy as f32 * cell_y;
+      while c < crossings.len() && crossings[c] <= y {
+        c += 1;
+      }
+      inside[grid_index(ny, ix, iy)] = c % 2 == 1;
+    }
+  }
+  inside
+}
+
+/// Offset closed loops (even-odd filled) by distance: positive grows the region, negative shrinks it.
+/// Works on a signed distance field sampled every `resolution` mm, so self-intersections and
+/// merging or vanishing loops are handled; the result is accurate to a small fraction of resolution.
+pub fn offset_loops(loops: &[Vec<Point2>], distance: f32, resolution: f32) -> Vec<Vec<Point2>> {
+  let all: Vec<Point2> = loops.iter().flatten().cloned().collect();
+  if all.is_empty() || resolution <= 0.0 {
+    return Vec::new();
+  }
+  let band = distance.abs() + 2.0 * resolution;
+  let min_x = all.iter().map(|p| p[0]).fold(f32::INFINITY, f32::min) - band;
+  let min_y = all.iter().map(|p| p[1]).fold(f32::INFINITY, f32::min) - band;
+  let max_x = all.iter().map(|p| p[0]).fold(f32::NEG_INFINITY, f32::max) + band;
+  let max_y = all.iter().map(|p| p[1]).fold(f32::NEG_INFINITY, f32::max) + band;
+  let nx = ((max_x - min_x) / resolution).ceil() as usize + 1;
+  let ny = ((max_y - min_y) / resolution).ceil() as usize + 1;
+
+  // Unsigned distance to the nearest edge, only needed within the band
+  let mut dist = vec![band; nx * ny];
+  for points in loops {
+    let n = points.len();
+    for i in 0..n {
+      let a = points[i];
+      let b = points[(i + 1) % n];
+      let ix0 = ((a[0].min(b[0]) - band - min_x) / resolution).floor().max(0.0) as usize;
+      let ix1 = (((a[0].max(b[0]) + band - min_x) / resolution).ceil() as usize).min(nx - 1);
+      let iy0 = ((a[1].min(b[1]) - band - min_y) / resolution).floor().max(0.0) as usize;
+      let iy1 = (((a[1].max(b[1]) + band - min_y) / resolution).ceil() as usize).min(ny - 1);
+      for ix in ix0..=ix1 {
+        for iy in iy0..=iy1 {
+          let p = [min_x + ix as f32 * resolution, min_y + iy as f32 * resolution];
+          let d = point_segment_distance(p, a, b);
+          let cell = &mut dist[grid_index(ny, ix, iy)];
+          if d < *cell {
+            *cell = d;
+          }
+        }
+      }
+    }
+  }
+
+  // Positive inside the offset region
+  let inside = even_odd_grid(loops, [min_x, min_y], resolution, resolution, nx, ny);
+  let field: Vec<f32> = dist.iter().zip(inside.iter()).map(|(&d, &inside)| if inside { d + distance } else { distance - d }).collect();
+
+  marching_squares(&field, nx, ny, [min_x, min_y], resolution, resolution, 0.0)
+    .iter()
+    .map(|l| simplify_loop(l, resolution * 0.05))
+    .filter(|l| l.len() > 2)
+    .collect()
+}
+
+/// A filled area: one counter-clockwise outer loop and any clockwise hole loops
+#[derive(Clone, Debug, Default)]
+pub struct Region {
+  pub outer: Vec<Point2>,
+  pub holes: Vec<Vec<Point2>>,
+}
+
+impl Region {
+  /// Outer loop followed by the holes, in the even-odd form offset_loops expects
+  pub fn loops(&self) -> Vec<Vec<Point2>> {
+    let mut loops = vec![self.outer.clone()];
+    loops.extend(self.holes.iter().cloned());
+    loops
+  }
+}
+
+/// Flatten regions back to loops (outer loops and holes)
+pub fn regions_to_loops(regions: &[Region]) -> Vec<Vec<Point2>> {
+  regions.iter().flat_map(|r| r.loops()).collect()
+}
+
+/// Group non-crossing loops into regions by nesting depth: loops inside an even number of
+/// other loops are outer boundaries, odd ones are holes of their innermost container.
+/// Loops are re-oriented so outers are counter-clockwise and holes clockwise.
+pub fn classify_loops(loops: Vec<Vec<Point2>>) -> Vec<Region> {
+  let loops: Vec<Vec<Point2>> = loops.into_iter().filter(|l| l.len() > 2 && signed_area(l).abs() > 1e-12).collect();
+  let areas: Vec<f32> = loops.iter().map(|l| signed_area(l).abs()).collect();
+  // Containers of each loop, tested with a vertex (loops don't cross)
+  let containers: Vec<Vec<usize>> = (0..loops.len())
+    .map(|i| {
+      (0..loops.len())
+        .filter(|&j| j != i && areas[j] > areas[i] && point_in_loops(loops[i][0], std::slice::from_ref(&loops[j])))
+        .collect()
+    })
+    .collect();
+  let mut regions: Vec<Region> = Vec::new();
+  let mut region_of = vec![usize::MAX; loops.len()];
+  let mut order: Vec<usize> = (0..loops.len()).collect();
+  order.sort_by_key(|&i| containers[i].len());
+  for i in order {
+    let oriented = |ccw: bool| {
+      let mut l = loops[i].clone();
+      if (signed_area(&l) > 0.0) != ccw {
+        l.reverse();
+      }
+      l
+    };
+    if containers[i].len().is_multiple_of(2) {
+      region_of[i] = regions.len();
+      regions.push(Region { outer: oriented(true), holes: Vec::new() });
+    } else {
+      // Innermost container is the smallest one
+      let parent = *containers[i].iter().min_by(|&&a, &&b| areas[a].partial_cmp(&areas[b]).unwrap()).unwrap();
+      regions[region_of[parent]].holes.push(oriented(false));
+    }
+  }
+  regions
+}
+
+/// Winding number of the loops around p (counter-clockwise turns count positive)
+pub fn winding_number(p: Point2, loops: &[Vec<Point2>]) -> i32 {
+  let mut wn = 0;
+  for points in loops {
+    let n = points.len();
+    for i in 0..n {
+      let a = points[i];
+      let b = points[(i + 1) % n];
+      let side = (b[0] - a[0]) * (p[1] - a[1]) - (p[0] - a[0]) * (b[1] - a[1]);
+      if a[1] <= p[1] {
+        if b[1] > p[1] && side > 0.0 {
+          wn += 1;
+        }
+      } else if b[1] <= p[1] && side < 0.0 {
+        wn -= 1;
+      }
+    }
+  }
+  wn
+}
+
+/// An open or closed 2D polyline (closed ones don't repeat the first point)
+#[derive(Clone, Debug, Default)]
+pub struct Polyline2 {
+  pub points: Vec<Point2>,
+  pub closed: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FillRule {
+  NonZero,
+  EvenOdd,
+}
+
+/// Filled regions of a set of non-crossing loops under a fill rule.
+/// A loop is an outer boundary if the area just inside it is filled and just outside is not,
+/// a hole if the reverse, and is dropped if it doesn't separate filled from unfilled area.
+pub fn fill_regions(loops: &[Vec<Point2>], rule: FillRule) -> Vec<Region> {
+  let filled = |p: Point2| {
+    let wn = winding_number(p, loops);
+    match rule {
+      FillRule::NonZero => wn != 0,
+      FillRule::EvenOdd => wn % 2 != 0,
+    }
+  };
+  let mut outers: Vec<Vec<Point2>> = Vec::new();
+  let mut holes: Vec<Vec<Point2>> = Vec::new();
+  for l in loops {
+    let area = signed_area(l);
+    if l.len() < 3 || area.abs() < 1e-12 {
+      continue;
+    }
+    // Probe both sides of the longest edge
+    let n = l.len();
+    let i = (0..n).max_by(|&i, &j| {
+      let li = (l[(i + 1) % n][0] - l[i][0]).hypot(l[(i + 1) % n][1] - l[i][1]);
+      let lj = (l[(j + 1) % n][0] - l[j][0]).hypot(l[(j + 1) % n][1] - l[j][1]);
+      li.partial_cmp(&lj).unwrap()
+    }).unwrap();
+    let (a, b) = (l[i], l[(i + 1) % n]);
+    let Some(d) = direction(a, b) else { continue };
+    let eps = area.abs().sqrt() * 1e-4;
+    let m = [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0];
+    // Left of travel is inside for a counter-clockwise loop
+    let s = if area > 0.0 { eps } else { -eps };
+    let inside = filled([m[0] - d[1] * s, m[1] + d[0] * s]);
+    let outside = filled([m[0] + d[1] * s, m[1] - d[0] * s]);
+    let mut l = l.clone();
+    match (inside, outside) {
+      (true, false) => {
+        if area < 0.0 {
+          l.reverse();
+        }
+        outers.push(l);
+      }
+      (false, true) => {
+        if area > 0.0 {
+          l.reverse();
+        }
+        holes.push(l);
+      }
+      _ => {}
+    }
+  }
+  let mut regions: Vec<Region> = outers.into_iter().map(|outer| Region { outer, holes: Vec::new() }).collect();
+  for hole in holes {
+    // Innermost (smallest) outer containing the hole
+    let parent = (0..regions.len())
+      .filter(|&r| point_in_loops(hole[0], std::slice::from_ref(&regions[r].outer)))
+      .min_by(|&a, &b| signed_area(&regions[a].outer).partial_cmp(&signed_area(&regions[b].outer)).unwrap());
+    if let Some(r) = parent {
+      regions[r].holes.push(hole);
+    }
+  }
+  regions
+}
+
+/// Intersection parameters (t on a-b, u on c-d) of two segments, if they cross or touch
+pub fn segment_intersection(a: Point2, b: Point2, c: Point2, d: Point2) -> Option<(f32, f32)> {
+  let r = [b[0] - a[0], b[1] - a[1]];
+  let s = [d[0] - c[0], d[1] - c[1]];
+  let denom = r[0] * s[1] - r[1] * s[0];
+  if denom.abs() < 1e-12 {
+    return None;
+  }
+  let qp = [c[0] - a[0], c[1] - a[1]];
+  let t = (qp[0] * s[1] - qp[1] * s[0]) / denom;
+  let u = (qp[0] * r[1] - qp[1] * r[0]) / denom;
+  if (-1e-6..=1.0 + 1e-6).contains(&t) && (-1e-6..=1.0 + 1e-6).contains(&u) {
+    Some((t.clamp(0.0, 1.0), u.clamp(0.0, 1.0)))
+  } else {
+    None
+  }
+}
+
+/// Zhang-Suen thinning of a grid mask (ix * ny + iy) to one cell wide, keeping connectivity.
+/// Uses the Lu-Wang neighbor count (3..6) so two cell thick diagonal branches aren't erased.
+pub fn thin_mask(mask: &mut [bool], nx: usize, ny: usize) {
+  let at = |mask: &[bool], ix: isize, iy: isize| -> bool {
+    ix >= 0 && iy >= 0 && (ix as usize) < nx && (iy as usize) < ny && mask[grid_index(ny, ix as usize, iy as usize)]
+  };
+  // Neighbors clockwise from north
+  const RING: [(isize, isize); 8] = [(0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1)];
+  loop {
+    let mut changed = false;
+    for step in 0..2 {
+      let mut remove = Vec::new();
+      for ix in 0..nx as isize {
+        for iy in 0..ny as isize {
+          if !at(mask, ix, iy) {
+            continue;
+          }
+          let p: Vec<bool> = RING.iter().map(|&(dx, dy)| at(mask, ix + dx, iy + dy)).collect();
+          let count = p.iter().filter(|&&v| v).count();
+          let transitions = (0..8).filter(|&i| !p[i] && p[(i + 1) % 8]).count();
+          let (n, e, s, w) = (p[0], p[2], p[4], p[6]);
+          let ok = if step == 0 { !(e && s && (n || w)) } else { !(n && w && (e || s)) };
+          if (3..=6).contains(&count) && transitions == 1 && ok {
+            remove.push(grid_index(ny, ix as usize, iy as usize));
+          }
+        }
+      }
+      changed |= !remove.is_empty();
+      for k in remove {
+        mask[k] = false;
+      }
+    }
+    if !changed {
+      break;
+    }
+  }
+}
+
+/// Trace a one cell wide mask into polylines of node indices, breaking at ends and junctions
+pub fn trace_skeleton(mask: &[bool], nx: usize, ny: usize) -> Vec<Vec<usize>> {
+  let on = |ix: isize, iy: isize| ix >= 0 && iy >= 0 && (ix as usize) < nx && (iy as usize) < ny && mask[grid_index(ny, ix as usize, iy as usize)];
+  // 4-neighbors, plus diagonals that aren't already joined through a 4-neighbor
+  let neighbors = |k: usize| -> Vec<usize> {
+    let (ix, iy) = grid_node(ny, k);
+    let (ix, iy) = (ix as isize, iy as isize);
+    let mut result = Vec::new();
+    for dx in -1..=1isize {
+      for dy in -1..=1isize {
+        if (dx == 0 && dy == 0) || !on(ix + dx, iy + dy) {
+          continue;
+        }
+        if dx != 0 && dy != 0 && (on(ix + dx, iy) || on(ix, iy + dy)) {
+          continue;
+        }
+        result.push(grid_index(ny, (ix + dx) as usize, (iy + dy) as usize));
+      }
+    }
+    result
+  };
+  let nodes: Vec<usize> = (0..nx * ny).filter(|&k| mask[k]).collect();
+  let mut visited = std::collections::HashSet::new();
+  let mut paths = Vec::new();
+  let walk = |start: usize, next: usize, visited: &mut std::collections::HashSet<(usize, usize)>| -> Vec<usize> {
+    let mut path = vec![start];
+    let (mut prev, mut cur) = (start, next);
+    visited.insert((prev.min(cur), prev.max(cur)));
+    loop {
+      path.push(cur);
+      let ns = neighbors(cur);
+      if ns.len() != 2 {
+        break;
+      }
+      let nxt = if ns[0] == prev { ns[1] } else { ns[0] };
+      if !visited.insert((cur.min(nxt), cur.max(nxt))) {
+        break;
+      }
+      prev = cur;
+      cur = nxt;
+    }
+    path
+  };
+  // Open branches first, starting from ends and junctions, then leftover cycles
+  for pass in 0..2 {
+    for &k in &nodes {
+      let ns = neighbors(k);
+      if pass == 0 && ns.len() == 2 {
+        continue;
+      }
+      if ns.is_empty() {
+        paths.push(vec![k]);
+        continue;
+      }
+      for n in ns {
+        if !visited.contains(&(k.min(n), k.max(n))) {
+          paths.push(walk(k, n, &mut visited));
+        }
+      }
+    }
+  }
+  paths
+}
+
+use js_sys::{Float32Array, Object, Uint32Array, Uint8Array};
+
+/// Convert regions to a JS object: { points: Float32Array (x, y pairs), counts: Uint32Array,
+/// regions: Uint32Array (region index of each loop) }. The first loop of each region is its outer loop.
+pub fn regions_to_js(regions: &[Region]) -> Object {
+  let mut points = Vec::new();
+  let mut counts = Vec::new();
+  let mut region_index = Vec::new();
+  for (i, region) in regions.iter().enumerate() {
+    for l in region.loops() {
+      points.extend(l.iter().flat_map(|p| p.iter().cloned()));
+      counts.push(l.len() as u32);
+      region_index.push(i as u32);
+    }
+  }
+  let result = Object::new();
+  js_sys::Reflect::set(&result, &"points".into(), &Float32Array::from(points.as_slice())).unwrap();
+  js_sys::Reflect::set(&result, &"counts".into(), &Uint32Array::from(counts.as_slice())).unwrap();
+  js_sys::Reflect::set(&result, &"regions".into(), &Uint32Array::from(region_index.as_slice())).unwrap();
+  result
+}
+
+/// Convert polylines to a JS object: { points: Float32Array (x, y pairs), counts: Uint32Array,
+/// closed: Uint8Array (1 for closed polylines) }
+pub fn polylines_to_js(polylines: &[Polyline2]) -> Object {
+  let points: Vec<f32> = polylines.iter().flat_map(|p| p.points.iter().flat_map(|q| q.iter().cloned())).collect();
+  let counts: Vec<u32> = polylines.iter().map(|p| p.points.len() as u32).collect();
+  let closed: Vec<u8> = polylines.iter().map(|p| p.closed as u8).collect();
+  let result = Object::new();
+  js_sys::Reflect::set(&result, &"points".into(), &Float32Array::from(points.as_slice())).unwrap();
+  js_sys::Reflect::set(&result, &"counts".into(), &Uint32Array::from(counts.as_slice())).unwrap();
+  js_sys::Reflect::set(&result, &"closed".into(), &Uint8Array::from(closed.as_slice())).unwrap();
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn signed_area_is_positive_for_ccw_square() {
+    let square = vec![[0.0, 0.0], [2.0, 0.0], [2.0, 2.0], [0.0, 2.0]];
+    assert!((signed_area(&square) - 4.0).abs() < 1e-6);
+    let reversed: Vec<Point2> = square.iter().rev().cloned().collect();
+    assert!((signed_area(&reversed) + 4.0).abs() < 1e-6);
+  }
+
+  #[test]
+  fn marching_squares_outlines_block() {
+    // 5x5 samples, 3x3 block of ones in the middle
+    let n = 5;
+    let mut values = vec![0.0; n * n];
+    for ix in 1..4 {
+      for iy in 1..4 {
+        values[ix * n + iy] = 1.0;
+      }
+    }
+    let loops = marching_squares(&values, n, n, [0.0, 0.0], 1.0, 1.0, 0.5);
+    assert_eq!(loops.len(), 1);
+    // Boundary runs halfway between samples: a 3x3 square from 0.5 to 3.5 with its corners cut
+    assert!((signed_area(&loops[0]) - 8.5).abs() < 1e-4, "area {}", signed_area(&loops[0]));
+  }
+
+  #[test]
+  fn offset_square_grows_and_shrinks() {
+    let square = vec![vec![[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0]]];
+    let grown = offset_loops(&square, 1.0, 0.1);
+    assert_eq!(grown.len(), 1);
+    // 12x12 square with rounded corners: 144 - (4 - pi)
+    let expected = 144.0 - (4.0 - std::f32::consts::PI);
+    assert!((signed_area(&grown[0]) - expected).abs() < 0.2, "area {}", signed_area(&grown[0]));
+    let shrunk = offset_loops(&square, -1.0, 0.1);
+    assert_eq!(shrunk.len(), 1);
+    assert!((signed_area(&shrunk[0]) - 64.0).abs() < 0.2, "area {}", signed_area(&shrunk[0]));
+    assert!(offset_loops(&square, -6.0, 0.1).is_empty());
+  }
+
+  #[test]
+  fn classify_loops_finds_holes() {
+    let outer = vec![[0.0, 0.0], [0.0, 10.0], [10.0, 10.0], [10.0, 0.0]]; // clockwise on purpose
+    let hole = vec![[2.0, 2.0], [4.0, 2.0], [4.0, 4.0], [2.0, 4.0]];
+    let island = vec![[2.5, 2.5], [3.5, 2.5], [3.5, 3.5], [2.5, 3.5]];
+    let regions = classify_loops(vec![island, outer, hole]);
+    assert_eq!(regions.len(), 2);
+    assert!(signed_area(&regions[0].outer) > 99.0);
+    assert_eq!(regions[0].holes.len(), 1);
+    assert!(signed_area(&regions[0].holes[0]) < 0.0);
+    assert!((signed_area(&regions[1].outer) - 1.0).abs() < 1e-6);
+  }
+
+  #[test]
+  fn fill_rule_decides_nested_loops() {
+    let outer = vec![[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0]];
+    let inner_same = vec![[3.0, 3.0], [7.0, 3.0], [7.0, 7.0], [3.0, 7.0]];
+    let loops = vec![outer, inner_same];
+    // Same direction: nonzero fills the inner loop, even-odd makes it a hole
+    let nonzero = fill_regions(&loops, FillRule::NonZero);
+    assert_eq!(nonzero.len(), 1);
+    assert!(nonzero[0].holes.is_empty());
+    let evenodd = fill_regions(&loops, FillRule::EvenOdd);
+    assert_eq!(evenodd.len(), 1);
+    assert_eq!(evenodd[0].holes.len(), 1);
+    assert!(signed_area(&evenodd[0].holes[0]) < 0.0);
+  }
+}
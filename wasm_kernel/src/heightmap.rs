@@ -0,0 +1,291 @@
+// Heightmap: a grid of z values, the one representation every kernel module shares
+// All units are mm unless otherwise noted
+//
+// Node (ix, iy) sits at (origin_x + ix * grid_size_x, origin_y + iy * grid_size_y) and its
+// height is stored at heights[ix * ny + iy] (x-major: all of column ix, then column ix + 1).
+// Every module indexes through index() / get() / set() instead of spelling the layout out
+// (grid_index() / grid_node() for masks and fields laid out the same way without a Heightmap),
+// and wasm bindings build a Heightmap from their flat arguments, so data can pass from one
+// module to the next without being transposed.
+//
+// Assumptions:
+// - The grid covers x in [origin_x, origin_x + (nx - 1) * grid_size_x], likewise for y
+// - Lookups by position go to the nearest node; positions off the grid have no height
+// - Heights may be -inf where there is no surface (heightmap_from_mesh_rust leaves cells no
+//   triangle covers that way)
+
+/// Position of node (ix, iy) in any array with the heightmap layout and ny nodes per column
+pub fn grid_index(ny: usize, ix: usize, iy: usize) -> usize {
+  ix * ny + iy
+}
+
+/// Node (ix, iy) of a position in any array with the heightmap layout and ny nodes per column
+pub fn grid_node(ny: usize, k: usize) -> (usize, usize) {
+  (k / ny, k % ny)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Heightmap {
+  pub nx: usize,
+  pub ny: usize,
+  pub grid_size_x: f32,
+  pub grid_size_y: f32,
+  pub origin_x: f32,
+  pub origin_y: f32,
+  pub heights: Vec<f32>, // heights[ix * ny + iy]
+}
+
+impl Heightmap {
+  /// nx by ny nodes, all at height z
+  pub fn new(nx: usize, ny: usize, grid_size_x: f32, grid_size_y: f32, origin_x: f32, origin_y: f32, z: f32) -> Heightmap {
+    Heightmap { nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, heights: vec![z; nx * ny] }
+  }
+
+  /// Wrap heights already in ix * ny + iy order; panics if the length doesn't match nx * ny
+  pub fn from_vec(nx: usize, ny: usize, grid_size_x: f32, grid_size_y: f32, origin_x: f32, origin_y: f32, heights: Vec<f32>) -> Heightmap {
+    if heights.len() != nx * ny {
+      panic!("heightmap length {} does not match nx*ny {}", heights.len(), nx * ny);
+    }
+    Heightmap { nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, heights }
+  }
+
+  /// Same grid, new heights
+  pub fn with_heights(&self, heights: Vec<f32>) -> Heightmap {
+    Heightmap::from_vec(self.nx, self.ny, self.grid_size_x, self.grid_size_y, self.origin_x, self.origin_y, heights)
+  }
+
+  /// Whether both heightmaps have the same nodes
+  pub fn same_grid(&self, other: &Heightmap) -> bool {
+    self.nx == other.nx
+      && self.ny == other.ny
+      && self.grid_size_x == other.grid_size_x
+      && self.grid_size_y == other.grid_size_y
+      && self.origin_x == other.origin_x
+      && self.origin_y == other.origin_y
+  }
+
+  /// Position of node (ix, iy) in the heights array
+  pub fn index(&self, ix: usize, iy: usize) -> usize {
+    grid_index(self.ny, ix, iy)
+  }
+
+  /// Node (ix, iy) of a position in the heights array
+  pub fn node(&self, k: usize) -> (usize, usize) {
+    grid_node(self.ny, k)
+  }
+
+  pub fn get(&self, ix: usize, iy: usize) -> f32 {
+    self.heights[self.index(ix, iy)]
+  }
+
+  pub fn set(&mut self, ix: usize, iy: usize, z: f32) {
+    let k = self.index(ix, iy);
+    self.heights[k] = z;
+  }
+
+  pub fn x(&self, ix: usize) -> f32 {
+    self.origin_x + ix as f32 * self.grid_size_x
+  }
+
+  pub fn y(&self, iy: usize) -> f32 {
+    self.origin_y + iy as f32 * self.grid_size_y
+  }
+
+  /// Distance from the first to the last node along x
+  pub fn width(&self) -> f32 {
+    self.nx.saturating_sub(1) as f32 * self.grid_size_x
+  }
+
+  /// Distance from the first to the last node along y
+  pub fn depth(&self) -> f32 {
+    self.ny.saturating_sub(1) as f32 * self.grid_size_y
+  }
+
+  /// Node nearest (x, y), None off the grid
+  pub fn nearest(&self, x: f32, y: f32) -> Option<(usize, usize)> {
+    let ix = ((x - self.origin_x) / self.grid_size_x).round();
+    let iy = ((y - self.origin_y) / self.grid_size_y).round();
+    if ix < 0.0 || iy < 0.0 || ix as usize >= self.nx || iy as usize >= self.ny {
+      return None;
+    }
+    Some((ix as usize, iy as usize))
+  }
+
+  /// Node nearest (x, y), clamped onto the grid
+  pub fn nearest_clamped(&self, x: f32, y: f32) -> (usize, usize) {
+    let ix = ((x - self.origin_x) / self.grid_size_x).round().max(0.0) as usize;
+    let iy = ((y - self.origin_y) / self.grid_size_y).round().max(0.0) as usize;
+    (ix.min(self.nx.saturating_sub(1)), iy.min(self.ny.saturating_sub(1)))
+  }
+
+  /// Height of the node nearest (x, y), None off the grid
+  pub fn height_at(&self, x: f32, y: f32) -> Option<f32> {
+    self.nearest(x, y).map(|(ix, iy)| self.get(ix, iy))
+  }
+
+  /// Nodes (in heights order) more than threshold above the same node of other, e.g. stock
+  /// still above the part; other must be on the same grid
+  pub fn above(&self, other: &Heightmap, threshold: f32) -> Vec<bool> {
+    self.heights.iter().zip(&other.heights).map(|(&s, &o)| s - o > threshold).collect()
+  }
+}
+
+/// Nodes ix0..ix1 by iy0..iy1 (end exclusive), e.g. the part of a heightmap a cut changed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NodeRect {
+  pub ix0: usize,
+  pub iy0: usize,
+  pub ix1: usize,
+  pub iy1: usize,
+}
+
+impl NodeRect {
+  pub fn area(&self) -> usize {
+    (self.ix1 - self.ix0) * (self.iy1 - self.iy0)
+  }
+
+  /// Smallest rect covering both
+  pub fn union(&self, other: &NodeRect) -> NodeRect {
+    NodeRect {
+      ix0: self.ix0.min(other.ix0),
+      iy0: self.iy0.min(other.iy0),
+      ix1: self.ix1.max(other.ix1),
+      iy1: self.iy1.max(other.iy1),
+    }
+  }
+
+  /// Nodes in both
+  pub fn overlap_area(&self, other: &NodeRect) -> usize {
+    let w = self.ix1.min(other.ix1).saturating_sub(self.ix0.max(other.ix0));
+    let h = self.iy1.min(other.iy1).saturating_sub(self.iy0.max(other.iy0));
+    w * h
+  }
+}
+
+/// Rects changed by a run of cuts, in order. Each new rect is merged into the last one while
+/// the merged box stays within twice the nodes the merged rects dirtied (counting each rect's
+/// overlap with the one before it once), so a long row yields one rect and a diagonal a chain
+/// of boxes a few footprints across
+#[derive(Clone, Debug, Default)]
+pub struct DirtyRects {
+  pub rects: Vec<NodeRect>,
+  previous: Option<NodeRect>, // last rect pushed, before merging
+  dirtied: usize,             // nodes dirtied by the rects merged into the last one
+}
+
+impl DirtyRects {
+  pub fn push(&mut self, rect: NodeRect) {
+    let added = rect.area() - self.previous.map_or(0, |p| p.overlap_area(&rect));
+    self.previous = Some(rect);
+    if let Some(last) = self.rects.last_mut() {
+      let merged = last.union(&rect);
+      if merged.area() <= 2 * (self.dirtied + added) {
+        *last = merged;
+        self.dirtied += added;
+        return;
+      }
+    }
+    self.rects.push(rect);
+    self.dirtied = rect.area();
+  }
+}
+
+/// Optional per-node layers kept beside a heightmap (same index()), stamped as nodes are cut:
+/// operation and tool ids (0 until first cut) and the time of the last cut (-1 until then)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NodeAttributes {
+  pub operation_id: Option<Vec<u32>>,
+  pub tool_id: Option<Vec<u32>>,
+  pub cut_time: Option<Vec<f32>>,
+}
+
+impl NodeAttributes {
+  /// Layers for every node of the heightmap, only those asked for
+  pub fn new(heightmap: &Heightmap, operation_id: bool, tool_id: bool, cut_time: bool) -> NodeAttributes {
+    let n = heightmap.nx * heightmap.ny;
+    NodeAttributes {
+      operation_id: operation_id.then(|| vec![0; n]),
+      tool_id: tool_id.then(|| vec![0; n]),
+      cut_time: cut_time.then(|| vec![-1.0; n]),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::heightmap_from_mesh::{heightmap_from_mesh_rust, HeightmapGridRust, MeshRust};
+  use crate::heightmap_to_solid_mesh::heightmap_to_solid_mesh;
+  use crate::simulate_material_removal::{simulate_material_removal, SimulateMaterialRemovalParams};
+
+  // Ramp rising along x only, 30 mm by 12 mm: a transposed layout would make it rise along y
+  fn ramp() -> MeshRust {
+    let positions = vec![
+      0.0, 0.0, 0.0, 30.0, 0.0, 6.0, 30.0, 12.0, 6.0, //
+      0.0, 0.0, 0.0, 30.0, 12.0, 6.0, 0.0, 12.0, 0.0,
+    ];
+    MeshRust { positions, indices: None }
+  }
+
+  #[test]
+  fn layout_and_lookup() {
+    let mut map = Heightmap::new(4, 3, 1.0, 2.0, 10.0, 20.0, 0.0);
+    map.set(3, 1, 5.0);
+    assert_eq!(map.heights[3 * 3 + 1], 5.0);
+    assert_eq!(map.node(map.index(3, 1)), (3, 1));
+    assert_eq!(map.height_at(13.2, 21.9), Some(5.0));
+    assert_eq!(map.height_at(9.0, 20.0), None);
+    assert_eq!(map.nearest_clamped(100.0, -5.0), (3, 0));
+    assert_eq!((map.width(), map.depth()), (3.0, 4.0));
+  }
+
+  #[test]
+  fn dirty_rects_merge_rows_and_stay_tight_along_diagonals() {
+    let square = |ix: usize, iy: usize| NodeRect { ix0: ix, iy0: iy, ix1: ix + 10, iy1: iy + 10 };
+    let mut row = DirtyRects::default();
+    for i in 0..100 {
+      row.push(square(i, 0));
+    }
+    assert_eq!(row.rects, vec![NodeRect { ix0: 0, iy0: 0, ix1: 109, iy1: 10 }]);
+
+    // Each diagonal step dirties 19 new nodes, so a box stops growing a few footprints across
+    let mut diagonal = DirtyRects::default();
+    for i in 0..100 {
+      diagonal.push(square(i, i));
+    }
+    assert!(diagonal.rects.len() > 3);
+    assert!(diagonal.rects.iter().all(|r| r.area() <= 2 * (100 + 19 * (r.ix1 - r.ix0 - 10))), "{:?}", diagonal.rects);
+  }
+
+  #[test]
+  fn mesh_to_simulation_to_solid_mesh_on_non_square_grid() {
+    let grid = HeightmapGridRust { min_x: 0.0, max_x: 30.0, min_y: 0.0, max_y: 12.0, res_x: 31, res_y: 13 };
+    let mut map = heightmap_from_mesh_rust(&ramp(), &grid);
+    assert_eq!((map.nx, map.ny), (31, 13));
+    // The ramp rises along x and is flat along y
+    for ix in 0..31 {
+      for iy in 0..13 {
+        assert!((map.get(ix, iy) - ix as f32 * 0.2).abs() < 1e-4, "({}, {}) = {}", ix, iy, map.get(ix, iy));
+      }
+    }
+    // A flat end mill plunged at (25, 3) cuts where the ramp is high, nowhere else
+    simulate_material_removal(SimulateMaterialRemovalParams {
+      heightmap: &mut map,
+      tool_type: "flat",
+      cutter_diameter: 2.0,
+      v_angle_deg: 0.0,
+      toolpath: &[25.0, 3.0, 2.0],
+      attributes: None,
+    });
+    assert_eq!(map.height_at(25.0, 3.0), Some(2.0));
+    assert_eq!(map.height_at(25.0, 5.0), Some(5.0));
+    // The solid mesh puts every top vertex where the heightmap says
+    let mesh = heightmap_to_solid_mesh(&map, -1.0);
+    for ix in 0..31 {
+      for iy in 0..13 {
+        let v = map.index(ix, iy) * 3;
+        assert_eq!(&mesh.positions[v..v + 3], &[map.x(ix), map.y(iy), map.get(ix, iy)]);
+      }
+    }
+  }
+}
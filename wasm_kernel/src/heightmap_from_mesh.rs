@@ -2,9 +2,9 @@
 // Port of JS heightmap_from_mesh to Rust/WASM
 // Converts a triangle mesh to a 2D heightmap grid (z=max at each cell)
 // Assumptions: mesh is a list of triangles, grid is axis-aligned, units are mm
+// The result is a Heightmap in the shared ix * ny + iy layout; uncovered cells are -inf
 
-use wasm_bindgen::prelude::*;
-use js_sys::{Float32Array, Uint32Array};
+use crate::heightmap::Heightmap;
 
 pub struct MeshRust {
   pub positions: Vec<f32>,
@@ -20,30 +20,13 @@ pub struct HeightmapGridRust {
   pub res_y: usize,
 }
 
-#[cfg(test)]
-mod tests {
-  use super::*;
-  #[test]
-  fn test_flat_triangle() {
-    let positions = vec![0.0, 0.0, 5.0, 2.0, 0.0, 5.0, 0.0, 2.0, 5.0];
-    let mesh = MeshRust { positions, indices: None };
-    let grid = HeightmapGridRust {
-      min_x: 0.0, max_x: 1.0, min_y: 0.0, max_y: 1.0, res_x: 2, res_y: 2
-    };
-    let heightmap = heightmap_from_mesh_rust(&mesh, &grid);
-    println!("heightmap: {:?}", heightmap);
-    assert_eq!(heightmap.len(), 4);
-    for (i, &z) in heightmap.iter().enumerate() {
-      assert_eq!(z, 5.0, "cell {} was not set correctly (got {})", i, z);
-    }
-  }
-}
-
 /// Compute a 2D heightmap from a triangle mesh. Each cell gets the max z of any triangle covering it.
-pub fn heightmap_from_mesh_rust(mesh: &MeshRust, grid: &HeightmapGridRust) -> Vec<f32> {
+pub fn heightmap_from_mesh_rust(mesh: &MeshRust, grid: &HeightmapGridRust) -> Heightmap {
   let nx = grid.res_x;
   let ny = grid.res_y;
-  let mut heightmap = vec![f32::NEG_INFINITY; nx * ny];
+  let grid_size_x = (grid.max_x - grid.min_x) / (nx as f32 - 1.0);
+  let grid_size_y = (grid.max_y - grid.min_y) / (ny as f32 - 1.0);
+  let mut heightmap = Heightmap::new(nx, ny, grid_size_x, grid_size_y, grid.min_x, grid.min_y, f32::NEG_INFINITY);
   let positions = &mesh.positions;
   let indices = mesh.indices.as_ref();
   // Helper to get vertex by index
@@ -58,7 +41,6 @@ pub fn heightmap_from_mesh_rust(mesh: &MeshRust, grid: &HeightmapGridRust) -> Ve
     positions.len() / 9
   };
 
-  // Use row-major order: iy * nx + ix
   for t in 0..triangle_count {
     let (a, b, c) = if let Some(idxs) = indices {
       (get_vertex(idxs[t * 3] as usize), get_vertex(idxs[t * 3 + 1] as usize), get_vertex(idxs[t * 3 + 2] as usize))
@@ -69,22 +51,19 @@ pub fn heightmap_from_mesh_rust(mesh: &MeshRust, grid: &HeightmapGridRust) -> Ve
     let max_x = a[0].max(b[0]).max(c[0]);
     let min_y = a[1].min(b[1]).min(c[1]);
     let max_y = a[1].max(b[1]).max(c[1]);
-    let grid_size_x = (grid.max_x - grid.min_x) / (nx as f32 - 1.0);
-    let grid_size_y = (grid.max_y - grid.min_y) / (ny as f32 - 1.0);
     let ix0 = ((min_x - grid.min_x) / grid_size_x).floor().max(0.0) as usize;
     let ix1 = ((max_x - grid.min_x) / grid_size_x).ceil().min((nx - 1) as f32) as usize;
     let iy0 = ((min_y - grid.min_y) / grid_size_y).floor().max(0.0) as usize;
     let iy1 = ((max_y - grid.min_y) / grid_size_y).ceil().min((ny - 1) as f32) as usize;
     for iy in iy0..=iy1 {
       for ix in ix0..=ix1 {
-        let x = grid.min_x + ix as f32 * grid_size_x;
-        let y = grid.min_y + iy as f32 * grid_size_y;
+        let x = heightmap.x(ix);
+        let y = heightmap.y(iy);
         let inside = point_in_triangle_2d(x, y, a, b, c);
         if inside {
           let z = interpolate_z(x, y, a, b, c);
-          let idx = iy * nx + ix;
-          if z > heightmap[idx] {
-            heightmap[idx] = z;
+          if z > heightmap.get(ix, iy) {
+            heightmap.set(ix, iy, z);
           }
         }
       }
@@ -95,12 +74,12 @@ pub fn heightmap_from_mesh_rust(mesh: &MeshRust, grid: &HeightmapGridRust) -> Ve
   // After main loop, if all values except -inf are the same, fill -inf cells with that value.
   let mut max_z = f32::NEG_INFINITY;
   let mut min_z = f32::INFINITY;
-  for &z in &heightmap {
+  for &z in &heightmap.heights {
     if z > max_z { max_z = z; }
     if z < min_z && z > f32::NEG_INFINITY { min_z = z; }
   }
   if max_z == min_z && max_z > f32::NEG_INFINITY {
-    for z in &mut heightmap {
+    for z in &mut heightmap.heights {
       if *z == f32::NEG_INFINITY {
         *z = max_z;
       }
@@ -132,3 +111,22 @@ fn interpolate_z(x: f32, y: f32, a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> f32 {
   let l3 = 1.0 - l1 - l2;
   l1 * z0 + l2 * z1 + l3 * z2
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  #[test]
+  fn test_flat_triangle() {
+    let positions = vec![0.0, 0.0, 5.0, 2.0, 0.0, 5.0, 0.0, 2.0, 5.0];
+    let mesh = MeshRust { positions, indices: None };
+    let grid = HeightmapGridRust {
+      min_x: 0.0, max_x: 1.0, min_y: 0.0, max_y: 1.0, res_x: 2, res_y: 2
+    };
+    let heightmap = heightmap_from_mesh_rust(&mesh, &grid);
+    println!("heightmap: {:?}", heightmap);
+    assert_eq!(heightmap.heights.len(), 4);
+    for (i, &z) in heightmap.heights.iter().enumerate() {
+      assert_eq!(z, 5.0, "cell {} was not set correctly (got {})", i, z);
+    }
+  }
+}
@@ -18,7 +18,7 @@ mod tests {
     assert!((pos[2] - 5.0).abs() < 1e-6);
     // First bottom vertex should be at (-60, -50, min_z)
     let nxy = 11 * 11;
-    assert!((pos[nxy * 3 + 0] - -60.0).abs() < 1e-6);
+    assert!((pos[nxy * 3] - -60.0).abs() < 1e-6);
     assert!((pos[nxy * 3 + 1] - -50.0).abs() < 1e-6);
     assert!((pos[nxy * 3 + 2] - min_z).abs() < 1e-6);
   }
@@ -33,6 +33,61 @@ mod tests {
     assert!(normals[1].abs() < 0.1); // y component close to 0
   }
 
+  #[test]
+  fn vertex_values_follow_the_mesh_vertices() {
+    let mut stock = create_heightmap_stock(10.0, 6.0, 11, 7, 5.0, 0.0, 0.0);
+    stock.set(4, 2, 3.0);
+    // A layer that names each node, so every vertex can be traced back to its node
+    let layer: Vec<u32> = (0..stock.heights.len() as u32).collect();
+    let rect = NodeRect { ix0: 2, iy0: 1, ix1: 9, iy1: 6 };
+    let mesh = solid_mesh_rect(&stock, 0.0, rect);
+    let values = solid_mesh_vertex_values(&stock, &layer, rect);
+    assert_eq!(values.len() * 3, mesh.positions.len());
+    for (v, &k) in values.iter().enumerate() {
+      let (ix, iy) = stock.node(k as usize);
+      assert_eq!(&mesh.positions[v * 3..v * 3 + 2], &[stock.x(ix), stock.y(iy)]);
+    }
+  }
+
+  #[test]
+  #[should_panic(expected = "node layer length 76 does not match nx*ny 77")]
+  fn short_layer_is_rejected() {
+    let stock = create_heightmap_stock(10.0, 6.0, 11, 7, 5.0, 0.0, 0.0);
+    solid_mesh_vertex_values(&stock, &[0u32; 76], NodeRect { ix0: 0, iy0: 0, ix1: 11, iy1: 7 });
+  }
+
+  #[test]
+  fn patches_match_a_full_remesh() {
+    use crate::simulate_material_removal::{simulate_material_removal, SimulateMaterialRemovalParams};
+    let mut stock = create_heightmap_stock(40.0, 20.0, 81, 41, 5.0, 0.0, 0.0);
+    let mut mesh = heightmap_to_solid_mesh(&stock, 0.0);
+    // Two short ball cuts far apart, one at the edge of the stock
+    let dirty = simulate_material_removal(SimulateMaterialRemovalParams {
+      heightmap: &mut stock,
+      tool_type: "ball",
+      cutter_diameter: 3.0,
+      v_angle_deg: 0.0,
+      toolpath: &[5.0, 5.0, 3.0, 6.0, 5.0, 3.0, 39.0, 0.0, 2.0, 39.0, 1.0, 2.0],
+      attributes: None,
+    });
+    assert_eq!(dirty.len(), 2);
+    assert_eq!((dirty[0].ix0, dirty[0].ix1), (7, 16));
+    assert_eq!((dirty[1].ix0, dirty[1].ix1, dirty[1].iy0), (75, 81, 0));
+
+    let patches = solid_mesh_patches(&stock, 0.0, &dirty, 8);
+    // Tiles of 8 columns: three around the first cut (normals change up to column 16), and
+    // 72..80 and 80..81 at the edge; three vertex blocks each
+    assert_eq!(patches.len(), 3 * 5);
+    for patch in &patches {
+      let range = patch.first_vertex * 3..patch.first_vertex * 3 + patch.positions.len();
+      mesh.positions[range.clone()].copy_from_slice(&patch.positions);
+      mesh.normals[range].copy_from_slice(&patch.normals);
+    }
+    let full = heightmap_to_solid_mesh(&stock, 0.0);
+    assert_eq!(mesh.positions, full.positions);
+    assert_eq!(mesh.normals, full.normals);
+  }
+
   #[test]
   fn mesh_large_grid_does_not_panic() {
     let width = 100.0;
@@ -48,30 +103,9 @@ mod tests {
   }
 }
 
-#[derive(Debug)]
-pub struct Stock {
-  pub width: f32,
-  pub height: f32,
-  pub grid_cells_x: usize,
-  pub grid_cells_y: usize,
-  pub grid_size_x: f32,
-  pub grid_size_y: f32,
-  pub origin_x: f32,
-  pub origin_y: f32,
-  pub heights: Vec<f32>, // row-major: ix * ny + iy
-}
-
-impl Stock {
-  pub fn get_height(&self, x: f32, y: f32) -> f32 {
-    let ix = ((x - self.origin_x) / self.grid_size_x).round() as isize;
-    let iy = ((y - self.origin_y) / self.grid_size_y).round() as isize;
-    if ix < 0 || iy < 0 || (ix as usize) >= self.grid_cells_x || (iy as usize) >= self.grid_cells_y {
-      return 0.0;
-    }
-    self.heights[ix as usize * self.grid_cells_y + iy as usize]
-  }
-}
+use crate::heightmap::{Heightmap, NodeRect};
 
+/// Stock block with a flat top at initial_height, width by height mm from the origin
 pub fn create_heightmap_stock(
   width: f32,
   height: f32,
@@ -80,21 +114,10 @@ pub fn create_heightmap_stock(
   initial_height: f32,
   origin_x: f32,
   origin_y: f32,
-) -> Stock {
+) -> Heightmap {
   let grid_size_x = width / (grid_cells_x as f32 - 1.0);
   let grid_size_y = height / (grid_cells_y as f32 - 1.0);
-  let heights = vec![initial_height; grid_cells_x * grid_cells_y];
-  Stock {
-    width,
-    height,
-    grid_cells_x,
-    grid_cells_y,
-    grid_size_x,
-    grid_size_y,
-    origin_x,
-    origin_y,
-    heights,
-  }
+  Heightmap::new(grid_cells_x, grid_cells_y, grid_size_x, grid_size_y, origin_x, origin_y, initial_height)
 }
 
 pub struct Mesh {
@@ -103,11 +126,15 @@ pub struct Mesh {
   pub normals: Vec<f32>,
 }
 
-pub fn heightmap_to_solid_mesh(stock: &Stock, min_z: f32) -> Mesh {
-  let nx = stock.grid_cells_x;
-  let ny = stock.grid_cells_y;
-  let grid_size_x = stock.grid_size_x;
-  let grid_size_y = stock.grid_size_y;
+pub fn heightmap_to_solid_mesh(stock: &Heightmap, min_z: f32) -> Mesh {
+  solid_mesh_rect(stock, min_z, NodeRect { ix0: 0, iy0: 0, ix1: stock.nx, iy1: stock.ny })
+}
+
+/// Solid mesh of the nodes in rect, as if they were the whole stock
+pub fn solid_mesh_rect(stock: &Heightmap, min_z: f32, rect: NodeRect) -> Mesh {
+  let (ix0, iy0) = (rect.ix0, rect.iy0);
+  let nx = rect.ix1 - rect.ix0;
+  let ny = rect.iy1 - rect.iy0;
   let num_top_vertices = nx * ny;
   let mut positions = Vec::with_capacity(num_top_vertices * 3 * 3); // top, bottom, duplicated top
   let mut indices = Vec::new();
@@ -115,21 +142,16 @@ pub fn heightmap_to_solid_mesh(stock: &Stock, min_z: f32) -> Mesh {
   // Generate top vertices (z from heightmap)
   for ix in 0..nx {
     for iy in 0..ny {
-      let x = stock.origin_x + ix as f32 * grid_size_x;
-      let y = stock.origin_y + iy as f32 * grid_size_y;
-      let z = stock.get_height(x, y);
-      positions.push(x);
-      positions.push(y);
-      positions.push(z);
+      positions.push(stock.x(ix0 + ix));
+      positions.push(stock.y(iy0 + iy));
+      positions.push(stock.get(ix0 + ix, iy0 + iy));
     }
   }
   // Generate bottom vertices (z = min_z)
   for ix in 0..nx {
     for iy in 0..ny {
-      let x = stock.origin_x + ix as f32 * grid_size_x;
-      let y = stock.origin_y + iy as f32 * grid_size_y;
-      positions.push(x);
-      positions.push(y);
+      positions.push(stock.x(ix0 + ix));
+      positions.push(stock.y(iy0 + iy));
       positions.push(min_z);
     }
   }
@@ -158,12 +180,9 @@ pub fn heightmap_to_solid_mesh(stock: &Stock, min_z: f32) -> Mesh {
   let side_top_offset = positions.len() / 3;
   for ix in 0..nx {
     for iy in 0..ny {
-      let x = stock.origin_x + ix as f32 * grid_size_x;
-      let y = stock.origin_y + iy as f32 * grid_size_y;
-      let z = stock.get_height(x, y);
-      positions.push(x);
-      positions.push(y);
-      positions.push(z);
+      positions.push(stock.x(ix0 + ix));
+      positions.push(stock.y(iy0 + iy));
+      positions.push(stock.get(ix0 + ix, iy0 + iy));
     }
   }
   // Side faces
@@ -199,7 +218,12 @@ pub fn heightmap_to_solid_mesh(stock: &Stock, min_z: f32) -> Mesh {
     let bot_b = idx(ix + 1, ny - 1, false);
     indices.extend_from_slice(&[top_a as u32, top_b as u32, bot_a as u32, bot_a as u32, top_b as u32, bot_b as u32]);
   }
-  // Compute normals (per-vertex, averaged from faces)
+  let normals = vertex_normals(&positions, &indices);
+  Mesh { positions, indices, normals }
+}
+
+/// Per-vertex normals, averaged from the faces around each vertex (weighted by area)
+pub fn vertex_normals(positions: &[f32], indices: &[u32]) -> Vec<f32> {
   let n_verts = positions.len() / 3;
   let mut normals = vec![0.0f32; positions.len()];
   for tri in indices.chunks(3) {
@@ -238,11 +262,73 @@ pub fn heightmap_to_solid_mesh(stock: &Stock, min_z: f32) -> Mesh {
       normals[i * 3 + 2] /= mag;
     }
   }
-  Mesh { positions, indices, normals }
+  normals
+}
+
+/// Per-vertex values of a per-node layer (e.g. NodeAttributes) for the mesh solid_mesh_rect
+/// builds: its top, bottom and side vertices each repeat the values of their nodes
+pub fn solid_mesh_vertex_values<T: Copy>(stock: &Heightmap, layer: &[T], rect: NodeRect) -> Vec<T> {
+  if layer.len() != stock.heights.len() {
+    panic!("node layer length {} does not match nx*ny {}", layer.len(), stock.heights.len());
+  }
+  let mut block = Vec::with_capacity(rect.area());
+  for ix in rect.ix0..rect.ix1 {
+    for iy in rect.iy0..rect.iy1 {
+      block.push(layer[stock.index(ix, iy)]);
+    }
+  }
+  block.repeat(3)
+}
+
+/// New vertex data for a run of vertices of a heightmap_to_solid_mesh mesh
+pub struct MeshPatch {
+  pub first_vertex: usize,
+  pub positions: Vec<f32>,
+  pub normals: Vec<f32>,
+}
+
+/// Regenerate the vertices of the column tiles (tile_columns wide) that the dirty rects touch.
+/// The mesh keeps three blocks of nx * ny vertices (top, bottom, side copies of the top), each
+/// column by column, so a tile is one contiguous run per block: three patches per tile, equal
+/// to what a full heightmap_to_solid_mesh would give. Indices never change.
+pub fn solid_mesh_patches(stock: &Heightmap, min_z: f32, dirty: &[NodeRect], tile_columns: usize) -> Vec<MeshPatch> {
+  let (nx, ny) = (stock.nx, stock.ny);
+  if nx < 2 || ny < 2 || tile_columns == 0 {
+    return Vec::new();
+  }
+  // A node's normal depends on the cells around it, so a change reaches one column further
+  let mut tiles: Vec<usize> = dirty
+    .iter()
+    .filter(|r| r.ix1 > r.ix0)
+    .flat_map(|r| r.ix0.saturating_sub(1) / tile_columns..=r.ix1.min(nx - 1) / tile_columns)
+    .collect();
+  tiles.sort_unstable();
+  tiles.dedup();
+
+  let mut patches = Vec::new();
+  for tile in tiles {
+    let c0 = tile * tile_columns;
+    let c1 = (c0 + tile_columns).min(nx);
+    // Mesh the tile with one column of margin on each side for its edge normals
+    let m0 = c0.saturating_sub(1);
+    let m1 = (c1 + 1).min(nx);
+    let mesh = solid_mesh_rect(stock, min_z, NodeRect { ix0: m0, iy0: 0, ix1: m1, iy1: ny });
+    let block = (m1 - m0) * ny;
+    for b in 0..3 {
+      let first = b * block + (c0 - m0) * ny;
+      let count = (c1 - c0) * ny;
+      patches.push(MeshPatch {
+        first_vertex: b * nx * ny + c0 * ny,
+        positions: mesh.positions[first * 3..(first + count) * 3].to_vec(),
+        normals: mesh.normals[first * 3..(first + count) * 3].to_vec(),
+      });
+    }
+  }
+  patches
 }
 
 use wasm_bindgen::prelude::*;
-use js_sys::{Float32Array, Uint32Array, Object, Array};
+use js_sys::{Float32Array, Uint32Array, Object};
 
 #[wasm_bindgen]
 pub fn heightmap_to_solid_mesh_wasm(
@@ -254,23 +340,30 @@ pub fn heightmap_to_solid_mesh_wasm(
   origin_x: f32,
   origin_y: f32,
   min_z: f32,
+  operation_id: Option<Uint32Array>,
+  tool_id: Option<Uint32Array>,
+  cut_time: Option<Float32Array>,
+  top_z: Option<f32>,
+  design: Option<Float32Array>,
 ) -> Object {
-  // Assumptions: heights is row-major, length = grid_cells_x * grid_cells_y
-  let mut heights_vec = vec![0.0; heights.length() as usize];
-  heights.copy_to(&mut heights_vec);
+  // Assumptions: heights is in Heightmap layout (ix * ny + iy), length = grid_cells_x * grid_cells_y
+  // Optional per-node layers (same layout) come back as per-vertex arrays of the same name;
+  // top_z adds depth (top_z - z) and design adds deviation (z - design, 0 where design has no surface)
   let grid_size_x = width / (grid_cells_x as f32 - 1.0);
   let grid_size_y = height / (grid_cells_y as f32 - 1.0);
-  let stock = Stock {
-    width,
-    height,
-    grid_cells_x,
-    grid_cells_y,
-    grid_size_x,
-    grid_size_y,
-    origin_x,
-    origin_y,
-    heights: heights_vec,
-  };
+  let stock = Heightmap::from_vec(grid_cells_x, grid_cells_y, grid_size_x, grid_size_y, origin_x, origin_y, heights.to_vec());
+  let expected_len = stock.heights.len();
+  let layer_lengths = [
+    ("operation_id", operation_id.as_ref().map(|a| a.length())),
+    ("tool_id", tool_id.as_ref().map(|a| a.length())),
+    ("cut_time", cut_time.as_ref().map(|a| a.length())),
+    ("design", design.as_ref().map(|a| a.length())),
+  ];
+  for (name, len) in layer_lengths {
+    if let Some(len) = len.filter(|&len| len as usize != expected_len) {
+      panic!("{} layer length {} does not match nx*ny {}", name, len, expected_len);
+    }
+  }
   let mesh = heightmap_to_solid_mesh(&stock, min_z);
   // Convert to JS arrays
   let positions = Float32Array::from(mesh.positions.as_slice());
@@ -280,5 +373,64 @@ pub fn heightmap_to_solid_mesh_wasm(
   js_sys::Reflect::set(&result, &"positions".into(), &positions).unwrap();
   js_sys::Reflect::set(&result, &"indices".into(), &indices).unwrap();
   js_sys::Reflect::set(&result, &"normals".into(), &normals).unwrap();
+  let rect = NodeRect { ix0: 0, iy0: 0, ix1: stock.nx, iy1: stock.ny };
+  if let Some(layer) = operation_id {
+    let values = solid_mesh_vertex_values(&stock, &layer.to_vec(), rect);
+    js_sys::Reflect::set(&result, &"operation_id".into(), &Uint32Array::from(values.as_slice())).unwrap();
+  }
+  if let Some(layer) = tool_id {
+    let values = solid_mesh_vertex_values(&stock, &layer.to_vec(), rect);
+    js_sys::Reflect::set(&result, &"tool_id".into(), &Uint32Array::from(values.as_slice())).unwrap();
+  }
+  if let Some(layer) = cut_time {
+    let values = solid_mesh_vertex_values(&stock, &layer.to_vec(), rect);
+    js_sys::Reflect::set(&result, &"cut_time".into(), &Float32Array::from(values.as_slice())).unwrap();
+  }
+  if let Some(top_z) = top_z {
+    let depth: Vec<f32> = stock.heights.iter().map(|z| top_z - z).collect();
+    let values = solid_mesh_vertex_values(&stock, &depth, rect);
+    js_sys::Reflect::set(&result, &"depth".into(), &Float32Array::from(values.as_slice())).unwrap();
+  }
+  if let Some(design) = design {
+    let design = stock.with_heights(design.to_vec());
+    let deviation: Vec<f32> = stock.heights.iter().zip(&design.heights).map(|(z, d)| if d.is_finite() { z - d } else { 0.0 }).collect();
+    let values = solid_mesh_vertex_values(&stock, &deviation, rect);
+    js_sys::Reflect::set(&result, &"deviation".into(), &Float32Array::from(values.as_slice())).unwrap();
+  }
+  result
+}
+
+/// Patches for the tiles touched by dirty rects (flat ix0, iy0, ix1, iy1 as returned by
+/// simulate_material_removal_wasm): Array of { first_vertex, positions, normals }, ready for
+/// BufferAttribute update ranges
+#[wasm_bindgen]
+pub fn heightmap_to_solid_mesh_patches_wasm(
+  heights: &Float32Array,
+  width: f32,
+  height: f32,
+  grid_cells_x: usize,
+  grid_cells_y: usize,
+  origin_x: f32,
+  origin_y: f32,
+  min_z: f32,
+  dirty_js: &Uint32Array,
+  tile_columns: usize,
+) -> js_sys::Array {
+  let grid_size_x = width / (grid_cells_x as f32 - 1.0);
+  let grid_size_y = height / (grid_cells_y as f32 - 1.0);
+  let stock = Heightmap::from_vec(grid_cells_x, grid_cells_y, grid_size_x, grid_size_y, origin_x, origin_y, heights.to_vec());
+  let dirty: Vec<NodeRect> = dirty_js
+    .to_vec()
+    .chunks_exact(4)
+    .map(|r| NodeRect { ix0: r[0] as usize, iy0: r[1] as usize, ix1: r[2] as usize, iy1: r[3] as usize })
+    .collect();
+  let result = js_sys::Array::new();
+  for patch in solid_mesh_patches(&stock, min_z, &dirty, tile_columns) {
+    let item = Object::new();
+    js_sys::Reflect::set(&item, &"first_vertex".into(), &(patch.first_vertex as u32).into()).unwrap();
+    js_sys::Reflect::set(&item, &"positions".into(), &Float32Array::from(patch.positions.as_slice())).unwrap();
+    js_sys::Reflect::set(&item, &"normals".into(), &Float32Array::from(patch.normals.as_slice())).unwrap();
+    result.push(&item);
+  }
   result
 }
@@ -0,0 +1,263 @@
+// Heightmap from a grayscale image (PNG), for relief carving from a bitmap
+// All units are mm unless otherwise noted
+//
+// Brightness maps to depth below the stock top: white is min_depth, black is max_depth
+// (swapped by invert). Heights are z = -depth, so the stock top is z = 0.
+//
+// Assumptions:
+// - 8 and 16 bit grayscale PNGs are read at full precision; color images are converted to luminance
+//   and alpha is ignored
+// - Each pixel is one grid cell; the image covers x in [0, width], y in [0, height] with
+//   cell centers at half a cell from the edges
+// - Image row 0 is the top of the picture, so it becomes the largest y
+// - The result is a plain Heightmap, so it feeds simulate_material_removal and the toolpath
+//   generators directly
+
+use crate::heightmap::Heightmap;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ImageHeightmapParams {
+  pub width: f32,       // physical width of the image
+  pub height: f32,      // physical height, <= 0 keeps the image aspect ratio (square cells)
+  pub min_depth: f32,   // depth of white pixels
+  pub max_depth: f32,   // depth of black pixels
+  pub invert: bool,     // black is shallow, white is deep
+  pub gamma: f32,       // brightness curve, applied as v^gamma before mapping to depth
+  pub blur_radius: f32, // gaussian blur sigma in mm, 0 disables
+}
+
+/// Decode a PNG into normalized (0..1) luminance, row major from the top row. Returns (values, width, height)
+pub fn decode_png_gray(bytes: &[u8]) -> Result<(Vec<f32>, usize, usize), String> {
+  let mut decoder = png::Decoder::new(bytes);
+  // Expand palettes and sub-byte grays, keep 16 bit samples
+  decoder.set_transformations(png::Transformations::EXPAND);
+  let mut reader = decoder.read_info().map_err(|e| format!("invalid PNG: {}", e))?;
+  let mut buf = vec![0; reader.output_buffer_size()];
+  let info = reader.next_frame(&mut buf).map_err(|e| format!("invalid PNG: {}", e))?;
+  let (w, h) = (info.width as usize, info.height as usize);
+  let channels = info.color_type.samples();
+  let sixteen = info.bit_depth == png::BitDepth::Sixteen;
+  let max = if sixteen { 65535.0 } else { 255.0 };
+  let sample = |row: &[u8], i: usize| -> f32 {
+    if sixteen {
+      u16::from_be_bytes([row[2 * i], row[2 * i + 1]]) as f32 / max
+    } else {
+      row[i] as f32 / max
+    }
+  };
+  let mut values = Vec::with_capacity(w * h);
+  for row in buf[..info.buffer_size()].chunks_exact(info.line_size).take(h) {
+    for x in 0..w {
+      let i = x * channels;
+      let v = if channels >= 3 {
+        // Rec. 709 luma
+        0.2126 * sample(row, i) + 0.7152 * sample(row, i + 1) + 0.0722 * sample(row, i + 2)
+      } else {
+        sample(row, i)
+      };
+      values.push(v);
+    }
+  }
+  Ok((values, w, h))
+}
+
+// Separable gaussian blur of a row-major image, sigma in cells per axis; edges are clamped
+fn gaussian_blur(values: &[f32], w: usize, h: usize, sigma_x: f32, sigma_y: f32) -> Vec<f32> {
+  fn kernel(sigma: f32) -> Vec<f32> {
+    let radius = (sigma * 3.0).ceil() as isize;
+    let k: Vec<f32> = (-radius..=radius).map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp()).collect();
+    let sum: f32 = k.iter().sum();
+    k.iter().map(|v| v / sum).collect()
+  }
+  let blur_axis = |src: &[f32], sigma: f32, horizontal: bool| -> Vec<f32> {
+    if sigma <= 0.0 {
+      return src.to_vec();
+    }
+    let k = kernel(sigma);
+    let r = (k.len() / 2) as isize;
+    let mut out = vec![0.0; src.len()];
+    for y in 0..h {
+      for x in 0..w {
+        let mut acc = 0.0;
+        for (j, kv) in k.iter().enumerate() {
+          let o = j as isize - r;
+          let (sx, sy) = if horizontal {
+            ((x as isize + o).clamp(0, w as isize - 1) as usize, y)
+          } else {
+            (x, (y as isize + o).clamp(0, h as isize - 1) as usize)
+          };
+          acc += kv * src[sy * w + sx];
+        }
+        out[y * w + x] = acc;
+      }
+    }
+    out
+  };
+  let tmp = blur_axis(values, sigma_x, true);
+  blur_axis(&tmp, sigma_y, false)
+}
+
+/// Convert normalized luminance (row major from the top row) to a heightmap; errors on an empty
+/// image or a width that isn't a positive size
+pub fn heightmap_from_gray(values: &[f32], w: usize, h: usize, params: &ImageHeightmapParams) -> Result<Heightmap, String> {
+  if w == 0 || h == 0 || values.len() < w * h {
+    return Err("empty image".to_string());
+  }
+  if !(params.width > 0.0 && params.width.is_finite()) {
+    return Err(format!("image width must be positive, got {}", params.width));
+  }
+  if !params.height.is_finite() {
+    return Err(format!("image height must be finite, got {}", params.height));
+  }
+  let cell_x = params.width / w as f32;
+  let cell_y = if params.height > 0.0 { params.height / h as f32 } else { cell_x };
+  let values = if params.blur_radius > 0.0 {
+    gaussian_blur(values, w, h, params.blur_radius / cell_x, params.blur_radius / cell_y)
+  } else {
+    values.to_vec()
+  };
+  let gamma = if params.gamma > 0.0 { params.gamma } else { 1.0 };
+  let mut map = Heightmap::new(w, h, cell_x, cell_y, cell_x / 2.0, cell_y / 2.0, 0.0);
+  for row in 0..h {
+    let iy = h - 1 - row;
+    for ix in 0..w {
+      let mut v = values[row * w + ix].clamp(0.0, 1.0).powf(gamma);
+      if params.invert {
+        v = 1.0 - v;
+      }
+      let depth = params.min_depth + (1.0 - v) * (params.max_depth - params.min_depth);
+      map.set(ix, iy, -depth);
+    }
+  }
+  Ok(map)
+}
+
+/// Decode a grayscale PNG and map it to a heightmap
+pub fn heightmap_from_png(bytes: &[u8], params: &ImageHeightmapParams) -> Result<Heightmap, String> {
+  let (values, w, h) = decode_png_gray(bytes)?;
+  heightmap_from_gray(&values, w, h, params)
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Float32Array, Object};
+
+/// Returns { heights: Float32Array (ix * ny + iy), nx, ny, grid_size_x, grid_size_y, origin_x, origin_y }
+#[wasm_bindgen]
+pub fn heightmap_from_png_wasm(
+  png_bytes: &[u8],
+  width: f32,
+  height: f32,
+  min_depth: f32,
+  max_depth: f32,
+  invert: bool,
+  gamma: f32,
+  blur_radius: f32,
+) -> Result<Object, JsValue> {
+  let params = ImageHeightmapParams { width, height, min_depth, max_depth, invert, gamma, blur_radius };
+  let map = heightmap_from_png(png_bytes, &params).map_err(|e| JsValue::from_str(&e))?;
+  let result = Object::new();
+  js_sys::Reflect::set(&result, &"heights".into(), &Float32Array::from(map.heights.as_slice())).unwrap();
+  js_sys::Reflect::set(&result, &"nx".into(), &(map.nx as u32).into()).unwrap();
+  js_sys::Reflect::set(&result, &"ny".into(), &(map.ny as u32).into()).unwrap();
+  js_sys::Reflect::set(&result, &"grid_size_x".into(), &map.grid_size_x.into()).unwrap();
+  js_sys::Reflect::set(&result, &"grid_size_y".into(), &map.grid_size_y.into()).unwrap();
+  js_sys::Reflect::set(&result, &"origin_x".into(), &map.origin_x.into()).unwrap();
+  js_sys::Reflect::set(&result, &"origin_y".into(), &map.origin_y.into()).unwrap();
+  Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::simulate_material_removal::{generate_safe_toolpath, simulate_material_removal, SimulateMaterialRemovalParams};
+
+  fn encode_gray(w: u32, h: u32, depth: png::BitDepth, data: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    {
+      let mut encoder = png::Encoder::new(&mut bytes, w, h);
+      encoder.set_color(png::ColorType::Grayscale);
+      encoder.set_depth(depth);
+      let mut writer = encoder.write_header().unwrap();
+      writer.write_image_data(data).unwrap();
+    }
+    bytes
+  }
+
+  fn params() -> ImageHeightmapParams {
+    ImageHeightmapParams { width: 40.0, height: 0.0, min_depth: 0.0, max_depth: 5.0, invert: false, gamma: 1.0, blur_radius: 0.0 }
+  }
+
+  #[test]
+  fn eight_bit_orientation_and_depths() {
+    // 4 x 2 image: top row white, bottom row black except one gray pixel
+    let data = [255, 255, 255, 255, 0, 0, 0, 51];
+    let map = heightmap_from_png(&encode_gray(4, 2, png::BitDepth::Eight, &data), &params()).unwrap();
+    assert_eq!((map.nx, map.ny), (4, 2));
+    assert_eq!((map.grid_size_x, map.grid_size_y), (10.0, 10.0));
+    // Top image row is the larger y
+    assert_eq!(map.get(0, 1), 0.0);
+    assert_eq!(map.get(0, 0), -5.0);
+    assert!((map.get(3, 0) + 4.0).abs() < 1e-5);
+
+    let inverted = heightmap_from_png(&encode_gray(4, 2, png::BitDepth::Eight, &data), &ImageHeightmapParams { invert: true, ..params() }).unwrap();
+    assert_eq!(inverted.get(0, 1), -5.0);
+    let gamma = heightmap_from_png(&encode_gray(4, 2, png::BitDepth::Eight, &data), &ImageHeightmapParams { gamma: 0.5, ..params() }).unwrap();
+    // 0.2^0.5 brightness
+    assert!((gamma.get(3, 0) + 5.0 * (1.0 - 0.2f32.sqrt())).abs() < 1e-4);
+  }
+
+  #[test]
+  fn sixteen_bit_keeps_precision() {
+    // Two pixels one 16 bit step apart, indistinguishable at 8 bits
+    let data = [0x80, 0x00, 0x80, 0x01];
+    let map = heightmap_from_png(&encode_gray(2, 1, png::BitDepth::Sixteen, &data), &ImageHeightmapParams { max_depth: 65535.0, ..params() }).unwrap();
+    assert!((map.get(1, 0) - map.get(0, 0) - 1.0).abs() < 1e-2);
+  }
+
+  #[test]
+  fn blur_and_simulation() {
+    // White square on black, 20 x 20 pixels over 20 mm
+    let mut data = vec![0u8; 400];
+    for y in 5..15 {
+      for x in 5..15 {
+        data[y * 20 + x] = 255;
+      }
+    }
+    let p = ImageHeightmapParams { width: 20.0, blur_radius: 1.0, ..params() };
+    let map = heightmap_from_png(&encode_gray(20, 20, png::BitDepth::Eight, &data), &p).unwrap();
+    // Blur softens the step at the square edge but not its middle
+    assert!(map.get(10, 10) > -0.01);
+    let edge = map.get(5, 10);
+    assert!(edge < -0.5 && edge > -4.5, "edge {}", edge);
+
+    // Safe toolpath follows the relief; cutting it from flat stock never goes below the target
+    let xy: Vec<(f32, f32)> = (0..20).map(|i| (map.origin_x + i as f32, 10.5)).collect();
+    let zs = generate_safe_toolpath(&map, "ball", 2.0, 0.0, &xy);
+    let path: Vec<f32> = xy.iter().zip(zs.iter()).flat_map(|(&(x, y), &z)| [x, y, z]).collect();
+    let mut stock = map.with_heights(vec![0.0; map.heights.len()]);
+    simulate_material_removal(SimulateMaterialRemovalParams {
+      heightmap: &mut stock,
+      tool_type: "ball",
+      cutter_diameter: 2.0,
+      v_angle_deg: 0.0,
+      toolpath: &path,
+      attributes: None,
+    });
+    for (s, t) in stock.heights.iter().zip(map.heights.iter()) {
+      assert!(*s >= *t - 1e-4);
+    }
+    assert!(stock.heights.iter().any(|&s| s < -1.0));
+  }
+
+  #[test]
+  fn bad_sizes_are_errors() {
+    let values = vec![0.5; 6];
+    for width in [0.0, -10.0, f32::NAN, f32::INFINITY] {
+      let p = ImageHeightmapParams { width, blur_radius: 1.0, ..params() };
+      assert!(heightmap_from_gray(&values, 3, 2, &p).is_err(), "width {}", width);
+    }
+    assert!(heightmap_from_gray(&values, 0, 2, &params()).is_err());
+    assert!(heightmap_from_gray(&values, 4, 2, &params()).is_err());
+    assert!(heightmap_from_gray(&values, 3, 2, &params()).is_ok());
+  }
+}
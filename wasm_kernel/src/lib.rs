@@ -1,16 +1,70 @@
+// wasm bindings take flat argument lists so they can be called directly from JS
+#![allow(clippy::too_many_arguments)]
+
 use wasm_bindgen::prelude::*;
 
+mod heightmap;
 mod simulate_material_removal;
 mod minimal;
 mod heightmap_to_solid_mesh;
 mod heightmap_from_mesh;
+mod geometry_2d;
+mod toolpath;
+mod contour_toolpath;
+mod cutout_toolpath;
+mod silhouette;
+mod svg_import;
+mod dxf_import;
+mod image_heightmap;
+mod vcarve_toolpath;
+mod text_engrave;
+mod drill_toolpath;
+mod polar_finish;
+mod scallop_finish;
+mod pencil_toolpath;
+mod rest_machining;
+mod flatten_toolpath;
+mod cut_direction;
+mod stock_model;
+mod dexel_stock;
+mod rotary_stock;
+mod wrapped_raster;
+mod tiled_mesh;
+mod simplified_mesh;
+mod stock_lod;
 
-pub use simulate_material_removal::simulate_material_removal;
+pub use simulate_material_removal::{simulate_material_removal, CutStamp, SimulateMaterialRemovalParams};
 pub use simulate_material_removal::simulate_material_removal_wasm;
+pub use simulate_material_removal::generate_safe_toolpath;
 pub use minimal::double_array;
-pub use heightmap_to_solid_mesh::heightmap_to_solid_mesh_wasm;
+pub use svg_import::{import_svg, import_svg_wasm, VectorGeometry};
+pub use dxf_import::{import_dxf, import_dxf_wasm, DxfImport};
+pub use image_heightmap::{heightmap_from_png, heightmap_from_png_wasm, heightmap_from_gray, ImageHeightmapParams};
+pub use silhouette::{mesh_silhouette, heightmap_outline, mesh_silhouette_wasm, heightmap_outline_wasm};
+pub use geometry_2d::{Point2, Polyline2, Region, FillRule, fill_regions, offset_loops, regions_to_loops};
+pub use cutout_toolpath::{generate_cutout_toolpath, generate_cutout_toolpath_wasm, CutoutParams, TabParams, TabShape};
+pub use contour_toolpath::{generate_contour_toolpath, generate_contour_toolpath_wasm, ContourToolpathParams, LeadParams, LeadSide};
+pub use vcarve_toolpath::{generate_vcarve_toolpath, generate_vcarve_toolpath_wasm, VCarveParams, VCarveToolpaths};
+pub use text_engrave::{layout_text, layout_text_wasm, engrave_passes, TextAlign, TextParams};
+pub use drill_toolpath::{detect_holes, detect_holes_wasm, drill_gcode, drill_passes, generate_drill_wasm, order_holes, simulate_drilling, simulate_drilling_wasm, DrillCycle, DrillParams, Hole};
+pub use polar_finish::{generate_polar_finish, generate_polar_finish_wasm, PolarFinishParams, PolarPattern};
+pub use scallop_finish::{generate_scallop_finish, generate_scallop_finish_wasm, ScallopFinish, ScallopFinishParams};
+pub use pencil_toolpath::{generate_pencil_toolpath, generate_pencil_toolpath_wasm, valley_mask, PencilParams};
+pub use rest_machining::{generate_rest_toolpath, generate_rest_toolpath_wasm, RestMachiningParams};
+pub use flatten_toolpath::{generate_flatten_toolpath, generate_flatten_toolpath_wasm, FlattenArea, FlattenParams};
+pub use cut_direction::{classify_cuts, classify_cuts_wasm, cut_direction, set_cut_direction, set_cut_direction_wasm, CutDirection, CutKind, CutMix};
+pub use stock_model::{StockModel, ToolShape};
+pub use dexel_stock::{DexelStock, DexelStockHandle};
+pub use rotary_stock::{rotary_stock_to_mesh_wasm, simulate_rotary_removal_wasm, RotaryStock};
+pub use wrapped_raster::{generate_wrapped_raster, generate_wrapped_raster_wasm, radial_heightmap_from_mesh, radial_heightmap_from_mesh_wasm, rotary_safe_z, WrapPattern, WrappedRasterParams};
+pub use heightmap_to_solid_mesh::{heightmap_to_solid_mesh_wasm, heightmap_to_solid_mesh_patches_wasm};
+pub use heightmap::{DirtyRects, Heightmap, NodeAttributes, NodeRect};
+pub use heightmap_to_solid_mesh::{Mesh, MeshPatch, create_heightmap_stock, heightmap_to_solid_mesh, solid_mesh_patches, solid_mesh_rect, solid_mesh_vertex_values, vertex_normals};
+pub use simplified_mesh::{heightmap_to_simplified_mesh, heightmap_to_simplified_mesh_wasm};
+pub use stock_lod::{heightmap_lod_mesh, heightmap_lod_mesh_wasm, heightmap_mip_levels, heightmap_mip_levels_wasm, mip_level, region_rect, update_mip_levels, update_mip_levels_wasm};
+pub use tiled_mesh::{heightmap_tile_mesh, heightmap_to_tiled_mesh, heightmap_to_tiled_mesh_wasm, tile_counts, tile_rect, tiles_for_rects, MeshTile};
 
-use heightmap_from_mesh::{MeshRust, HeightmapGridRust, heightmap_from_mesh_rust};
+pub use heightmap_from_mesh::{MeshRust, HeightmapGridRust, heightmap_from_mesh_rust};
 use js_sys::{Float32Array, Uint32Array};
 
 #[wasm_bindgen]
@@ -34,5 +88,5 @@ pub fn heightmap_from_mesh_wasm(
     min_x, max_x, min_y, max_y, res_x, res_y
   };
   let heightmap = heightmap_from_mesh_rust(&mesh, &grid);
-  Float32Array::from(heightmap.as_slice())
+  Float32Array::from(heightmap.heights.as_slice())
 }
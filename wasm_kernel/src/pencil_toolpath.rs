@@ -0,0 +1,235 @@
+// Pencil (valley) cleanup after ball-nose finishing
+// All units are mm unless otherwise noted
+//
+// A finishing ball can't reach into concave corners tighter than its radius, so material is
+// left along valleys and where walls meet floors. The pencil tool runs where it touches both
+// sides of such a corner at once (the bitangent line): there its tool offset surface (the
+// safe tool tip height at every node) has a crease, bending upward more sharply than at the
+// nodes beside it. Crease nodes are kept only where the simulated stock (any StockModel) is
+// still more than `threshold` above the part, thinned to single lines and traced into passes. z along a pass
+// is the offset surface, so the tool never gouges.
+//
+// Assumptions:
+// - The stock is sampled at the part's nodes; part nodes off the stock count as cut
+// - A crease bends the offset surface by more than MIN_TURN (change of slope) across a node
+//   along x, y or a diagonal; walls need not be symmetric, so a floor meeting a wall counts
+// - The pencil tool is smaller than the finishing tool (otherwise there is nothing it can reach)
+// - Passes shorter than min_length are dropped as noise
+// - Passes are simplified to `tolerance` and ordered nearest-first, reversing as needed
+
+use crate::geometry_2d::{thin_mask, trace_skeleton};
+use crate::heightmap::Heightmap;
+use crate::simulate_material_removal::generate_safe_toolpath;
+use crate::stock_model::StockModel;
+use crate::toolpath::{order_open_passes, simplify_path, Pass};
+
+pub struct PencilParams<'a> {
+  pub part: &'a Heightmap,  // target surface
+  pub stock: &'a dyn StockModel, // simulated stock after finishing
+  pub tool_type: &'a str,
+  pub cutter_diameter: f32,
+  pub v_angle_deg: f32,
+  pub threshold: f32,  // stock left above the part that counts as uncut
+  pub min_length: f32, // shortest pass kept
+  pub tolerance: f32,  // simplification tolerance, <= 0 to keep every node
+}
+
+// Change of slope across a node that counts as a crease in the tool offset surface
+const MIN_TURN: f32 = 0.1;
+
+/// Nodes (in heights order) on a crease of the tool offset surface: where it bends upward by
+/// more than MIN_TURN along some direction, and by at least as much as at both neighbors
+pub fn valley_mask(offset: &Heightmap) -> Vec<bool> {
+  let (nx, ny) = (offset.nx as isize, offset.ny as isize);
+  let height = |ix: isize, iy: isize| {
+    if ix >= 0 && iy >= 0 && ix < nx && iy < ny { Some(offset.get(ix as usize, iy as usize)) } else { None }
+  };
+  let mut mask = vec![false; offset.heights.len()];
+  for (dx, dy) in [(1, 0), (0, 1), (1, 1), (1, -1)] {
+    let run = (dx as f32 * offset.grid_size_x).hypot(dy as f32 * offset.grid_size_y);
+    // Change of slope across (ix, iy) along this direction
+    let bend = |ix: isize, iy: isize| match (height(ix - dx, iy - dy), height(ix, iy), height(ix + dx, iy + dy)) {
+      (Some(a), Some(b), Some(c)) => (a - 2.0 * b + c) / run,
+      _ => f32::NEG_INFINITY,
+    };
+    for ix in 0..nx {
+      for iy in 0..ny {
+        let b = bend(ix, iy);
+        if b > MIN_TURN && b >= bend(ix - dx, iy - dy) && b >= bend(ix + dx, iy + dy) {
+          mask[offset.index(ix as usize, iy as usize)] = true;
+        }
+      }
+    }
+  }
+  mask
+}
+
+/// Generate pencil passes along the creases of the tool offset surface inside the uncut regions
+pub fn generate_pencil_toolpath(params: &PencilParams) -> Vec<Pass> {
+  let part = params.part;
+  let (nx, ny) = (part.nx, part.ny);
+  if nx == 0 || ny == 0 {
+    return Vec::new();
+  }
+  let xy: Vec<(f32, f32)> = (0..nx * ny)
+    .map(|k| {
+      let (ix, iy) = part.node(k);
+      (part.x(ix), part.y(iy))
+    })
+    .collect();
+  let offset = part.with_heights(generate_safe_toolpath(
+    params.part,
+    params.tool_type,
+    params.cutter_diameter,
+    params.v_angle_deg,
+    &xy,
+  ));
+  let residual = params.stock.heights_on(part).above(part, params.threshold);
+  let mut mask: Vec<bool> = valley_mask(&offset).iter().zip(&residual).map(|(&v, &r)| v && r).collect();
+  thin_mask(&mut mask, nx, ny);
+
+  let mut passes = Vec::new();
+  for nodes in trace_skeleton(&mask, nx, ny) {
+    let pass: Pass = nodes.iter().map(|&k| [xy[k].0, xy[k].1, offset.heights[k]]).collect();
+    let length: f32 = pass.windows(2).map(|w| (w[1][0] - w[0][0]).hypot(w[1][1] - w[0][1])).sum();
+    if pass.len() < 2 || length < params.min_length {
+      continue;
+    }
+    passes.push(if params.tolerance > 0.0 { simplify_path(&pass, params.tolerance) } else { pass });
+  }
+  order_open_passes(passes)
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Float32Array, Object};
+use crate::toolpath::{passes_to_js, surface_max};
+
+#[wasm_bindgen]
+pub fn generate_pencil_toolpath_wasm(
+  part_js: &Float32Array,
+  stock_js: &Float32Array,
+  nx: usize,
+  ny: usize,
+  grid_size_x: f32,
+  grid_size_y: f32,
+  origin_x: f32,
+  origin_y: f32,
+  tool_type: &str,
+  cutter_diameter: f32,
+  v_angle_deg: f32,
+  threshold: f32,
+  min_length: f32,
+  tolerance: f32,
+  clearance_z: f32,
+) -> Object {
+  let part = Heightmap::from_vec(nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, part_js.to_vec());
+  let stock = Heightmap::from_vec(nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, stock_js.to_vec());
+  let passes = generate_pencil_toolpath(&PencilParams {
+    part: &part,
+    stock: &stock,
+    tool_type,
+    cutter_diameter,
+    v_angle_deg,
+    threshold,
+    min_length,
+    tolerance,
+  });
+  passes_to_js(&passes, clearance_z.max(surface_max(&stock.heights)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::simulate_material_removal::{simulate_material_removal, SimulateMaterialRemovalParams};
+
+  const N: usize = 61;
+  const CELL: f32 = 0.5;
+
+  fn grid(heights: Vec<f32>) -> Heightmap {
+    Heightmap::from_vec(N, N, CELL, CELL, 0.0, 0.0, heights)
+  }
+
+  // Simulate a ball raster along y over the stock, riding on the part
+  fn finish(part: &Heightmap, stock: &mut Heightmap, diameter: f32) {
+    let xy: Vec<(f32, f32)> = (0..N).flat_map(|ix| (0..N).map(move |iy| (ix as f32 * CELL, iy as f32 * CELL))).collect();
+    let zs = generate_safe_toolpath(part, "ball", diameter, 0.0, &xy);
+    let toolpath: Vec<f32> = xy.iter().zip(zs).flat_map(|(&(x, y), z)| [x, y, z]).collect();
+    cut(stock, &toolpath, diameter);
+  }
+
+  fn cut(stock: &mut Heightmap, toolpath: &[f32], diameter: f32) {
+    simulate_material_removal(SimulateMaterialRemovalParams {
+      heightmap: stock,
+      tool_type: "ball",
+      cutter_diameter: diameter,
+      v_angle_deg: 0.0,
+      toolpath,
+      attributes: None,
+    });
+  }
+
+  fn params<'a>(part: &'a Heightmap, stock: &'a Heightmap) -> PencilParams<'a> {
+    PencilParams {
+      part,
+      stock,
+      tool_type: "ball",
+      cutter_diameter: 1.0,
+      v_angle_deg: 0.0,
+      threshold: 0.05,
+      min_length: 2.0,
+      tolerance: 0.01,
+    }
+  }
+
+  #[test]
+  fn pencil_follows_valley_left_by_large_ball() {
+    // V groove along y at x = 15 with 45 degree walls
+    let part = grid((0..N * N).map(|k| ((k / N) as f32 * CELL - 15.0).abs() - 5.0).collect());
+    let mut stock = grid(vec![10.0; N * N]);
+    finish(&part, &mut stock, 6.0);
+    let before = stock.get(30, 30) - part.get(30, 30);
+    assert!(before > 0.5, "left {}", before);
+
+    // Keep every node so the simulation below sees the whole pass
+    let passes = generate_pencil_toolpath(&PencilParams { tolerance: 0.0, ..params(&part, &stock) });
+    assert_eq!(passes.len(), 1);
+    let pass = &passes[0];
+    assert!(pass.iter().all(|p| (p[0] - 15.0).abs() < 0.01));
+    let (y0, y1) = (pass[0][1].min(pass[pass.len() - 1][1]), pass[0][1].max(pass[pass.len() - 1][1]));
+    assert!(y0 < 4.0 && y1 > 26.0, "pass from y = {} to {}", y0, y1);
+
+    // The small ball cleans up most of what the large one left
+    let toolpath: Vec<f32> = pass.iter().flatten().cloned().collect();
+    cut(&mut stock, &toolpath, 1.0);
+    let after = stock.get(30, 30) - part.get(30, 30);
+    assert!(after < before / 4.0, "left {} after pencil, {} before", after, before);
+    assert!(stock.heights.iter().zip(&part.heights).all(|(s, p)| s >= &(p - 1e-4)));
+  }
+
+  #[test]
+  fn pencil_follows_floor_wall_corner() {
+    // Flat floor at -5 meeting a 45 degree wall along y at x = 20
+    let part = grid((0..N * N).map(|k| ((k / N) as f32 * CELL - 20.0).max(0.0) - 5.0).collect());
+    let mut stock = grid(vec![10.0; N * N]);
+    finish(&part, &mut stock, 6.0);
+    // Uncut material spans several nodes across the corner, on the floor and on the wall
+    let residual = stock.above(&part, 0.05);
+    assert!((39..=41).all(|ix| residual[part.index(ix, 30)]));
+
+    // A 1 mm ball touches floor and wall at x = 20 - 0.5 * (sqrt(2) - 1)
+    let passes = generate_pencil_toolpath(&PencilParams { tolerance: 0.0, ..params(&part, &stock) });
+    assert_eq!(passes.len(), 1);
+    let corner = 20.0 - 0.5 * (2.0f32.sqrt() - 1.0);
+    assert!(passes[0].iter().all(|p| (p[0] - corner).abs() <= CELL / 2.0), "pass at x = {}", passes[0][0][0]);
+    assert!(passes[0].len() > N / 2);
+  }
+
+  #[test]
+  fn no_passes_when_stock_matches_part() {
+    let part = grid((0..N * N).map(|k| (k % N) as f32 * 0.1).collect());
+    let mut stock = part.clone();
+    stock.set(10, 10, part.get(10, 10) + 1.0); // single uncut node, shorter than min_length
+    assert!(generate_pencil_toolpath(&params(&part, &stock)).is_empty());
+    assert_eq!(stock.above(&part, 0.05).iter().filter(|&&m| m).count(), 1);
+  }
+}
@@ -0,0 +1,291 @@
+// Spiral and radial finishing passes over a heightmap
+// All units are mm unless otherwise noted
+//
+// Spiral: an Archimedean spiral from the center outward, one stepover per turn, counter-clockwise
+// (climb) unless the global cut direction is conventional.
+// Radial: spokes from the center, cut in rings. Each ring spaces its spokes evenly, with as
+// many spokes as keep the gap at its outer edge at one stepover, and starts where the gap
+// would drop below RADIAL_BAND stepovers, so the gap stays between RADIAL_BAND and one
+// stepover everywhere outside the 8 base spokes (which meet at the center).
+// Both are sampled every sample_step along the path; z at each sample comes from
+// generate_safe_toolpath, so the tool rides on the surface without gouging.
+//
+// Assumptions:
+// - Node (0, 0) of the heightmap is centered at its origin
+// - Samples outside the heightmap or outside the boundary are dropped and split the pass
+// - boundary loops are even-odd filled; an empty boundary keeps everything on the heightmap
+// - Radial spokes are cut counter-clockwise around the center, so uncut material is on the
+//   side of increasing angle: climb spokes run inward, conventional outward; with either they
+//   alternate so consecutive spokes start near each other
+// - A spoke ends at its ring's outer edge and the next ring's spokes start there
+
+use crate::cut_direction::{cut_direction, raster_row_reversed, CutDirection};
+use crate::geometry_2d::{point_in_loops, Point2};
+use crate::heightmap::Heightmap;
+use crate::simulate_material_removal::generate_safe_toolpath;
+use crate::toolpath::Pass;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PolarPattern {
+  Spiral,
+  Radial,
+}
+
+// Smallest radial gap, as a fraction of the stepover
+const RADIAL_BAND: f32 = 0.8;
+
+pub struct PolarFinishParams<'a> {
+  pub heightmap: &'a Heightmap,
+  pub tool_type: &'a str,
+  pub cutter_diameter: f32,
+  pub v_angle_deg: f32,
+  pub pattern: PolarPattern,
+  pub center: Point2,
+  pub radius: f32,       // outer radius, <= 0 to reach the farthest heightmap corner
+  pub stepover: f32,
+  pub sample_step: f32,  // distance between samples along the path, <= 0 for the grid size
+  pub boundary: &'a [Vec<Point2>],
+}
+
+// Sample (x, y) paths, clip them, and set z from the safe toolpath
+fn clip_and_drop(paths: Vec<Vec<Point2>>, params: &PolarFinishParams) -> Vec<Pass> {
+  let map = params.heightmap;
+  let half_x = map.grid_size_x / 2.0;
+  let half_y = map.grid_size_y / 2.0;
+  let max_x = map.origin_x + map.width() + half_x;
+  let max_y = map.origin_y + map.depth() + half_y;
+  let keep = |p: Point2| {
+    p[0] >= map.origin_x - half_x
+      && p[0] <= max_x
+      && p[1] >= map.origin_y - half_y
+      && p[1] <= max_y
+      && (params.boundary.is_empty() || point_in_loops(p, params.boundary))
+  };
+  let mut passes = Vec::new();
+  for path in paths {
+    let xy: Vec<(f32, f32)> = path.iter().map(|p| (p[0], p[1])).collect();
+    let zs = generate_safe_toolpath(
+      params.heightmap,
+      params.tool_type,
+      params.cutter_diameter,
+      params.v_angle_deg,
+      &xy,
+    );
+    let mut pass = Pass::new();
+    for (p, z) in path.iter().zip(zs) {
+      if keep(*p) && z.is_finite() {
+        pass.push([p[0], p[1], z]);
+      } else if !pass.is_empty() {
+        passes.push(std::mem::take(&mut pass));
+      }
+    }
+    if !pass.is_empty() {
+      passes.push(pass);
+    }
+  }
+  passes.retain(|p| p.len() > 1);
+  passes
+}
+
+/// Generate spiral or radial finishing passes
+pub fn generate_polar_finish(params: &PolarFinishParams) -> Vec<Pass> {
+  let map = params.heightmap;
+  if params.stepover <= 0.0 || map.nx == 0 || map.ny == 0 {
+    return Vec::new();
+  }
+  let step = if params.sample_step > 0.0 { params.sample_step } else { map.grid_size_x.min(map.grid_size_y) };
+  let c = params.center;
+  let radius = if params.radius > 0.0 {
+    params.radius
+  } else {
+    // Farthest heightmap corner
+    let xs = [map.origin_x, map.origin_x + map.width()];
+    let ys = [map.origin_y, map.origin_y + map.depth()];
+    xs.iter().flat_map(|&x| ys.iter().map(move |&y| (x - c[0]).hypot(y - c[1]))).fold(0.0, f32::max)
+  };
+  let tau = std::f32::consts::TAU;
+
+  let paths = match params.pattern {
+    PolarPattern::Spiral => {
+      // r = b * theta, one stepover per turn; step theta by arc length
+      let b = params.stepover / tau;
+      // Uncut material is outside: counter-clockwise keeps it on the right (climb)
+      let turn = if cut_direction() == CutDirection::Conventional { -1.0 } else { 1.0 };
+      let mut path = vec![c];
+      let mut theta: f32 = 0.0;
+      loop {
+        let r = b * theta;
+        theta += step / (r * r + b * b).sqrt();
+        let r = (b * theta).min(radius);
+        path.push([c[0] + r * theta.cos(), c[1] + turn * r * theta.sin()]);
+        if r >= radius {
+          break;
+        }
+      }
+      vec![path]
+    }
+    PolarPattern::Radial => {
+      // count spokes reach one stepover apart at radius stepover * count / tau. The next ring
+      // has count / RADIAL_BAND spokes (rounded down, so its inner gap stays in the band)
+      let mut count = 8usize;
+      let mut start = 0.0;
+      let mut paths = Vec::new();
+      while start < radius {
+        let end = (params.stepover * count as f32 / tau).min(radius);
+        let n = ((end - start) / step).ceil().max(1.0) as usize;
+        for i in 0..count {
+          let angle = tau * i as f32 / count as f32;
+          let dir = [angle.cos(), angle.sin()];
+          let mut spoke: Vec<Point2> = (0..=n)
+            .map(|k| {
+              let r = start + (end - start) * k as f32 / n as f32;
+              [c[0] + dir[0] * r, c[1] + dir[1] * r]
+            })
+            .collect();
+          // Material ahead is counter-clockwise: inward keeps it on the right (climb)
+          if raster_row_reversed().unwrap_or(i % 2 == 1) {
+            spoke.reverse();
+          }
+          paths.push(spoke);
+        }
+        start = end;
+        count = (count as f32 / RADIAL_BAND).floor() as usize;
+      }
+      paths
+    }
+  };
+  clip_and_drop(paths, params)
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Float32Array, Object, Uint32Array};
+use crate::toolpath::{passes_to_js, split_polylines};
+
+#[wasm_bindgen]
+pub fn generate_polar_finish_wasm(
+  heightmap_js: &Float32Array,
+  nx: usize,
+  ny: usize,
+  grid_size_x: f32,
+  grid_size_y: f32,
+  origin_x: f32,
+  origin_y: f32,
+  tool_type: &str,
+  cutter_diameter: f32,
+  v_angle_deg: f32,
+  pattern: &str, // "spiral" or "radial"
+  center_x: f32,
+  center_y: f32,
+  radius: f32,
+  stepover: f32,
+  sample_step: f32,
+  boundary_points_js: &Float32Array, // flat x, y of boundary loops, empty for none
+  boundary_counts_js: &Uint32Array,
+  clearance_z: f32,
+) -> Object {
+  let heightmap = Heightmap::from_vec(nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, heightmap_js.to_vec());
+  let boundary = split_polylines::<2>(&boundary_points_js.to_vec(), &boundary_counts_js.to_vec());
+  let passes = generate_polar_finish(&PolarFinishParams {
+    heightmap: &heightmap,
+    tool_type,
+    cutter_diameter,
+    v_angle_deg,
+    pattern: if pattern == "radial" { PolarPattern::Radial } else { PolarPattern::Spiral },
+    center: [center_x, center_y],
+    radius,
+    stepover,
+    sample_step,
+    boundary: &boundary,
+  });
+  let top = crate::toolpath::surface_max(&heightmap.heights);
+  passes_to_js(&passes, clearance_z.max(top))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cut_direction::set_cut_direction;
+
+  // 41 x 41 grid of 1 mm cells over [0, 40]^2
+  fn params<'a>(heightmap: &'a Heightmap, pattern: PolarPattern, boundary: &'a [Vec<Point2>]) -> PolarFinishParams<'a> {
+    PolarFinishParams {
+      heightmap,
+      tool_type: "ball",
+      cutter_diameter: 3.0,
+      v_angle_deg: 0.0,
+      pattern,
+      center: [20.0, 20.0],
+      radius: 0.0,
+      stepover: 1.5,
+      sample_step: 0.25,
+      boundary,
+    }
+  }
+
+  #[test]
+  fn spiral_has_constant_stepover_and_rides_surface() {
+    // Dome of radius 15 on a flat floor at -5
+    let heights: Vec<f32> = (0..41 * 41)
+      .map(|k| {
+        let (x, y) = ((k / 41) as f32 - 20.0, (k % 41) as f32 - 20.0);
+        (15.0f32 * 15.0 - x * x - y * y).max(0.0).sqrt().max(5.0) - 10.0
+      })
+      .collect();
+    let heightmap = Heightmap::from_vec(41, 41, 1.0, 1.0, 0.0, 0.0, heights);
+    let passes = generate_polar_finish(&params(&heightmap, PolarPattern::Spiral, &[]));
+    // Clipped where the spiral leaves the square heightmap
+    assert!(passes.len() > 1);
+    let first = &passes[0];
+    // Radius grows one stepover per turn
+    let radius = |p: &[f32; 3]| (p[0] - 20.0).hypot(p[1] - 20.0);
+    let angle = |p: &[f32; 3]| (p[1] - 20.0).atan2(p[0] - 20.0);
+    for w in first.windows(2).filter(|w| radius(&w[0]) > 3.0) {
+      let expected = 1.5 / std::f32::consts::TAU * (angle(&w[1]) - angle(&w[0])).rem_euclid(std::f32::consts::TAU);
+      assert!((radius(&w[1]) - radius(&w[0]) - expected).abs() < 1e-3);
+    }
+    // Top of the dome, and the ball never dips below the surface under it
+    assert!((first[0][2] - 5.0).abs() < 1e-4);
+    assert!(passes.iter().flatten().all(|p| p[2] >= -5.0 - 1e-4));
+    assert!(passes.iter().flatten().all(|p| p[0] >= -0.5 && p[0] <= 40.5 && p[1] >= -0.5 && p[1] <= 40.5));
+  }
+
+  #[test]
+  fn radial_spokes_keep_gap_near_stepover_inside_boundary() {
+    let heightmap = Heightmap::new(41, 41, 1.0, 1.0, 0.0, 0.0, -1.0);
+    let boundary = vec![(0..64).map(|i| {
+      let a = i as f32 / 64.0 * std::f32::consts::TAU;
+      [20.0 + 12.0 * a.cos(), 20.0 + 12.0 * a.sin()]
+    }).collect::<Vec<Point2>>()];
+    let passes = generate_polar_finish(&params(&heightmap, PolarPattern::Radial, &boundary));
+    assert!(passes.iter().flatten().all(|p| (p[0] - 20.0).hypot(p[1] - 20.0) <= 12.0 && p[2] == -1.0));
+    // Away from the center, neighboring spokes are between RADIAL_BAND and one stepover apart
+    for r in [2.0f32, 5.0, 8.0, 11.5] {
+      let mut angles: Vec<f32> = passes
+        .iter()
+        .filter(|p| p.iter().any(|q| (q[0] - 20.0).hypot(q[1] - 20.0) <= r) && p.iter().any(|q| (q[0] - 20.0).hypot(q[1] - 20.0) >= r))
+        .map(|p| {
+          let q = p.iter().max_by(|a, b| (a[0] - 20.0).hypot(a[1] - 20.0).partial_cmp(&(b[0] - 20.0).hypot(b[1] - 20.0)).unwrap()).unwrap();
+          (q[1] - 20.0).atan2(q[0] - 20.0)
+        })
+        .collect();
+      angles.sort_by(f32::total_cmp);
+      let gaps: Vec<f32> = angles.windows(2).map(|w| w[1] - w[0]).chain(std::iter::once(angles[0] + std::f32::consts::TAU - angles[angles.len() - 1])).collect();
+      let max_gap = gaps.iter().cloned().fold(0.0, f32::max) * r;
+      let min_gap = gaps.iter().cloned().fold(f32::INFINITY, f32::min) * r;
+      assert!(max_gap <= 1.5 + 1e-3, "gap {} at radius {}", max_gap, r);
+      assert!(min_gap >= RADIAL_BAND * 1.5 - 1e-3, "gap {} at radius {}", min_gap, r);
+    }
+  }
+
+  #[test]
+  fn radial_spokes_follow_cut_direction() {
+    let heightmap = Heightmap::new(41, 41, 1.0, 1.0, 0.0, 0.0, 0.0);
+    let radius = |p: &[f32; 3]| (p[0] - 20.0).hypot(p[1] - 20.0);
+    for (direction, inward) in [(CutDirection::Climb, true), (CutDirection::Conventional, false)] {
+      set_cut_direction(direction);
+      let passes = generate_polar_finish(&params(&heightmap, PolarPattern::Radial, &[]));
+      assert!(passes.iter().all(|p| (radius(&p[p.len() - 1]) < radius(&p[0])) == inward), "{:?}", direction);
+    }
+    set_cut_direction(CutDirection::Either);
+  }
+}
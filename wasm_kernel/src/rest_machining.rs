@@ -0,0 +1,267 @@
+// Rest machining: raster passes for a smaller tool only where a previous tool left material
+// All units are mm unless otherwise noted
+//
+// The stock after simulate_material_removal (any StockModel) is compared with the target part
+// surface at the part's nodes. Nodes where the stock is more than `threshold` above the part hold remaining
+// material. A raster (rows along x, stepover apart in y) is laid over the grid at
+// each stepdown level; a sample is kept only if remaining material inside the tool
+// footprint is above the cut z there, so the tool skips everything the previous tool
+// already cleared. z is the level, raised to the safe surface of the part under the tool.
+//
+// Assumptions:
+// - The stock is sampled at the part's nodes; part nodes off the stock have no material left
+// - Levels step down from the highest remaining stock by stepdown; stepdown <= 0 cuts in
+//   a single level straight to the part surface
+// - A row is split into separate passes wherever samples are dropped
+// - Passes are ordered level by level, rows alternating direction unless the global cut
+//   direction asks for one-way rows
+
+use crate::cut_direction::raster_row_reversed;
+use crate::heightmap::Heightmap;
+use crate::simulate_material_removal::generate_safe_toolpath;
+use crate::stock_model::StockModel;
+use crate::toolpath::Pass;
+
+pub struct RestMachiningParams<'a> {
+  pub part: &'a Heightmap,  // target surface
+  pub stock: &'a dyn StockModel, // simulated stock after the previous tool
+  pub tool_type: &'a str,
+  pub cutter_diameter: f32,
+  pub v_angle_deg: f32,
+  pub stepover: f32,
+  pub stepdown: f32,    // <= 0 for a single level at the part surface
+  pub threshold: f32,   // stock left above the part that counts as remaining material
+  pub sample_step: f32, // distance between samples along a row, <= 0 for the grid size
+}
+
+// Highest remaining stock (sampled on the part grid) within the tool radius of each node,
+// NEG_INFINITY where there is none
+fn remaining_under_tool(params: &RestMachiningParams, stock: &Heightmap, remaining: &[bool]) -> Vec<f32> {
+  let (nx, ny) = (stock.nx, stock.ny);
+  let r = params.cutter_diameter / 2.0;
+  let rx = (r / stock.grid_size_x).ceil() as isize;
+  let ry = (r / stock.grid_size_y).ceil() as isize;
+  let offsets: Vec<(isize, isize)> = (-rx..=rx)
+    .flat_map(|dx| (-ry..=ry).map(move |dy| (dx, dy)))
+    .filter(|&(dx, dy)| (dx as f32 * stock.grid_size_x).hypot(dy as f32 * stock.grid_size_y) <= r + 1e-6)
+    .collect();
+  let mut result = vec![f32::NEG_INFINITY; nx * ny];
+  for ix in 0..nx as isize {
+    for iy in 0..ny as isize {
+      let k = stock.index(ix as usize, iy as usize);
+      if !remaining[k] {
+        continue;
+      }
+      // Spread this node's stock height to every tool center that would touch it
+      for &(dx, dy) in &offsets {
+        let (jx, jy) = (ix + dx, iy + dy);
+        if jx >= 0 && jy >= 0 && (jx as usize) < nx && (jy as usize) < ny {
+          let j = stock.index(jx as usize, jy as usize);
+          result[j] = result[j].max(stock.heights[k]);
+        }
+      }
+    }
+  }
+  result
+}
+
+/// Generate raster passes that only visit regions where material remains
+pub fn generate_rest_toolpath(params: &RestMachiningParams) -> Vec<Pass> {
+  let part = params.part;
+  if part.nx == 0 || part.ny == 0 || params.stepover <= 0.0 {
+    return Vec::new();
+  }
+  let stock = params.stock.heights_on(part);
+  let remaining = stock.above(part, params.threshold);
+  if !remaining.iter().any(|&m| m) {
+    return Vec::new();
+  }
+  let reach = remaining_under_tool(params, &stock, &remaining);
+  let top = stock.heights.iter().zip(&remaining).filter(|(_, &m)| m).map(|(&s, _)| s).fold(f32::NEG_INFINITY, f32::max);
+
+  // Raster rows and the safe surface under each sample
+  let step = if params.sample_step > 0.0 { params.sample_step } else { part.grid_size_x };
+  let width = part.width();
+  let height = part.depth();
+  let columns = (width / step).ceil() as usize + 1;
+  let rows = (height / params.stepover).ceil() as usize + 1;
+  let xy: Vec<(f32, f32)> = (0..rows)
+    .flat_map(|row| {
+      let y = part.origin_y + (row as f32 * params.stepover).min(height);
+      (0..columns).map(move |c| (part.origin_x + (c as f32 * step).min(width), y))
+    })
+    .collect();
+  let safe = generate_safe_toolpath(
+    params.part,
+    params.tool_type,
+    params.cutter_diameter,
+    params.v_angle_deg,
+    &xy,
+  );
+  let node = |x: f32, y: f32| {
+    let (ix, iy) = part.nearest_clamped(x, y);
+    part.index(ix, iy)
+  };
+
+  let floor = safe.iter().cloned().fold(f32::INFINITY, f32::min);
+  let levels: Vec<f32> = if params.stepdown > 0.0 {
+    let count = ((top - floor) / params.stepdown).ceil().max(1.0) as usize;
+    (1..=count).map(|i| top - i as f32 * params.stepdown).collect()
+  } else {
+    vec![f32::NEG_INFINITY]
+  };
+
+  let mut passes = Vec::new();
+  for level in levels {
+    for row in 0..rows {
+      let mut samples: Vec<usize> = (row * columns..(row + 1) * columns).collect();
+      if raster_row_reversed().unwrap_or(row % 2 == 1) {
+        samples.reverse();
+      }
+      let mut pass = Pass::new();
+      for i in samples {
+        let (x, y) = xy[i];
+        let z = level.max(safe[i]);
+        if reach[node(x, y)] > z + params.threshold {
+          pass.push([x, y, z]);
+        } else if !pass.is_empty() {
+          passes.push(std::mem::take(&mut pass));
+        }
+      }
+      if !pass.is_empty() {
+        passes.push(pass);
+      }
+    }
+  }
+  passes
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Float32Array, Object};
+use crate::toolpath::{passes_to_js, surface_max};
+
+#[wasm_bindgen]
+pub fn generate_rest_toolpath_wasm(
+  part_js: &Float32Array,
+  stock_js: &Float32Array,
+  nx: usize,
+  ny: usize,
+  grid_size_x: f32,
+  grid_size_y: f32,
+  origin_x: f32,
+  origin_y: f32,
+  tool_type: &str,
+  cutter_diameter: f32,
+  v_angle_deg: f32,
+  stepover: f32,
+  stepdown: f32,
+  threshold: f32,
+  sample_step: f32,
+  clearance_z: f32,
+) -> Object {
+  let part = Heightmap::from_vec(nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, part_js.to_vec());
+  let stock = Heightmap::from_vec(nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, stock_js.to_vec());
+  let passes = generate_rest_toolpath(&RestMachiningParams {
+    part: &part,
+    stock: &stock,
+    tool_type,
+    cutter_diameter,
+    v_angle_deg,
+    stepover,
+    stepdown,
+    threshold,
+    sample_step,
+  });
+  passes_to_js(&passes, clearance_z.max(surface_max(&stock.heights)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::dexel_stock::DexelStock;
+  use crate::simulate_material_removal::{simulate_material_removal, SimulateMaterialRemovalParams};
+
+  const N: usize = 61;
+  const CELL: f32 = 0.5;
+
+  fn cut(stock: &mut Heightmap, passes: &[Pass], diameter: f32) {
+    let toolpath: Vec<f32> = passes.iter().flatten().flatten().cloned().collect();
+    simulate_material_removal(SimulateMaterialRemovalParams {
+      heightmap: stock,
+      tool_type: "flat",
+      cutter_diameter: diameter,
+      v_angle_deg: 0.0,
+      toolpath: &toolpath,
+      attributes: None,
+    });
+  }
+
+  fn params<'a>(part: &'a Heightmap, stock: &'a dyn StockModel, diameter: f32) -> RestMachiningParams<'a> {
+    RestMachiningParams {
+      part,
+      stock,
+      tool_type: "flat",
+      cutter_diameter: diameter,
+      v_angle_deg: 0.0,
+      stepover: CELL,
+      stepdown: 2.0,
+      threshold: 0.05,
+      sample_step: CELL,
+    }
+  }
+
+  // Square pocket 5 deep over [10, 20]^2
+  fn pocket() -> Heightmap {
+    let mut part = Heightmap::new(N, N, CELL, CELL, 0.0, 0.0, 0.0);
+    for ix in 0..N {
+      for iy in 0..N {
+        let (x, y) = (part.x(ix), part.y(iy));
+        if (10.0..=20.0).contains(&x) && (10.0..=20.0).contains(&y) {
+          part.set(ix, iy, -5.0);
+        }
+      }
+    }
+    part
+  }
+
+  fn leftover(part: &Heightmap, stock: &Heightmap) -> f32 {
+    part.heights.iter().zip(&stock.heights).map(|(p, s)| s - p).sum::<f32>() * CELL * CELL
+  }
+
+  #[test]
+  fn smaller_tool_only_visits_corners() {
+    let part = pocket();
+    let mut stock = part.with_heights(vec![0.0; N * N]);
+    // Rough the whole pocket with a 6 mm end mill: it can't reach the square corners
+    let rough = generate_rest_toolpath(&params(&part, &stock, 6.0));
+    cut(&mut stock, &rough, 6.0);
+    let before = leftover(&part, &stock);
+    assert!(before > 1.0, "left {}", before);
+
+    let rest = generate_rest_toolpath(&params(&part, &stock, 2.0));
+    assert!(!rest.is_empty());
+    let corners = [[10.0, 10.0], [10.0, 20.0], [20.0, 10.0], [20.0, 20.0]];
+    assert!(rest.iter().flatten().all(|p| corners.iter().any(|c| (p[0] - c[0]).hypot(p[1] - c[1]) < 4.0)));
+    cut(&mut stock, &rest, 2.0);
+    let after = leftover(&part, &stock);
+    assert!(after < before / 3.0, "left {} after rest, {} before", after, before);
+    assert!(stock.heights.iter().zip(&part.heights).all(|(s, p)| *s >= p - 1e-4));
+  }
+
+  #[test]
+  fn nothing_to_do_when_stock_matches_part() {
+    let part = pocket();
+    assert!(generate_rest_toolpath(&params(&part, &part, 2.0)).is_empty());
+  }
+
+  #[test]
+  fn stock_on_another_model_and_grid() {
+    let part = pocket();
+    // Untouched block as dexels on a finer grid gives the same passes as a heightmap
+    let block = DexelStock::new_box(0.0, 0.0, 2 * N - 1, 2 * N - 1, CELL / 2.0, CELL / 2.0, -10.0, 0.0);
+    let flat = part.with_heights(vec![0.0; N * N]);
+    let passes = generate_rest_toolpath(&params(&part, &block, 2.0));
+    assert!(!passes.is_empty());
+    assert_eq!(passes, generate_rest_toolpath(&params(&part, &flat, 2.0)));
+  }
+}
@@ -0,0 +1,294 @@
+// Cylindrical stock for a 4th (A) axis: material radius as a function of x and angle
+// All units are mm unless otherwise noted
+//
+// The stock turns about the X axis. Each node holds the radius of the material along a ray
+// from the axis at (x, angle), like a heightmap wrapped around a cylinder. The tool stays
+// vertical over the axis and moves in X and Z while A turns the stock, so a toolpath point is
+// (x, a, z): a in degrees, z the height of the tool tip above the axis. Each tool position
+// is intersected exactly with every ray under it.
+//
+// Assumptions:
+// - Node (ix, ia) is at x = origin_x + ix * cell_x and material angle ia * 360 / na degrees,
+//   layout ix * na + ia
+// - Angles are measured from +Z toward +Y; A turns the stock right-handed about +X, so with
+//   A = a the tool above the axis is over material angle a
+// - The tool tip is at y = 0; moves are sampled at most half a cell apart in x, z and angle
+// - A ray only loses material when the tool covers it from where it enters out past the current
+//   radius; a tool passing through the middle of a ray (an undercut) leaves it, as the heightmap does
+// - The mesh is shown at A = 0
+
+use crate::dexel_stock::{clip_hull, quadratic_le_zero};
+use crate::heightmap_to_solid_mesh::Mesh;
+use crate::stock_model::ToolShape;
+
+#[derive(Clone, Debug)]
+pub struct RotaryStock {
+  pub nx: usize,
+  pub na: usize,
+  pub cell_x: f32,
+  pub origin_x: f32,
+  pub radii: Vec<f32>, // per node (ix * na + ia)
+}
+
+/// Distances along the ray from the axis at world angle phi (radians) covered by the tool,
+/// dx from the tool axis in x and with its tip tz above the axis
+pub fn ray_in_tool(tool: &ToolShape, dx: f32, tz: f32, phi: f32) -> Option<(f32, f32)> {
+  let r = tool.cutter_diameter / 2.0;
+  if dx.abs() > r {
+    return None;
+  }
+  // Half width of the tool in this plane; the ray leaves it sideways at `reach`
+  let w = (r * r - dx * dx).sqrt();
+  let (s, c) = phi.sin_cos();
+  let reach = if s.abs() < 1e-9 { f32::INFINITY } else { w / s.abs() };
+  // rho * c >= z
+  let above = |z: f32| quadratic_le_zero(0.0, -c, z);
+  match tool.tool_type {
+    "ball" => {
+      // Cylinder above the ball center, or inside the ball (a circle of radius w in this plane)
+      let z0 = tz + r;
+      let mut pieces = above(z0);
+      pieces.extend(quadratic_le_zero(1.0, -2.0 * z0 * c, z0 * z0 - w * w));
+      clip_hull(pieces, (0.0, reach))
+    }
+    "vbit" => {
+      // (rho c - tz)^2 >= k^2 (dx^2 + rho^2 s^2) on the upper nappe
+      let k = 1.0 / (tool.v_angle_deg.to_radians() / 2.0).tan().max(1e-6);
+      let within = clip_hull(above(tz), (0.0, reach))?;
+      clip_hull(quadratic_le_zero(k * k * s * s - c * c, 2.0 * tz * c, k * k * dx * dx - tz * tz), within)
+    }
+    _ => clip_hull(above(tz), (0.0, reach)),
+  }
+}
+
+impl RotaryStock {
+  /// Round bar of the given radius, length along x from origin_x, nx by na nodes
+  pub fn new_cylinder(origin_x: f32, length: f32, nx: usize, na: usize, radius: f32) -> RotaryStock {
+    let cell_x = if nx > 1 { length / (nx - 1) as f32 } else { length };
+    RotaryStock { nx, na, cell_x, origin_x, radii: vec![radius; nx * na] }
+  }
+
+  /// Angle between neighboring rays in radians
+  pub fn cell_a(&self) -> f32 {
+    std::f32::consts::TAU / self.na as f32
+  }
+
+  /// Radius at the node nearest (x, angle in degrees), None off the ends
+  pub fn radius_at(&self, x: f32, angle_deg: f32) -> Option<f32> {
+    let ix = ((x - self.origin_x) / self.cell_x).round();
+    if ix < 0.0 || ix as usize >= self.nx || self.na == 0 {
+      return None;
+    }
+    let ia = (angle_deg.to_radians() / self.cell_a()).round() as isize;
+    Some(self.radii[ix as usize * self.na + ia.rem_euclid(self.na as isize) as usize])
+  }
+
+  /// Remove the material swept by the tool through (x, a degrees, z) toolpath points
+  pub fn remove_swept_tool(&mut self, tool: &ToolShape, toolpath: &[[f32; 3]]) {
+    if self.nx == 0 || self.na == 0 {
+      return;
+    }
+    let r = tool.cutter_diameter / 2.0;
+    let cell_a_deg = 360.0 / self.na as f32;
+    let mut samples = toolpath.first().map(|&p| vec![p]).unwrap_or_default();
+    for w in toolpath.windows(2) {
+      let (a, b) = (w[0], w[1]);
+      let n = ((b[0] - a[0]).abs() / (self.cell_x / 2.0))
+        .max((b[2] - a[2]).abs() / (self.cell_x / 2.0))
+        .max((b[1] - a[1]).abs() / (cell_a_deg / 2.0))
+        .ceil()
+        .max(1.0) as usize;
+      samples.extend((1..=n).map(|i| {
+        let t = i as f32 / n as f32;
+        [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t]
+      }));
+    }
+    for [x, a, z] in samples {
+      let ix0 = ((x - r - self.origin_x) / self.cell_x).ceil().max(0.0) as usize;
+      let ix1 = ((x + r - self.origin_x) / self.cell_x).floor();
+      if ix1 < 0.0 {
+        continue;
+      }
+      for ix in ix0..=(ix1 as usize).min(self.nx - 1) {
+        let dx = self.origin_x + ix as f32 * self.cell_x - x;
+        for ia in 0..self.na {
+          // World angle of this ray with the stock turned to a
+          let phi = ia as f32 * self.cell_a() - a.to_radians();
+          if z > 0.0 && phi.cos() <= 0.0 {
+            continue;
+          }
+          let k = ix * self.na + ia;
+          if let Some((lo, hi)) = ray_in_tool(tool, dx, z, phi) {
+            if lo < self.radii[k] && hi >= self.radii[k] {
+              self.radii[k] = lo.max(0.0);
+            }
+          }
+        }
+      }
+    }
+  }
+
+  /// Volume of the material in mm^3, trapezoid rule along x
+  pub fn volume(&self) -> f32 {
+    let mut sum = 0.0;
+    for ix in 0..self.nx {
+      let weight = if self.nx > 1 && (ix == 0 || ix == self.nx - 1) { 0.5 } else { 1.0 };
+      let area: f32 = self.radii[ix * self.na..(ix + 1) * self.na].iter().map(|r| 0.5 * r * r).sum();
+      sum += weight * area;
+    }
+    sum * self.cell_a() * self.cell_x
+  }
+
+  /// Closed mesh of the surface around the axis, with flat end caps
+  pub fn to_mesh(&self) -> Mesh {
+    let (nx, na) = (self.nx, self.na);
+    let mut positions = Vec::new();
+    let mut indices = Vec::new();
+    let point = |ix: usize, ia: usize| {
+      let (s, c) = (ia as f32 * self.cell_a()).sin_cos();
+      let r = self.radii[ix * na + ia];
+      [self.origin_x + ix as f32 * self.cell_x, r * s, r * c]
+    };
+    for ix in 0..nx {
+      for ia in 0..na {
+        positions.extend_from_slice(&point(ix, ia));
+      }
+    }
+    let idx = |ix: usize, ia: usize| (ix * na + ia % na) as u32;
+    for ix in 0..nx.saturating_sub(1) {
+      for ia in 0..na {
+        indices.extend_from_slice(&[idx(ix, ia), idx(ix + 1, ia), idx(ix + 1, ia + 1), idx(ix, ia), idx(ix + 1, ia + 1), idx(ix, ia + 1)]);
+      }
+    }
+    // End caps get their own ring of vertices for sharp normals, fanned from the axis
+    for (ix, outward) in [(0, false), (nx.saturating_sub(1), true)] {
+      if nx < 2 || na < 3 {
+        break;
+      }
+      let center = (positions.len() / 3) as u32;
+      positions.extend_from_slice(&[self.origin_x + ix as f32 * self.cell_x, 0.0, 0.0]);
+      for ia in 0..na {
+        positions.extend_from_slice(&point(ix, ia));
+      }
+      for ia in 0..na as u32 {
+        let (a, b) = (center + 1 + ia, center + 1 + (ia + 1) % na as u32);
+        indices.extend_from_slice(&if outward { [center, b, a] } else { [center, a, b] });
+      }
+    }
+    // Per-vertex normals averaged from the faces
+    let mut normals = vec![0.0f32; positions.len()];
+    for tri in indices.chunks(3) {
+      let p = |i: u32| [positions[i as usize * 3], positions[i as usize * 3 + 1], positions[i as usize * 3 + 2]];
+      let (a, b, c) = (p(tri[0]), p(tri[1]), p(tri[2]));
+      let (u, v) = ([b[0] - a[0], b[1] - a[1], b[2] - a[2]], [c[0] - a[0], c[1] - a[1], c[2] - a[2]]);
+      let n = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+      for &i in tri {
+        for j in 0..3 {
+          normals[i as usize * 3 + j] += n[j];
+        }
+      }
+    }
+    for n in normals.chunks_mut(3) {
+      let mag = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+      if mag > 1e-8 {
+        n.iter_mut().for_each(|v| *v /= mag);
+      }
+    }
+    Mesh { positions, indices, normals }
+  }
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Float32Array, Object, Uint32Array};
+
+fn rotary_stock_from_js(radii_js: &Float32Array, nx: usize, na: usize, cell_x: f32, origin_x: f32) -> RotaryStock {
+  let radii = radii_js.to_vec();
+  if radii.len() != nx * na {
+    panic!("radii length {} does not match nx*na {}", radii.len(), nx * na);
+  }
+  RotaryStock { nx, na, cell_x, origin_x, radii }
+}
+
+/// Cut the toolpath (flat x, a degrees, z) into the radii in place
+#[wasm_bindgen]
+pub fn simulate_rotary_removal_wasm(
+  radii_js: &Float32Array,
+  nx: usize,
+  na: usize,
+  cell_x: f32,
+  origin_x: f32,
+  tool_type: &str,
+  cutter_diameter: f32,
+  v_angle_deg: f32,
+  toolpath_js: &Float32Array,
+) {
+  let mut stock = rotary_stock_from_js(radii_js, nx, na, cell_x, origin_x);
+  let toolpath: Vec<[f32; 3]> = toolpath_js.to_vec().chunks_exact(3).map(|p| [p[0], p[1], p[2]]).collect();
+  stock.remove_swept_tool(&ToolShape { tool_type, cutter_diameter, v_angle_deg }, &toolpath);
+  radii_js.copy_from(&stock.radii);
+}
+
+/// Returns { positions, indices, normals } of the stock at A = 0
+#[wasm_bindgen]
+pub fn rotary_stock_to_mesh_wasm(radii_js: &Float32Array, nx: usize, na: usize, cell_x: f32, origin_x: f32) -> Object {
+  let mesh = rotary_stock_from_js(radii_js, nx, na, cell_x, origin_x).to_mesh();
+  let result = Object::new();
+  js_sys::Reflect::set(&result, &"positions".into(), &Float32Array::from(mesh.positions.as_slice())).unwrap();
+  js_sys::Reflect::set(&result, &"indices".into(), &Uint32Array::from(mesh.indices.as_slice())).unwrap();
+  js_sys::Reflect::set(&result, &"normals".into(), &Float32Array::from(mesh.normals.as_slice())).unwrap();
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const FLAT: ToolShape = ToolShape { tool_type: "flat", cutter_diameter: 4.0, v_angle_deg: 0.0 };
+
+  // 20 mm long bar of radius 10, 0.5 mm by 2 degree cells
+  fn bar() -> RotaryStock {
+    RotaryStock::new_cylinder(0.0, 20.0, 41, 180, 10.0)
+  }
+
+  #[test]
+  fn flat_along_x_cuts_a_flat() {
+    let mut stock = bar();
+    let full = stock.volume();
+    assert!((full - std::f32::consts::PI * 100.0 * 20.0).abs() < full * 0.01, "{}", full);
+    stock.remove_swept_tool(&FLAT, &[[-5.0, 90.0, 8.0], [25.0, 90.0, 8.0]]);
+    assert_eq!(stock.radius_at(10.0, 90.0), Some(8.0));
+    // 4 degrees off the top the flat is still 8 above the axis
+    assert!((stock.radius_at(10.0, 94.0).unwrap() - 8.0 / 4f32.to_radians().cos()).abs() < 1e-4);
+    // Out past the tool width the bar is round; the other side is untouched
+    assert_eq!(stock.radius_at(10.0, 90.0 + 20.0), Some(10.0));
+    assert_eq!(stock.radius_at(10.0, 270.0), Some(10.0));
+  }
+
+  #[test]
+  fn turning_a_full_turn_cuts_a_groove() {
+    let mut stock = bar();
+    stock.remove_swept_tool(&FLAT, &[[10.0, 0.0, 12.0], [10.0, 0.0, 6.0], [10.0, 360.0, 6.0], [10.0, 360.0, 12.0]]);
+    for angle in (0..360).step_by(10) {
+      assert_eq!(stock.radius_at(10.0, angle as f32), Some(6.0));
+      assert_eq!(stock.radius_at(10.0 + 1.5, angle as f32), Some(6.0));
+      assert_eq!(stock.radius_at(10.0 + 2.5, angle as f32), Some(10.0));
+    }
+    // A 4 mm wide groove from radius 10 down to 6
+    let removed = bar().volume() - stock.volume();
+    let expected = std::f32::consts::PI * (100.0 - 36.0) * 4.0;
+    assert!((removed - expected).abs() < expected * 0.15, "removed {} expected {}", removed, expected);
+  }
+
+  #[test]
+  fn deep_plunge_leaves_the_sides_of_a_slot() {
+    let mut stock = bar();
+    let tool = ToolShape { tool_type: "ball", cutter_diameter: 4.0, v_angle_deg: 0.0 };
+    stock.remove_swept_tool(&tool, &[[5.0, 0.0, 2.0], [15.0, 0.0, 2.0]]);
+    assert_eq!(stock.radius_at(10.0, 0.0), Some(2.0));
+    // 40 degrees over, the ray leaves the slot wall long before the surface: an undercut, left as is
+    assert_eq!(stock.radius_at(10.0, 40.0), Some(10.0));
+    let mesh = stock.to_mesh();
+    assert_eq!(mesh.positions.len(), (41 * 180 + 2 * 181) * 3);
+    assert!(mesh.indices.iter().all(|&i| (i as usize) < mesh.positions.len() / 3));
+  }
+}
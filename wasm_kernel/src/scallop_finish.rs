@@ -0,0 +1,263 @@
+// Constant-scallop finishing with a ball end mill
+// All units are mm unless otherwise noted
+//
+// Passes are spaced a constant distance apart measured along the surface, not in XY, so
+// steep areas get passes closer together in XY and the scallop left between passes is the
+// same everywhere. The surface distance from the boundary is found with Dijkstra over the
+// tool tip surface (16 neighbors per node); passes are its level sets, one stepover apart.
+// The predicted scallop height at every node is reported alongside the passes.
+//
+// Assumptions:
+// - Node (0, 0) of the heightmap is centered at its origin
+// - The tool is a ball end mill; stepover comes from the scallop height on a flat surface,
+//   s = 2 * sqrt(r^2 - (r - h)^2), and surface curvature is ignored
+// - Passes start half a stepover in from the boundary (the heightmap edge, or the boundary loops)
+//   and run outermost first, following the global cut direction
+// - The predicted scallop at a node is r - sqrt(r^2 - d^2), with d the surface distance to the
+//   nearest pass
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use crate::cut_direction::loop_needs_reverse;
+use crate::geometry_2d::{even_odd_grid, marching_squares, signed_area, Point2};
+use crate::heightmap::Heightmap;
+use crate::simulate_material_removal::generate_safe_toolpath;
+use crate::toolpath::Pass;
+
+pub struct ScallopFinishParams<'a> {
+  pub heightmap: &'a Heightmap,
+  pub cutter_diameter: f32, // ball end mill
+  pub scallop_height: f32,
+  pub boundary: &'a [Vec<Point2>], // even-odd loops, empty for the whole heightmap
+}
+
+#[derive(Clone, Debug)]
+pub struct ScallopFinish {
+  pub passes: Vec<Pass>,
+  pub scallop: Heightmap, // predicted scallop height on the input grid, 0 outside the boundary
+  pub stepover: f32,     // pass spacing along the surface
+}
+
+// Min-heap entry for Dijkstra
+#[derive(PartialEq)]
+struct Visit(f32, usize);
+
+impl Eq for Visit {}
+
+impl Ord for Visit {
+  fn cmp(&self, other: &Self) -> Ordering {
+    other.0.partial_cmp(&self.0).unwrap_or(Ordering::Equal)
+  }
+}
+
+impl PartialOrd for Visit {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+/// Generate constant-scallop passes and the predicted scallop heightmap
+pub fn generate_scallop_finish(params: &ScallopFinishParams) -> ScallopFinish {
+  let map = params.heightmap;
+  let (nx, ny) = (map.nx, map.ny);
+  let (gx, gy) = (map.grid_size_x, map.grid_size_y);
+  let r = params.cutter_diameter / 2.0;
+  let h = params.scallop_height.clamp(1e-6, r);
+  let stepover = 2.0 * (r * r - (r - h) * (r - h)).sqrt();
+  let mut result = ScallopFinish { passes: Vec::new(), scallop: map.with_heights(vec![0.0; nx * ny]), stepover };
+  if nx < 2 || ny < 2 || r <= 0.0 {
+    return result;
+  }
+
+  // Tool tip surface: where the ball rides at each node
+  let nodes: Vec<(f32, f32)> = (0..nx * ny)
+    .map(|k| {
+      let (ix, iy) = map.node(k);
+      (map.x(ix), map.y(iy))
+    })
+    .collect();
+  let tip = generate_safe_toolpath(map, "ball", params.cutter_diameter, 0.0, &nodes);
+  let region = if params.boundary.is_empty() {
+    vec![true; nx * ny]
+  } else {
+    even_odd_grid(params.boundary, [map.origin_x, map.origin_y], gx, gy, nx, ny)
+  };
+
+  // Surface distance from the boundary: seeds are the heightmap edge and nodes outside the region
+  let mut dist = vec![f32::INFINITY; nx * ny];
+  let mut heap = BinaryHeap::new();
+  for ix in 0..nx {
+    for iy in 0..ny {
+      let k = map.index(ix, iy);
+      let edge = ix == 0 || iy == 0 || ix == nx - 1 || iy == ny - 1;
+      if !region[k] || edge {
+        dist[k] = 0.0;
+        heap.push(Visit(0.0, k));
+      }
+    }
+  }
+  const STEPS: [(isize, isize); 16] = [
+    (1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1),
+    (1, 2), (2, 1), (-1, 2), (-2, 1), (1, -2), (2, -1), (-1, -2), (-2, -1),
+  ];
+  while let Some(Visit(d, k)) = heap.pop() {
+    if d > dist[k] {
+      continue;
+    }
+    let (ix, iy) = map.node(k);
+    let (ix, iy) = (ix as isize, iy as isize);
+    for (dx, dy) in STEPS {
+      let (jx, jy) = (ix + dx, iy + dy);
+      if jx < 0 || jy < 0 || jx >= nx as isize || jy >= ny as isize {
+        continue;
+      }
+      let j = map.index(jx as usize, jy as usize);
+      if !region[j] {
+        continue;
+      }
+      let dz = tip[j] - tip[k];
+      let nd = d + ((dx as f32 * gx).powi(2) + (dy as f32 * gy).powi(2) + dz * dz).sqrt();
+      if nd < dist[j] {
+        dist[j] = nd;
+        heap.push(Visit(nd, j));
+      }
+    }
+  }
+
+  // Passes at (k + 0.5) stepovers from the boundary, outermost first
+  let field: Vec<f32> = dist.iter().zip(region.iter()).map(|(&d, &inside)| if inside { d } else { -1.0 }).collect();
+  let max_dist = field.iter().cloned().fold(0.0, f32::max);
+  let levels: Vec<f32> = (0..).map(|k| (k as f32 + 0.5) * stepover).take_while(|&l| l < max_dist).collect();
+  for &level in &levels {
+    for mut l in marching_squares(&field, nx, ny, [map.origin_x, map.origin_y], gx, gy, level) {
+      // Loops run counter-clockwise around the uncut area further in
+      if loop_needs_reverse(&l, signed_area(&l) >= 0.0) {
+        l.reverse();
+      }
+      let xy: Vec<(f32, f32)> = l.iter().chain(l.first()).map(|p| (p[0], p[1])).collect();
+      let zs = generate_safe_toolpath(map, "ball", params.cutter_diameter, 0.0, &xy);
+      result.passes.push(xy.iter().zip(zs).map(|(&(x, y), z)| [x, y, z]).collect());
+    }
+  }
+
+  // Distance to the nearest pass gives the scallop left at each node
+  let scallop = field
+    .iter()
+    .map(|&d| {
+      if d < 0.0 || levels.is_empty() {
+        return 0.0;
+      }
+      let k = ((d / stepover - 0.5).round().max(0.0) as usize).min(levels.len() - 1);
+      let off = (d - levels[k]).abs().min(r);
+      r - (r * r - off * off).sqrt()
+    })
+    .collect();
+  result.scallop = map.with_heights(scallop);
+  result
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Float32Array, Object, Uint32Array};
+use crate::toolpath::{passes_to_js, split_polylines, surface_max};
+
+/// Returns { passes, linked, scallop: Float32Array (heightmap layout, on the input grid), stepover }
+#[wasm_bindgen]
+pub fn generate_scallop_finish_wasm(
+  heightmap_js: &Float32Array,
+  nx: usize,
+  ny: usize,
+  grid_size_x: f32,
+  grid_size_y: f32,
+  origin_x: f32,
+  origin_y: f32,
+  cutter_diameter: f32,
+  scallop_height: f32,
+  boundary_points_js: &Float32Array, // flat x, y of boundary loops, empty for none
+  boundary_counts_js: &Uint32Array,
+  clearance_z: f32,
+) -> Object {
+  let heightmap = Heightmap::from_vec(nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, heightmap_js.to_vec());
+  let boundary = split_polylines::<2>(&boundary_points_js.to_vec(), &boundary_counts_js.to_vec());
+  let finish = generate_scallop_finish(&ScallopFinishParams {
+    heightmap: &heightmap,
+    cutter_diameter,
+    scallop_height,
+    boundary: &boundary,
+  });
+  let result = passes_to_js(&finish.passes, clearance_z.max(surface_max(&heightmap.heights)));
+  js_sys::Reflect::set(&result, &"scallop".into(), &Float32Array::from(finish.scallop.heights.as_slice())).unwrap();
+  js_sys::Reflect::set(&result, &"stepover".into(), &finish.stepover.into()).unwrap();
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // 61 x 61 nodes, 0.5 mm apart, over [0, 30]^2
+  fn params<'a>(heightmap: &'a Heightmap, boundary: &'a [Vec<Point2>]) -> ScallopFinishParams<'a> {
+    ScallopFinishParams {
+      heightmap,
+      cutter_diameter: 6.0,
+      scallop_height: 0.1,
+      boundary,
+    }
+  }
+
+  // Mean spacing between passes crossing the line x = 15.25 (vertical) or y = 15.25,
+  // taking only crossings between lo and hi along the line
+  fn mean_gap(passes: &[Pass], vertical: bool, lo: f32, hi: f32) -> f32 {
+    let (a, b) = if vertical { (0, 1) } else { (1, 0) };
+    let mut hits: Vec<f32> = passes
+      .iter()
+      .flat_map(|p| p.windows(2))
+      .filter(|w| (w[0][a] - 15.25) * (w[1][a] - 15.25) < 0.0)
+      .map(|w| {
+        let t = (15.25 - w[0][a]) / (w[1][a] - w[0][a]);
+        w[0][b] + (w[1][b] - w[0][b]) * t
+      })
+      .filter(|c| (lo..hi).contains(c))
+      .collect();
+    hits.sort_by(f32::total_cmp);
+    assert!(hits.len() > 2);
+    (hits[hits.len() - 1] - hits[0]) / (hits.len() - 1) as f32
+  }
+
+  #[test]
+  fn flat_surface_uses_flat_stepover() {
+    let heightmap = Heightmap::new(61, 61, 0.5, 0.5, 0.0, 0.0, 0.0);
+    let finish = generate_scallop_finish(&params(&heightmap, &[]));
+    assert!((finish.stepover - 2.0 * (9.0f32 - 2.9 * 2.9).sqrt()).abs() < 1e-4);
+    // Concentric squares one stepover apart
+    for vertical in [true, false] {
+      let gap = mean_gap(&finish.passes, vertical, 0.0, 14.0);
+      assert!((gap - finish.stepover).abs() < 0.1, "gap {}", gap);
+    }
+    assert!(finish.passes.iter().flatten().all(|p| p[2].abs() < 1e-5));
+    let worst = finish.scallop.heights.iter().cloned().fold(0.0, f32::max);
+    assert!(worst <= 0.1 + 1e-4 && worst > 0.05, "worst scallop {}", worst);
+  }
+
+  #[test]
+  fn steep_slope_gets_closer_passes() {
+    // 45 degree ramp rising in y: passes measured from the low edge are 1 / sqrt(2) closer in y
+    let heightmap = Heightmap::from_vec(61, 61, 0.5, 0.5, 0.0, 0.0, (0..61 * 61).map(|k| (k % 61) as f32 * 0.5).collect());
+    let finish = generate_scallop_finish(&params(&heightmap, &[]));
+    let along_slope = mean_gap(&finish.passes, true, 0.0, 10.0);
+    let across_slope = mean_gap(&finish.passes, false, 0.0, 14.0);
+    let ratio = across_slope / along_slope;
+    assert!((ratio - std::f32::consts::SQRT_2).abs() < 0.15, "{} vs {}", along_slope, across_slope);
+    assert!(finish.scallop.heights.iter().all(|&s| s <= 0.1 + 1e-4));
+  }
+
+  #[test]
+  fn boundary_limits_passes() {
+    let heightmap = Heightmap::new(61, 61, 0.5, 0.5, 0.0, 0.0, 0.0);
+    let boundary = vec![vec![[5.0, 5.0], [20.0, 5.0], [20.0, 20.0], [5.0, 20.0]]];
+    let finish = generate_scallop_finish(&params(&heightmap, &boundary));
+    assert!(!finish.passes.is_empty());
+    assert!(finish.passes.iter().flatten().all(|p| p[0] > 5.0 && p[0] < 20.0 && p[1] > 5.0 && p[1] < 20.0));
+    assert_eq!(finish.scallop.height_at(12.5, 25.0), Some(0.0));
+  }
+}
@@ -0,0 +1,350 @@
+// XY outline (silhouette) of a mesh or of the non-empty part of a heightmap
+// All units are mm unless otherwise noted
+//
+// Mesh silhouette: the exact union of the triangles projected onto the XY plane.
+// Candidate boundary edges are split where they cross each other; a piece is on the
+// boundary when exactly one side of it is covered by some triangle.
+//
+// Assumptions:
+// - Projected triangles with ~zero area (vertical walls) are ignored, their neighbours cover them
+// - An edge shared by two triangles that project to opposite sides of it is interior and skipped
+// - Output loops are ready for offset_loops: outers counter-clockwise, holes clockwise
+
+use std::collections::HashMap;
+
+use crate::geometry_2d::{classify_loops, segment_intersection, simplify_loop, marching_squares, Point2, Region};
+use crate::heightmap::Heightmap;
+use crate::heightmap_from_mesh::MeshRust;
+
+// Uniform bucket grid over the XY bounding box, for triangles and segments
+struct BucketGrid {
+  min: Point2,
+  cell: f32,
+  n: usize,
+  buckets: Vec<Vec<usize>>,
+}
+
+impl BucketGrid {
+  fn new(min: Point2, max: Point2, items: usize) -> BucketGrid {
+    let n = ((items as f32).sqrt().ceil() as usize).clamp(1, 512);
+    let cell = ((max[0] - min[0]).max(max[1] - min[1]) / n as f32).max(1e-6);
+    BucketGrid { min, cell, n, buckets: vec![Vec::new(); n * n] }
+  }
+
+  fn range(&self, lo: f32, hi: f32, axis: usize) -> (usize, usize) {
+    let i0 = ((lo - self.min[axis]) / self.cell).floor().max(0.0) as usize;
+    let i1 = ((hi - self.min[axis]) / self.cell).floor().max(0.0) as usize;
+    (i0.min(self.n - 1), i1.min(self.n - 1))
+  }
+
+  fn insert(&mut self, item: usize, lo: Point2, hi: Point2) {
+    let (ix0, ix1) = self.range(lo[0], hi[0], 0);
+    let (iy0, iy1) = self.range(lo[1], hi[1], 1);
+    for ix in ix0..=ix1 {
+      for iy in iy0..=iy1 {
+        self.buckets[ix * self.n + iy].push(item);
+      }
+    }
+  }
+
+  fn at(&self, p: Point2) -> &[usize] {
+    let (ix, _) = self.range(p[0], p[0], 0);
+    let (iy, _) = self.range(p[1], p[1], 1);
+    &self.buckets[ix * self.n + iy]
+  }
+}
+
+fn cross(o: Point2, a: Point2, b: Point2) -> f32 {
+  (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
+
+fn point_in_triangle(p: Point2, t: &[Point2; 3]) -> bool {
+  let d0 = cross(t[0], t[1], p);
+  let d1 = cross(t[1], t[2], p);
+  let d2 = cross(t[2], t[0], p);
+  (d0 > 0.0 && d1 > 0.0 && d2 > 0.0) || (d0 < 0.0 && d1 < 0.0 && d2 < 0.0)
+}
+
+/// Exact silhouette of a mesh projected onto XY, as regions with holes
+pub fn mesh_silhouette(mesh: &MeshRust) -> Vec<Region> {
+  let positions = &mesh.positions;
+  let vertex = |i: usize| [positions[i * 3], positions[i * 3 + 1], positions[i * 3 + 2]];
+  let triangle_count = match &mesh.indices {
+    Some(idxs) => idxs.len() / 3,
+    None => positions.len() / 9,
+  };
+  let corner = |t: usize, k: usize| -> [f32; 3] {
+    match &mesh.indices {
+      Some(idxs) => vertex(idxs[t * 3 + k] as usize),
+      None => vertex(t * 3 + k),
+    }
+  };
+  if triangle_count == 0 {
+    return Vec::new();
+  }
+
+  let mut min = [f32::INFINITY; 2];
+  let mut max = [f32::NEG_INFINITY; 2];
+  for i in 0..positions.len() / 3 {
+    let v = vertex(i);
+    min = [min[0].min(v[0]), min[1].min(v[1])];
+    max = [max[0].max(v[0]), max[1].max(v[1])];
+  }
+  let scale = (max[0] - min[0]).hypot(max[1] - min[1]).max(1e-6);
+  let quantum = scale * 1e-5;
+  let key3 = |v: [f32; 3]| -> (i64, i64, i64) {
+    ((v[0] / quantum).round() as i64, (v[1] / quantum).round() as i64, (v[2] / quantum).round() as i64)
+  };
+  let key2 = |p: Point2| -> (i64, i64) { ((p[0] / quantum).round() as i64, (p[1] / quantum).round() as i64) };
+
+  // Projected, non-degenerate triangles and the edges they share
+  let mut tris: Vec<[Point2; 3]> = Vec::new();
+  // Edge (by quantized 3D endpoints) -> projected endpoints and the side of each adjacent triangle
+  type EdgeKey = ((i64, i64, i64), (i64, i64, i64));
+  let mut edges: HashMap<EdgeKey, (Point2, Point2, Vec<f32>)> = HashMap::new();
+  for t in 0..triangle_count {
+    let c = [corner(t, 0), corner(t, 1), corner(t, 2)];
+    let p: [Point2; 3] = [[c[0][0], c[0][1]], [c[1][0], c[1][1]], [c[2][0], c[2][1]]];
+    if cross(p[0], p[1], p[2]).abs() < quantum * scale {
+      continue;
+    }
+    tris.push(p);
+    for k in 0..3 {
+      let (a, b, o) = (k, (k + 1) % 3, (k + 2) % 3);
+      let (ka, kb) = (key3(c[a]), key3(c[b]));
+      let (key, pa, pb) = if ka < kb { ((ka, kb), p[a], p[b]) } else { ((kb, ka), p[b], p[a]) };
+      // Side of the opposite vertex relative to the canonical edge direction
+      let side = cross(pa, pb, p[o]).signum();
+      edges.entry(key).or_insert_with(|| (pa, pb, Vec::new())).2.push(side);
+    }
+  }
+  let candidates: Vec<(Point2, Point2)> = edges
+    .into_values()
+    .filter(|(_, _, sides)| !(sides.len() == 2 && sides[0] != sides[1]))
+    .map(|(a, b, _)| (a, b))
+    .filter(|(a, b)| key2(*a) != key2(*b))
+    .collect();
+
+  let mut tri_grid = BucketGrid::new(min, max, tris.len());
+  for (i, t) in tris.iter().enumerate() {
+    let lo = [t[0][0].min(t[1][0]).min(t[2][0]), t[0][1].min(t[1][1]).min(t[2][1])];
+    let hi = [t[0][0].max(t[1][0]).max(t[2][0]), t[0][1].max(t[1][1]).max(t[2][1])];
+    tri_grid.insert(i, lo, hi);
+  }
+  let covered = |p: Point2| tri_grid.at(p).iter().any(|&i| point_in_triangle(p, &tris[i]));
+
+  // Split candidate edges where they cross or touch each other
+  let mut seg_grid = BucketGrid::new(min, max, candidates.len());
+  for (i, (a, b)) in candidates.iter().enumerate() {
+    seg_grid.insert(i, [a[0].min(b[0]), a[1].min(b[1])], [a[0].max(b[0]), a[1].max(b[1])]);
+  }
+  let mut splits: Vec<Vec<f32>> = vec![vec![0.0, 1.0]; candidates.len()];
+  for bucket in &seg_grid.buckets {
+    for (k, &i) in bucket.iter().enumerate() {
+      for &j in &bucket[k + 1..] {
+        let (a, b) = candidates[i];
+        let (c, d) = candidates[j];
+        if let Some((t, u)) = segment_intersection(a, b, c, d) {
+          splits[i].push(t);
+          splits[j].push(u);
+        } else {
+          // Collinear overlaps: split each at the other's endpoints
+          for (s, (p, q), (e0, e1)) in [(i, (a, b), (c, d)), (j, (c, d), (a, b))] {
+            for e in [e0, e1] {
+              let len2 = (q[0] - p[0]).powi(2) + (q[1] - p[1]).powi(2);
+              let t = ((e[0] - p[0]) * (q[0] - p[0]) + (e[1] - p[1]) * (q[1] - p[1])) / len2;
+              if t > 0.0 && t < 1.0 && cross(p, q, e).abs() / len2.sqrt() < quantum * 10.0 {
+                splits[s].push(t);
+              }
+            }
+          }
+        }
+      }
+    }
+  }
+
+  // Keep pieces with exactly one covered side, oriented with the covered side on the left
+  let eps = scale * 1e-5;
+  let mut next: HashMap<(i64, i64), Vec<Point2>> = HashMap::new();
+  let mut seen = std::collections::HashSet::new();
+  let mut starts: Vec<Point2> = Vec::new();
+  for (i, (a, b)) in candidates.iter().enumerate() {
+    let ts = &mut splits[i];
+    ts.sort_by(|x, y| x.partial_cmp(y).unwrap());
+    for w in ts.windows(2) {
+      let p = [a[0] + (b[0] - a[0]) * w[0], a[1] + (b[1] - a[1]) * w[0]];
+      let q = [a[0] + (b[0] - a[0]) * w[1], a[1] + (b[1] - a[1]) * w[1]];
+      let (kp, kq) = (key2(p), key2(q));
+      if kp == kq || !seen.insert(if kp < kq { (kp, kq) } else { (kq, kp) }) {
+        continue;
+      }
+      let len = (q[0] - p[0]).hypot(q[1] - p[1]);
+      let n = [-(q[1] - p[1]) / len * eps, (q[0] - p[0]) / len * eps];
+      let m = [(p[0] + q[0]) / 2.0, (p[1] + q[1]) / 2.0];
+      let left = covered([m[0] + n[0], m[1] + n[1]]);
+      let right = covered([m[0] - n[0], m[1] - n[1]]);
+      let (from, to) = match (left, right) {
+        (true, false) => (p, q),
+        (false, true) => (q, p),
+        _ => continue,
+      };
+      next.entry(key2(from)).or_default().push(to);
+      starts.push(from);
+    }
+  }
+
+  // Chain boundary pieces into loops
+  let mut loops = Vec::new();
+  for start in starts {
+    let mut points = Vec::new();
+    let mut p = start;
+    while let Some(to) = next.get_mut(&key2(p)).and_then(|v| v.pop()) {
+      points.push(p);
+      p = to;
+      if key2(p) == key2(start) {
+        break;
+      }
+    }
+    if points.len() > 2 {
+      loops.push(simplify_loop(&points, quantum * 10.0));
+    }
+  }
+  classify_loops(loops)
+}
+
+/// Outline of the cells whose height is above min_z.
+/// Cells at -inf (no geometry, as heightmap_from_mesh_rust leaves them) are always outside.
+pub fn heightmap_outline(heightmap: &Heightmap, min_z: f32) -> Vec<Region> {
+  // Binary occupancy, so the outline runs halfway between filled and empty samples
+  let occupancy: Vec<f32> = heightmap.heights.iter().map(|&z| if z.is_finite() && z > min_z { 1.0 } else { 0.0 }).collect();
+  let loops = marching_squares(
+    &occupancy,
+    heightmap.nx,
+    heightmap.ny,
+    [heightmap.origin_x, heightmap.origin_y],
+    heightmap.grid_size_x,
+    heightmap.grid_size_y,
+    0.5,
+  );
+  let tolerance = heightmap.grid_size_x.min(heightmap.grid_size_y) * 0.01;
+  classify_loops(loops.iter().map(|l| simplify_loop(l, tolerance)).collect())
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Float32Array, Object, Uint32Array};
+use crate::geometry_2d::regions_to_js;
+
+#[wasm_bindgen]
+pub fn mesh_silhouette_wasm(positions: &Float32Array, indices: Option<Uint32Array>) -> Object {
+  let mesh = MeshRust {
+    positions: positions.to_vec(),
+    indices: indices.map(|arr| arr.to_vec()),
+  };
+  regions_to_js(&mesh_silhouette(&mesh))
+}
+
+#[wasm_bindgen]
+pub fn heightmap_outline_wasm(
+  heightmap_js: &Float32Array,
+  nx: usize,
+  ny: usize,
+  grid_size_x: f32,
+  grid_size_y: f32,
+  origin_x: f32,
+  origin_y: f32,
+  min_z: f32,
+) -> Object {
+  let heightmap = Heightmap::from_vec(nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, heightmap_js.to_vec());
+  regions_to_js(&heightmap_outline(&heightmap, min_z))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::geometry_2d::signed_area;
+
+  // Closed box mesh from (x0, y0, 0) to (x1, y1, h)
+  fn box_mesh(x0: f32, y0: f32, x1: f32, y1: f32, h: f32) -> MeshRust {
+    let mut positions = Vec::new();
+    for &(x, y, z) in &[(x0, y0, 0.0), (x1, y0, 0.0), (x1, y1, 0.0), (x0, y1, 0.0), (x0, y0, h), (x1, y0, h), (x1, y1, h), (x0, y1, h)] {
+      positions.extend_from_slice(&[x, y, z]);
+    }
+    let indices = vec![
+      0, 2, 1, 0, 3, 2, // bottom
+      4, 5, 6, 4, 6, 7, // top
+      0, 1, 5, 0, 5, 4, 1, 2, 6, 1, 6, 5, 2, 3, 7, 2, 7, 6, 3, 0, 4, 3, 4, 7, // sides
+    ];
+    MeshRust { positions, indices: Some(indices) }
+  }
+
+  #[test]
+  fn box_silhouette_is_its_footprint() {
+    let regions = mesh_silhouette(&box_mesh(1.0, 2.0, 11.0, 7.0, 3.0));
+    assert_eq!(regions.len(), 1);
+    assert!(regions[0].holes.is_empty());
+    assert_eq!(regions[0].outer.len(), 4);
+    assert!((signed_area(&regions[0].outer) - 50.0).abs() < 1e-3);
+  }
+
+  #[test]
+  fn overlapping_triangles_union_with_hole() {
+    // Four triangles-as-quads forming a square frame around (5, 5), overlapping at the corners
+    let mut positions = Vec::new();
+    let mut quad = |x0: f32, y0: f32, x1: f32, y1: f32| {
+      for &(x, y) in &[(x0, y0), (x1, y0), (x1, y1), (x0, y0), (x1, y1), (x0, y1)] {
+        positions.extend_from_slice(&[x, y, 1.0]);
+      }
+    };
+    quad(0.0, 0.0, 10.0, 3.0);
+    quad(0.0, 7.0, 10.0, 10.0);
+    quad(0.0, 0.0, 3.0, 10.0);
+    quad(7.0, 0.0, 10.0, 10.0);
+    let regions = mesh_silhouette(&MeshRust { positions, indices: None });
+    assert_eq!(regions.len(), 1);
+    assert_eq!(regions[0].holes.len(), 1);
+    assert!((signed_area(&regions[0].outer) - 100.0).abs() < 1e-3);
+    assert!((signed_area(&regions[0].holes[0]) + 16.0).abs() < 1e-3);
+  }
+
+  #[test]
+  fn heightmap_outline_ignores_empty_cells() {
+    let n = 10;
+    let mut heightmap = Heightmap::new(n, n, 1.0, 1.0, 0.0, 0.0, f32::NEG_INFINITY);
+    for ix in 2..6 {
+      for iy in 3..8 {
+        heightmap.set(ix, iy, 2.0);
+      }
+    }
+    let regions = heightmap_outline(&heightmap, f32::NEG_INFINITY);
+    assert_eq!(regions.len(), 1);
+    // 4x5 samples, outline halfway to the empty neighbours with the corners cut
+    assert!((signed_area(&regions[0].outer) - (20.0 - 0.5)).abs() < 1e-3);
+  }
+
+  #[test]
+  fn terrain_mesh_silhouette_is_its_border() {
+    // 20x20 quads of a bumpy open surface over [0, 20] x [0, 10]
+    let n = 20;
+    let mut positions = Vec::new();
+    for i in 0..=n {
+      for j in 0..=n {
+        let x = i as f32;
+        let y = j as f32 * 0.5;
+        positions.extend_from_slice(&[x, y, (x * 0.7).sin() + (y * 1.3).cos()]);
+      }
+    }
+    let mut indices = Vec::new();
+    for i in 0..n as u32 {
+      for j in 0..n as u32 {
+        let a = i * (n as u32 + 1) + j;
+        let b = a + n as u32 + 1;
+        indices.extend_from_slice(&[a, b, b + 1, a, b + 1, a + 1]);
+      }
+    }
+    let regions = mesh_silhouette(&MeshRust { positions, indices: Some(indices) });
+    assert_eq!(regions.len(), 1);
+    assert!(regions[0].holes.is_empty());
+    assert_eq!(regions[0].outer.len(), 4);
+    assert!((signed_area(&regions[0].outer) - 200.0).abs() < 1e-3);
+  }
+}
@@ -0,0 +1,269 @@
+// Error-bounded simplified mesh of the heightmap stock
+// All units are mm unless otherwise noted
+//
+// heightmap_to_solid_mesh spends two triangles on every cell, though most of a stock is flat
+// or planar. Here the cells are split as a quadtree: a block becomes one leaf when every node
+// in it lies within tolerance / 2 of the plane through its corners, otherwise it is split in
+// four, down to single cells. A merged leaf is fanned from its center (on that plane) through
+// every leaf corner along its edges, so neighbors of different sizes meet without T-junction
+// cracks. Every drawn vertex is within tolerance / 2 of the leaf's plane, hence every node is
+// within tolerance of the drawn surface.
+//
+// Assumptions:
+// - Single cells are drawn exactly, split like heightmap_to_solid_mesh
+// - The bottom is one quad at min_z; the side walls run between the top's boundary vertices
+//   and min_z, with vertices of their own for sharp edges
+// - Heights are finite
+
+use crate::heightmap::{Heightmap, NodeRect};
+use crate::heightmap_to_solid_mesh::{vertex_normals, Mesh};
+
+// Leaf of the quadtree: cells ix0..ix1 by iy0..iy1, i.e. nodes ix0..=ix1 by iy0..=iy1
+fn is_planar(stock: &Heightmap, cells: &NodeRect, tolerance: f32) -> bool {
+  let (ix0, iy0, ix1, iy1) = (cells.ix0, cells.iy0, cells.ix1, cells.iy1);
+  let (z00, z10, z01, z11) = (stock.get(ix0, iy0), stock.get(ix1, iy0), stock.get(ix0, iy1), stock.get(ix1, iy1));
+  // Plane through the middle of the corners with their mean slopes
+  let center = (z00 + z10 + z01 + z11) / 4.0;
+  let du = (z10 - z00 + z11 - z01) / 2.0;
+  let dv = (z01 - z00 + z11 - z10) / 2.0;
+  let (w, h) = ((ix1 - ix0) as f32, (iy1 - iy0) as f32);
+  for ix in ix0..=ix1 {
+    for iy in iy0..=iy1 {
+      let plane = center + du * ((ix - ix0) as f32 / w - 0.5) + dv * ((iy - iy0) as f32 / h - 0.5);
+      if (stock.get(ix, iy) - plane).abs() > tolerance / 2.0 {
+        return false;
+      }
+    }
+  }
+  true
+}
+
+fn split(stock: &Heightmap, cells: NodeRect, tolerance: f32, leaves: &mut Vec<NodeRect>) {
+  if cells.area() == 1 || is_planar(stock, &cells, tolerance) {
+    leaves.push(cells);
+    return;
+  }
+  let mx = (cells.ix0 + cells.ix1).div_ceil(2);
+  let my = (cells.iy0 + cells.iy1).div_ceil(2);
+  for (ix0, ix1) in [(cells.ix0, mx), (mx, cells.ix1)] {
+    for (iy0, iy1) in [(cells.iy0, my), (my, cells.iy1)] {
+      if ix1 > ix0 && iy1 > iy0 {
+        split(stock, NodeRect { ix0, iy0, ix1, iy1 }, tolerance, leaves);
+      }
+    }
+  }
+}
+
+/// Solid mesh of the stock with flat and planar regions merged, top within tolerance of every node
+pub fn heightmap_to_simplified_mesh(stock: &Heightmap, min_z: f32, tolerance: f32) -> Mesh {
+  let (nx, ny) = (stock.nx, stock.ny);
+  let mut mesh = Mesh { positions: Vec::new(), indices: Vec::new(), normals: Vec::new() };
+  if nx < 2 || ny < 2 {
+    return mesh;
+  }
+  let mut leaves = Vec::new();
+  split(stock, NodeRect { ix0: 0, iy0: 0, ix1: nx - 1, iy1: ny - 1 }, tolerance.max(0.0), &mut leaves);
+
+  // Top vertices: the corners of every leaf
+  let mut vertex = vec![u32::MAX; nx * ny];
+  let mut positions = Vec::new();
+  let add = |positions: &mut Vec<f32>, p: [f32; 3]| {
+    positions.extend_from_slice(&p);
+    (positions.len() / 3 - 1) as u32
+  };
+  for leaf in &leaves {
+    for (ix, iy) in [(leaf.ix0, leaf.iy0), (leaf.ix1, leaf.iy0), (leaf.ix0, leaf.iy1), (leaf.ix1, leaf.iy1)] {
+      let k = stock.index(ix, iy);
+      if vertex[k] == u32::MAX {
+        vertex[k] = add(&mut positions, [stock.x(ix), stock.y(iy), stock.get(ix, iy)]);
+      }
+    }
+  }
+
+  let mut indices = Vec::new();
+  for leaf in &leaves {
+    let corner = |ix: usize, iy: usize| vertex[stock.index(ix, iy)];
+    if leaf.area() == 1 {
+      let (a, b) = (corner(leaf.ix0, leaf.iy0), corner(leaf.ix1, leaf.iy0));
+      let (c, d) = (corner(leaf.ix1, leaf.iy1), corner(leaf.ix0, leaf.iy1));
+      indices.extend_from_slice(&[a, b, d, b, c, d]);
+      continue;
+    }
+    // Boundary counter-clockwise from (ix0, iy0), keeping every vertex a neighbor put there
+    let mut ring = Vec::new();
+    ring.extend((leaf.ix0..leaf.ix1).map(|ix| (ix, leaf.iy0)));
+    ring.extend((leaf.iy0..leaf.iy1).map(|iy| (leaf.ix1, iy)));
+    ring.extend((leaf.ix0 + 1..=leaf.ix1).rev().map(|ix| (ix, leaf.iy1)));
+    ring.extend((leaf.iy0 + 1..=leaf.iy1).rev().map(|iy| (leaf.ix0, iy)));
+    let ring: Vec<u32> = ring.into_iter().map(|(ix, iy)| vertex[stock.index(ix, iy)]).filter(|&v| v != u32::MAX).collect();
+    if ring.len() == 4 {
+      indices.extend_from_slice(&[ring[0], ring[1], ring[3], ring[1], ring[2], ring[3]]);
+      continue;
+    }
+    let center_z = (stock.get(leaf.ix0, leaf.iy0) + stock.get(leaf.ix1, leaf.iy0) + stock.get(leaf.ix0, leaf.iy1) + stock.get(leaf.ix1, leaf.iy1)) / 4.0;
+    let center = add(
+      &mut positions,
+      [(stock.x(leaf.ix0) + stock.x(leaf.ix1)) / 2.0, (stock.y(leaf.iy0) + stock.y(leaf.iy1)) / 2.0, center_z],
+    );
+    for i in 0..ring.len() {
+      indices.extend_from_slice(&[center, ring[i], ring[(i + 1) % ring.len()]]);
+    }
+  }
+  let normals = vertex_normals(&positions, &indices);
+  mesh.positions = positions;
+  mesh.indices = indices;
+  mesh.normals = normals;
+
+  // Walls along each edge, between consecutive top vertices, with their own vertices
+  let edge = |nodes: Vec<(usize, usize)>| -> Vec<(usize, usize)> {
+    nodes.into_iter().filter(|&(ix, iy)| vertex[stock.index(ix, iy)] != u32::MAX).collect()
+  };
+  let sides = [
+    (edge((0..ny).map(|iy| (0, iy)).collect()), true),       // left, x = min, along +y
+    (edge((0..ny).map(|iy| (nx - 1, iy)).collect()), false), // right
+    (edge((0..nx).map(|ix| (ix, 0)).collect()), false),      // front, y = min, along +x
+    (edge((0..nx).map(|ix| (ix, ny - 1)).collect()), true),  // back
+  ];
+  let mut part = Mesh { positions: Vec::new(), indices: Vec::new(), normals: Vec::new() };
+  for (nodes, flip) in &sides {
+    for w in nodes.windows(2) {
+      let base = (part.positions.len() / 3) as u32;
+      for &(ix, iy) in w {
+        part.positions.extend_from_slice(&[stock.x(ix), stock.y(iy), stock.get(ix, iy)]);
+        part.positions.extend_from_slice(&[stock.x(ix), stock.y(iy), min_z]);
+      }
+      let (top_a, bot_a, top_b, bot_b) = (base, base + 1, base + 2, base + 3);
+      if *flip {
+        part.indices.extend_from_slice(&[top_a, top_b, bot_a, bot_a, top_b, bot_b]);
+      } else {
+        part.indices.extend_from_slice(&[top_a, bot_a, top_b, top_b, bot_a, bot_b]);
+      }
+    }
+  }
+  // Bottom: one quad facing down
+  let base = (part.positions.len() / 3) as u32;
+  for (ix, iy) in [(0, 0), (nx - 1, 0), (nx - 1, ny - 1), (0, ny - 1)] {
+    part.positions.extend_from_slice(&[stock.x(ix), stock.y(iy), min_z]);
+  }
+  part.indices.extend_from_slice(&[base, base + 3, base + 1, base + 1, base + 3, base + 2]);
+  part.normals = vertex_normals(&part.positions, &part.indices);
+
+  let offset = (mesh.positions.len() / 3) as u32;
+  mesh.positions.extend_from_slice(&part.positions);
+  mesh.normals.extend_from_slice(&part.normals);
+  mesh.indices.extend(part.indices.iter().map(|i| i + offset));
+  mesh
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Float32Array, Object, Uint32Array};
+
+/// Returns { positions, indices, normals } of the simplified solid mesh
+#[wasm_bindgen]
+pub fn heightmap_to_simplified_mesh_wasm(
+  heights: &Float32Array,
+  nx: usize,
+  ny: usize,
+  grid_size_x: f32,
+  grid_size_y: f32,
+  origin_x: f32,
+  origin_y: f32,
+  min_z: f32,
+  tolerance: f32,
+) -> Object {
+  let stock = Heightmap::from_vec(nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, heights.to_vec());
+  let mesh = heightmap_to_simplified_mesh(&stock, min_z, tolerance);
+  let result = Object::new();
+  js_sys::Reflect::set(&result, &"positions".into(), &Float32Array::from(mesh.positions.as_slice())).unwrap();
+  js_sys::Reflect::set(&result, &"indices".into(), &Uint32Array::from(mesh.indices.as_slice())).unwrap();
+  js_sys::Reflect::set(&result, &"normals".into(), &Float32Array::from(mesh.normals.as_slice())).unwrap();
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::heightmap_to_solid_mesh::heightmap_to_solid_mesh;
+  use crate::simulate_material_removal::{simulate_material_removal, SimulateMaterialRemovalParams};
+  use std::collections::HashMap;
+
+  // Top triangles (facing up, above the bottom) as corner positions
+  fn top_triangles(mesh: &Mesh, min_z: f32) -> Vec<[[f32; 3]; 3]> {
+    mesh
+      .indices
+      .chunks(3)
+      .map(|t| [0, 1, 2].map(|i| {
+        let v = t[i] as usize;
+        [mesh.positions[v * 3], mesh.positions[v * 3 + 1], mesh.positions[v * 3 + 2]]
+      }))
+      .filter(|[a, b, c]| {
+        let up = (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+        up > 1e-9 && [a, b, c].iter().any(|p| p[2] > min_z)
+      })
+      .collect()
+  }
+
+  #[test]
+  fn flat_stock_is_a_box() {
+    let stock = Heightmap::new(301, 81, 1.0, 1.0, 0.0, 0.0, 5.0);
+    let mesh = heightmap_to_simplified_mesh(&stock, 0.0, 0.01);
+    // Two triangles for the top, the bottom and each wall
+    assert_eq!(mesh.indices.len() / 3, 12);
+    assert!(mesh.normals.chunks(3).take(4).all(|n| n[2] > 0.999));
+    assert!(mesh.normals.chunks(3).skip(mesh.normals.len() / 3 - 4).all(|n| n[2] < -0.999));
+  }
+
+  #[test]
+  fn cut_stock_stays_within_tolerance_without_cracks() {
+    let mut stock = Heightmap::new(121, 81, 0.25, 0.25, 0.0, 0.0, 5.0);
+    let toolpath: Vec<f32> = (0..=100).flat_map(|i| [5.0 + i as f32 * 0.2, 10.0 + (i as f32 * 0.1).sin() * 3.0, 3.0]).collect();
+    simulate_material_removal(SimulateMaterialRemovalParams {
+      heightmap: &mut stock,
+      tool_type: "ball",
+      cutter_diameter: 6.0,
+      v_angle_deg: 0.0,
+      toolpath: &toolpath,
+      attributes: None,
+    });
+    let tolerance = 0.02;
+    let mesh = heightmap_to_simplified_mesh(&stock, 0.0, tolerance);
+    let full = heightmap_to_solid_mesh(&stock, 0.0);
+    assert!(mesh.indices.len() * 3 < full.indices.len(), "{} vs {}", mesh.indices.len(), full.indices.len());
+
+    // Every node lies under some top triangle and within tolerance of it
+    let tris = top_triangles(&mesh, 0.0);
+    for ix in 0..stock.nx {
+      for iy in 0..stock.ny {
+        let (x, y, z) = (stock.x(ix), stock.y(iy), stock.get(ix, iy));
+        let drawn = tris.iter().find_map(|[a, b, c]| {
+          let d = (b[1] - c[1]) * (a[0] - c[0]) + (c[0] - b[0]) * (a[1] - c[1]);
+          let l1 = ((b[1] - c[1]) * (x - c[0]) + (c[0] - b[0]) * (y - c[1])) / d;
+          let l2 = ((c[1] - a[1]) * (x - c[0]) + (a[0] - c[0]) * (y - c[1])) / d;
+          let l3 = 1.0 - l1 - l2;
+          (l1 >= -1e-5 && l2 >= -1e-5 && l3 >= -1e-5).then(|| l1 * a[2] + l2 * b[2] + l3 * c[2])
+        });
+        let drawn = drawn.unwrap_or_else(|| panic!("node ({}, {}) not covered", ix, iy));
+        assert!((drawn - z).abs() <= tolerance + 1e-4, "node ({}, {}) at {} drawn at {}", ix, iy, z, drawn);
+      }
+    }
+
+    // No cracks: inside the top, every edge is used once in each direction
+    let key = |p: [f32; 3]| (p[0].to_bits(), p[1].to_bits(), p[2].to_bits());
+    let mut edges: HashMap<_, i32> = HashMap::new();
+    for t in &tris {
+      for i in 0..3 {
+        let (a, b) = (key(t[i]), key(t[(i + 1) % 3]));
+        *edges.entry((a.min(b), a.max(b))).or_default() += if a < b { 1 } else { -1 };
+      }
+    }
+    let on_border = |k: (u32, u32, u32)| {
+      let (x, y) = (f32::from_bits(k.0), f32::from_bits(k.1));
+      x == 0.0 || y == 0.0 || x == stock.x(stock.nx - 1) || y == stock.y(stock.ny - 1)
+    };
+    for ((a, b), count) in edges {
+      if !(on_border(a) && on_border(b)) {
+        assert_eq!(count, 0, "unmatched edge {:?} - {:?}", a, b);
+      }
+    }
+  }
+}
@@ -1,10 +1,11 @@
 // Rust implementation of simulate_material_removal for 2.5D CNC heightmap stock
 // All units are mm unless otherwise noted
 //
-// This function operates on a flat heightmap array (row-major, nx * ny),
+// This function operates on a Heightmap (layout ix * ny + iy, see heightmap.rs),
 // and a toolpath array of (x, y, z) triplets (flat, length = 3 * n_points).
 //
-// The function mutates the heightmap in-place.
+// The function mutates the heightmap in-place and returns the rectangles of nodes it lowered,
+// so a viewer can re-mesh only those (see solid_mesh_patches).
 //
 // Assumptions:
 // - Tool zero position is the center of the tip (x, y, z)
@@ -12,260 +13,130 @@
 // - Flat: cuts a flat-bottomed cylinder at z=pt.z
 // - Ball: cuts a hemisphere, tip at z=pt.z, surface at z=pt.z + sqrt(r^2 - d^2) - r
 // - V-bit: cuts a cone, tip at z=pt.z, surface at z=pt.z + d / tan(v_angle/2)
+// - The tool footprint is laid out on the heightmap's own cells, grid_size_x by grid_size_y,
+//   so a 300 x 80 mm board can use any resolution along either axis
+// - Node (ix, iy) is at origin + (ix * grid_size_x, iy * grid_size_y); the tool center snaps
+//   to the nearest node
+// - The cutter shape is ToolShape::profile; tool types without one cut nothing
+// - generate_safe_toolpath reads the surface through StockModel, so it works on any stock;
+//   it samples the tool footprint around each point at the stock's cell size
+// - With attributes, every node the cut lowers takes the stamp's operation id, tool id and time
+//   in whichever layers exist; nodes left alone keep their previous values
+
+use crate::heightmap::{DirtyRects, Heightmap, NodeAttributes, NodeRect};
+use crate::stock_model::{StockModel, ToolShape};
 
 pub struct SimulateMaterialRemovalParams<'a> {
-  pub heightmap: &'a mut [f32],
-  pub nx: usize,
-  pub ny: usize,
-  pub grid_size: f32,
-  pub origin_x: f32,
-  pub origin_y: f32,
+  pub heightmap: &'a mut Heightmap,
   pub tool_type: &'a str,
   pub cutter_diameter: f32,
   pub v_angle_deg: f32, // only used for vbit
   pub toolpath: &'a [f32], // flat array: x0, y0, z0, x1, y1, z1, ...
+  pub attributes: Option<CutStamp<'a>>, // per-node layers to stamp, if any
+}
+
+/// What a cut records in the attribute layers of the nodes it lowers
+pub struct CutStamp<'a> {
+  pub layers: &'a mut NodeAttributes,
+  pub operation_id: u32,
+  pub tool_id: u32,
+  pub time: f32,
+}
+
+/// Node offsets (dx, dy) under the tool on cells of cell_x by cell_y, and the height of the
+/// tool surface above the tip there
+pub fn tool_footprint(cell_x: f32, cell_y: f32, tool: &ToolShape) -> Vec<(isize, isize, f32)> {
+  let r = tool.cutter_diameter / 2.0;
+  let radius_x = (r / cell_x).ceil() as isize;
+  let radius_y = (r / cell_y).ceil() as isize;
+  let mut footprint = Vec::new();
+  for dx in -radius_x..=radius_x {
+    for dy in -radius_y..=radius_y {
+      let distance = (dx as f32 * cell_x).hypot(dy as f32 * cell_y);
+      if let Some(dz) = tool.profile(distance) {
+        footprint.push((dx, dy, dz));
+      }
+    }
+  }
+  footprint
+}
+
+// Node nearest (x, y), which may be off the grid
+fn tool_center(heightmap: &Heightmap, x: f32, y: f32) -> (isize, isize) {
+  (
+    ((x - heightmap.origin_x) / heightmap.grid_size_x).round() as isize,
+    ((y - heightmap.origin_y) / heightmap.grid_size_y).round() as isize,
+  )
 }
 
-pub fn simulate_material_removal(params: SimulateMaterialRemovalParams) {
+pub fn simulate_material_removal(params: SimulateMaterialRemovalParams) -> Vec<NodeRect> {
   let SimulateMaterialRemovalParams {
     heightmap,
-    nx,
-    ny,
-    grid_size,
-    origin_x,
-    origin_y,
     tool_type,
     cutter_diameter,
     v_angle_deg,
     toolpath,
+    mut attributes,
   } = params;
-  let r = cutter_diameter / 2.0;
-  let step = grid_size;
-  let tool_grid_radius = (r / step).ceil() as isize;
-  let tool_grid_size = tool_grid_radius * 2 + 1;
-  let tan_half_angle = if tool_type == "vbit" {
-    let v_angle_rad = v_angle_deg * std::f32::consts::PI / 180.0;
-    (v_angle_rad / 2.0).tan()
-  } else {
-    0.0
-  };
-
-  // Precompute tool elevation grid for a single tool position at (0,0,0)
-  let mut tool_elevation_grid = vec![vec![None; tool_grid_size as usize]; tool_grid_size as usize];
-  for ix in 0..tool_grid_size {
-    for iy in 0..tool_grid_size {
-      let x = (ix - tool_grid_radius) as f32 * step;
-      let y = (iy - tool_grid_radius) as f32 * step;
-      let distance = (x * x + y * y).sqrt();
-      let dz = if distance > r + 1e-6 {
-        None
-      } else if tool_type == "flat" {
-        Some(0.0)
-      } else if tool_type == "ball" {
-        Some(r - (r * r - distance * distance).max(0.0).sqrt())
-      } else if tool_type == "vbit" {
-        if tan_half_angle > 1e-8 {
-          Some(distance / tan_half_angle)
-        } else {
-          None
-        }
-      } else {
-        None
-      };
-      tool_elevation_grid[ix as usize][iy as usize] = dz;
-    }
-  }
+  let (nx, ny) = (heightmap.nx as isize, heightmap.ny as isize);
+  // Precompute the tool surface for a single tool position at (0,0,0)
+  let tool = ToolShape { tool_type, cutter_diameter, v_angle_deg };
+  let footprint = tool_footprint(heightmap.grid_size_x, heightmap.grid_size_y, &tool);
 
+  let mut dirty = DirtyRects::default();
   for pt in toolpath.chunks(3) {
     assert!(pt.len() == 3, "toolpath chunk is not length 3");
-    let x0 = pt[0];
-    let y0 = pt[1];
+    let (tool_cx, tool_cy) = tool_center(heightmap, pt[0], pt[1]);
     let z0 = pt[2];
-    if tool_type == "flat" && cutter_diameter <= step + 1e-6 {
-      // Only cut the center cell
-      let ix = ((x0 - origin_x) / step).round() as isize;
-      let iy = ((y0 - origin_y) / step).round() as isize;
-      if ix >= 0 && iy >= 0 && (ix as usize) < nx && (iy as usize) < ny {
-        let idx = ix as usize * ny + iy as usize;
-        if heightmap[idx] > z0 {
-          heightmap[idx] = z0;
-        }
+    let mut changed: Option<NodeRect> = None;
+    for &(dx, dy, dz) in &footprint {
+      let ix = tool_cx + dx;
+      let iy = tool_cy + dy;
+      if ix < 0 || iy < 0 || ix >= nx || iy >= ny {
+        continue;
       }
-      continue;
-    }
-    let tool_cx = ((x0 - origin_x) / step).round() as isize;
-    let tool_cy = ((y0 - origin_y) / step).round() as isize;
-    for dx in -tool_grid_radius..=tool_grid_radius {
-      for dy in -tool_grid_radius..=tool_grid_radius {
-        let ix = tool_cx + dx;
-        let iy = tool_cy + dy;
-        if ix < 0 || iy < 0 || (ix as usize) >= nx || (iy as usize) >= ny {
-          continue;
-        }
-        let grid_ix = (dx + tool_grid_radius) as usize;
-        let grid_iy = (dy + tool_grid_radius) as usize;
-        assert!(grid_ix < tool_elevation_grid.len(), "grid_ix {} out of bounds {}", grid_ix, tool_elevation_grid.len());
-        assert!(grid_iy < tool_elevation_grid[grid_ix].len(), "grid_iy {} out of bounds {}", grid_iy, tool_elevation_grid[grid_ix].len());
-        if let Some(dz) = tool_elevation_grid[grid_ix][grid_iy] {
-          let z = z0 + dz;
-          let idx = ix as usize * ny + iy as usize;
-          // Defensive: check idx is in bounds
-          assert!(idx < heightmap.len(), "heightmap idx {} out of bounds {}", idx, heightmap.len());
-          if heightmap[idx] > z {
-            heightmap[idx] = z;
+      let z = z0 + dz;
+      if heightmap.get(ix as usize, iy as usize) > z {
+        heightmap.set(ix as usize, iy as usize, z);
+        if let Some(stamp) = attributes.as_mut() {
+          let k = heightmap.index(ix as usize, iy as usize);
+          if let Some(layer) = stamp.layers.operation_id.as_mut() {
+            layer[k] = stamp.operation_id;
+          }
+          if let Some(layer) = stamp.layers.tool_id.as_mut() {
+            layer[k] = stamp.tool_id;
+          }
+          if let Some(layer) = stamp.layers.cut_time.as_mut() {
+            layer[k] = stamp.time;
           }
         }
+        let node = NodeRect { ix0: ix as usize, iy0: iy as usize, ix1: ix as usize + 1, iy1: iy as usize + 1 };
+        changed = Some(changed.map_or(node, |c| c.union(&node)));
       }
     }
+    if let Some(rect) = changed {
+      dirty.push(rect);
+    }
   }
+  dirty.rects
 }
 
-// Unit test for simulate_material_removal
-#[cfg(test)]
-mod tests {
-  use super::*;
-  use std::time::Instant;
-
-  #[test]
-  fn test_flat_endmill_removes_material() {
-    let nx = 5;
-    let ny = 5;
-    let mut heightmap = vec![5.0; nx * ny];
-    let toolpath = vec![2.0, 2.0, 2.0]; // single point
-    simulate_material_removal(SimulateMaterialRemovalParams {
-      heightmap: &mut heightmap, // remove mut
-      nx,
-      ny,
-      grid_size: 1.0,
-      origin_x: 0.0,
-      origin_y: 0.0,
-      tool_type: "flat",
-      cutter_diameter: 1.0,
-      v_angle_deg: 0.0,
-      toolpath: &toolpath,
-    });
-    assert!((heightmap[2 * ny + 2] - 2.0).abs() < 1e-6);
-    assert!((heightmap[0] - 5.0).abs() < 1e-6);
-  }
-
-  #[test]
-  fn bench_rust_simulate_material_removal() {
-    let nx = 500;
-    let ny = 500;
-    let mut heightmap = vec![50.0; nx * ny];
-    let toolpath = vec![250.0, 250.0, 10.0]; // single point in center
-    let start = Instant::now();
-    simulate_material_removal(SimulateMaterialRemovalParams {
-      heightmap: &mut heightmap, // remove mut
-      nx,
-      ny,
-      grid_size: 1.0,
-      origin_x: 0.0,
-      origin_y: 0.0,
-      tool_type: "flat",
-      cutter_diameter: 20.0,
-      v_angle_deg: 0.0,
-      toolpath: &toolpath,
-    });
-    let elapsed = start.elapsed();
-    println!("Rust simulate_material_removal 500x500, 20mm tool: {:?}", elapsed);
-  }
-
-
-  #[test]
-  fn test_generate_safe_toolpath_ball_over_bump() {
-    // Ball tool, diameter 2, over a bump at center
-    let nx = 5;
-    let ny = 5;
-    let mut heightmap = vec![1.0; nx * ny];
-    heightmap[2 * ny + 2] = 5.0; // bump at center
-    let grid_size = 1.0;
-    let origin_x = 0.0;
-    let origin_y = 0.0;
-    let tool_type = "ball";
-    let cutter_diameter = 2.0;
-    let v_angle_deg = 0.0;
-    // Toolpath: just one point at the bump
-    let toolpath_xy = vec![(2.0, 2.0)];
-    // Should return a Z such that the ball just touches the bump, not lower
-    let safe_zs = generate_safe_toolpath(
-      &heightmap,
-      nx,
-      ny,
-      grid_size,
-      origin_x,
-      origin_y,
-      tool_type,
-      cutter_diameter,
-      v_angle_deg,
-      &toolpath_xy,
-    );
-    // The safe Z should be >= 5.0 - r (ball tip just touches bump)
-    let r = cutter_diameter / 2.0;
-    assert!(safe_zs.len() == 1);
-    assert!(safe_zs[0] >= 5.0 - r - 1e-6, "Safe Z is too low: {}", safe_zs[0]);
-  }
-}
-
-// Compute the highest Z for each (x, y) so the tool never dips below the heightmap
-pub fn generate_safe_toolpath(
-  heightmap: &[f32],
-  nx: usize,
-  ny: usize,
-  grid_size: f32,
-  origin_x: f32,
-  origin_y: f32,
+// Compute the highest Z for each (x, y) so the tool never dips below the surface
+pub fn generate_safe_toolpath<S: StockModel + ?Sized>(
+  surface: &S,
   tool_type: &str,
   cutter_diameter: f32,
   v_angle_deg: f32,
   toolpath_xy: &[(f32, f32)],
 ) -> Vec<f32> {
-  let r = cutter_diameter / 2.0;
-  let step = grid_size;
-  let tool_grid_radius = (r / step).ceil() as isize;
-  let tool_grid_size = tool_grid_radius * 2 + 1;
-  let tan_half_angle = if tool_type == "vbit" {
-    let v_angle_rad = v_angle_deg * std::f32::consts::PI / 180.0;
-    (v_angle_rad / 2.0).tan()
-  } else {
-    0.0
-  };
+  let (cell_x, cell_y) = surface.cell_size();
+  let footprint = tool_footprint(cell_x, cell_y, &ToolShape { tool_type, cutter_diameter, v_angle_deg });
   let mut safe_zs = Vec::with_capacity(toolpath_xy.len());
   for &(x0, y0) in toolpath_xy.iter() {
-    let tool_cx = ((x0 - origin_x) / step).round() as isize;
-    let tool_cy = ((y0 - origin_y) / step).round() as isize;
-    let mut max_required_z = std::f32::NEG_INFINITY;
-    for dx in -tool_grid_radius..=tool_grid_radius {
-      for dy in -tool_grid_radius..=tool_grid_radius {
-        let ix = tool_cx + dx;
-        let iy = tool_cy + dy;
-        if ix < 0 || iy < 0 || (ix as usize) >= nx || (iy as usize) >= ny {
-          continue;
-        }
-        let x = (dx as f32) * step;
-        let y = (dy as f32) * step;
-        let distance = (x * x + y * y).sqrt();
-        if distance > r + 1e-6 {
-          continue;
-        }
-        let dz = if tool_type == "flat" {
-          0.0
-        } else if tool_type == "ball" {
-          r - (r * r - distance * distance).max(0.0).sqrt()
-        } else if tool_type == "vbit" {
-          if tan_half_angle > 1e-8 {
-            distance / tan_half_angle
-          } else {
-            continue;
-          }
-        } else {
-          continue;
-        };
-        let idx = ix as usize * ny + iy as usize;
-        let required_z = heightmap[idx] - dz;
-        if required_z > max_required_z {
-          max_required_z = required_z;
-        }
+    let mut max_required_z = f32::NEG_INFINITY;
+    for &(dx, dy, dz) in &footprint {
+      if let Some(h) = surface.height_at(x0 + dx as f32 * cell_x, y0 + dy as f32 * cell_y) {
+        max_required_z = max_required_z.max(h - dz);
       }
     }
     safe_zs.push(max_required_z);
@@ -274,62 +145,92 @@ pub fn generate_safe_toolpath(
 }
 
 use wasm_bindgen::prelude::*;
-use js_sys::Float32Array;
-use web_sys;
+use js_sys::{Float32Array, Uint32Array};
 
 #[wasm_bindgen(start)]
 pub fn main() {
   console_error_panic_hook::set_once();
 }
 
+/// Cuts the heightmap in place; returns the lowered node rects as flat ix0, iy0, ix1, iy1 (end exclusive).
+/// Attribute layers passed in (heightmap layout) are stamped in place with operation_id, tool_id and time.
 #[wasm_bindgen]
 pub fn simulate_material_removal_wasm(
   heightmap_js: &Float32Array,
   nx: usize,
   ny: usize,
-  grid_size: f32,
+  grid_size_x: f32,
+  grid_size_y: f32,
   origin_x: f32,
   origin_y: f32,
   tool_type: &str,
   cutter_diameter: f32,
   v_angle_deg: f32,
   toolpath_js: &Float32Array,
-) {
+  operation_id: u32,
+  tool_id: u32,
+  time: f32,
+  operation_id_js: Option<Uint32Array>,
+  tool_id_js: Option<Uint32Array>,
+  cut_time_js: Option<Float32Array>,
+) -> Uint32Array {
   // Defensive: log all input parameters
   web_sys::console::log_1(&format!(
-    "simulate_material_removal_wasm: heightmap_js.len={}, nx={}, ny={}, grid_size={}, origin_x={}, origin_y={}, tool_type={}, cutter_diameter={}, v_angle_deg={}, toolpath_js.len={}",
-    heightmap_js.length(), nx, ny, grid_size, origin_x, origin_y, tool_type, cutter_diameter, v_angle_deg, toolpath_js.length()
+    "simulate_material_removal_wasm: heightmap_js.len={}, nx={}, ny={}, grid_size_x={}, grid_size_y={}, origin_x={}, origin_y={}, tool_type={}, cutter_diameter={}, v_angle_deg={}, toolpath_js.len={}",
+    heightmap_js.length(), nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, tool_type, cutter_diameter, v_angle_deg, toolpath_js.length()
   ).into());
 
   let expected_len = nx * ny;
   if heightmap_js.length() as usize != expected_len {
     panic!("heightmap length {} does not match nx*ny {}", heightmap_js.length(), expected_len);
   }
-  if toolpath_js.length() % 3 != 0 {
+  if !toolpath_js.length().is_multiple_of(3) {
     panic!("toolpath length {} is not a multiple of 3", toolpath_js.length());
   }
+  let layer_lengths = [
+    operation_id_js.as_ref().map(|a| a.length()),
+    tool_id_js.as_ref().map(|a| a.length()),
+    cut_time_js.as_ref().map(|a| a.length()),
+  ];
+  for len in layer_lengths.into_iter().flatten() {
+    if len as usize != expected_len {
+      panic!("attribute layer length {} does not match nx*ny {}", len, expected_len);
+    }
+  }
   // Copy JS arrays into Rust Vecs
-  let mut heightmap: Vec<f32> = heightmap_js.to_vec();
+  let mut heightmap = Heightmap::from_vec(nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, heightmap_js.to_vec());
   let toolpath: Vec<f32> = toolpath_js.to_vec();
-  simulate_material_removal(SimulateMaterialRemovalParams {
+  let mut layers = NodeAttributes {
+    operation_id: operation_id_js.as_ref().map(|a| a.to_vec()),
+    tool_id: tool_id_js.as_ref().map(|a| a.to_vec()),
+    cut_time: cut_time_js.as_ref().map(|a| a.to_vec()),
+  };
+  let dirty = simulate_material_removal(SimulateMaterialRemovalParams {
     heightmap: &mut heightmap,
-    nx,
-    ny,
-    grid_size,
-    origin_x,
-    origin_y,
     tool_type,
     cutter_diameter,
     v_angle_deg,
     toolpath: &toolpath,
+    attributes: Some(CutStamp { layers: &mut layers, operation_id, tool_id, time }),
   });
   // Write back to JS array
-  for (i, v) in heightmap.iter().enumerate() {
+  for (i, v) in heightmap.heights.iter().enumerate() {
     // Defensive: check bounds
     if i < heightmap_js.length() as usize {
       heightmap_js.set_index(i as u32, *v);
     }
   }
+  if let (Some(js), Some(layer)) = (&operation_id_js, &layers.operation_id) {
+    js.copy_from(layer);
+  }
+  if let (Some(js), Some(layer)) = (&tool_id_js, &layers.tool_id) {
+    js.copy_from(layer);
+  }
+  if let (Some(js), Some(layer)) = (&cut_time_js, &layers.cut_time) {
+    js.copy_from(layer);
+  }
+  let flat: Vec<u32> = dirty.iter().flat_map(|r| [r.ix0, r.iy0, r.ix1, r.iy1].map(|i| i as u32)).collect();
+  Uint32Array::from(flat.as_slice())
 }
 
 #[wasm_bindgen]
@@ -346,20 +247,14 @@ pub fn generate_safe_toolpath_wasm(
   v_angle_deg: f32,
   toolpath_xy_js: &Float32Array, // flat array: x0, y0, x1, y1, ...
 ) -> Float32Array {
-  let heightmap: Vec<f32> = heightmap_js.to_vec();
+  let heightmap = Heightmap::from_vec(nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, heightmap_js.to_vec());
   let toolpath_xy_vec: Vec<f32> = toolpath_xy_js.to_vec();
   let mut toolpath_xy = Vec::with_capacity(toolpath_xy_vec.len() / 2);
   for i in (0..toolpath_xy_vec.len()).step_by(2) {
     toolpath_xy.push((toolpath_xy_vec[i], toolpath_xy_vec[i + 1]));
   }
-  let safe_zs = generate_safe_toolpath_grid(
+  let safe_zs = generate_safe_toolpath(
     &heightmap,
-    nx,
-    ny,
-    grid_size_x,
-    grid_size_y,
-    origin_x,
-    origin_y,
     tool_type,
     cutter_diameter,
     v_angle_deg,
@@ -368,68 +263,116 @@ pub fn generate_safe_toolpath_wasm(
   Float32Array::from(safe_zs.as_slice())
 }
 
-// New: grid_size_x/y version for non-square grids
-pub fn generate_safe_toolpath_grid(
-  heightmap: &[f32],
-  nx: usize,
-  ny: usize,
-  grid_size_x: f32,
-  grid_size_y: f32,
-  origin_x: f32,
-  origin_y: f32,
-  tool_type: &str,
-  cutter_diameter: f32,
-  v_angle_deg: f32,
-  toolpath_xy: &[(f32, f32)],
-) -> Vec<f32> {
-  let r = cutter_diameter / 2.0;
-  let tool_grid_radius_x = (r / grid_size_x).ceil() as isize;
-  let tool_grid_radius_y = (r / grid_size_y).ceil() as isize;
-  let tan_half_angle = if tool_type == "vbit" {
-    let v_angle_rad = v_angle_deg * std::f32::consts::PI / 180.0;
-    (v_angle_rad / 2.0).tan()
-  } else {
-    0.0
-  };
-  let mut safe_zs = Vec::with_capacity(toolpath_xy.len());
-  for &(x0, y0) in toolpath_xy.iter() {
-    let tool_cx = ((x0 - origin_x) / grid_size_x).round() as isize;
-    let tool_cy = ((y0 - origin_y) / grid_size_y).round() as isize;
-    let mut max_required_z = std::f32::NEG_INFINITY;
-    for dx in -tool_grid_radius_x..=tool_grid_radius_x {
-      for dy in -tool_grid_radius_y..=tool_grid_radius_y {
-        let ix = tool_cx + dx;
-        let iy = tool_cy + dy;
-        if ix < 0 || iy < 0 || (ix as usize) >= nx || (iy as usize) >= ny {
-          continue;
-        }
-        let x = (dx as f32) * grid_size_x;
-        let y = (dy as f32) * grid_size_y;
-        let distance = (x * x + y * y).sqrt();
-        if distance > r + 1e-6 {
-          continue;
-        }
-        let dz = if tool_type == "flat" {
-          0.0
-        } else if tool_type == "ball" {
-          r - (r * r - distance * distance).max(0.0).sqrt()
-        } else if tool_type == "vbit" {
-          if tan_half_angle > 1e-8 {
-            distance / tan_half_angle
-          } else {
-            continue;
-          }
-        } else {
-          continue;
-        };
-        let idx = ix as usize * ny + iy as usize;
-        let required_z = heightmap[idx] - dz;
-        if required_z > max_required_z {
-          max_required_z = required_z;
-        }
-      }
+// Unit test for simulate_material_removal
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::time::Instant;
+
+  #[test]
+  fn test_flat_endmill_removes_material() {
+    let mut heightmap = Heightmap::new(5, 5, 1.0, 1.0, 0.0, 0.0, 5.0);
+    let toolpath = vec![2.0, 2.0, 2.0]; // single point
+    simulate_material_removal(SimulateMaterialRemovalParams {
+      heightmap: &mut heightmap,
+      tool_type: "flat",
+      cutter_diameter: 1.0,
+      v_angle_deg: 0.0,
+      toolpath: &toolpath,
+      attributes: None,
+    });
+    assert!((heightmap.get(2, 2) - 2.0).abs() < 1e-6);
+    assert!((heightmap.get(0, 0) - 5.0).abs() < 1e-6);
+  }
+
+  #[test]
+  fn non_square_cells_cut_a_round_hole() {
+    // 300 x 80 mm board: 1 mm cells along x, 0.25 mm along y
+    let mut board = Heightmap::new(301, 321, 1.0, 0.25, 0.0, 0.0, 0.0);
+    simulate_material_removal(SimulateMaterialRemovalParams {
+      heightmap: &mut board,
+      tool_type: "flat",
+      cutter_diameter: 6.0,
+      v_angle_deg: 0.0,
+      toolpath: &[150.0, 40.0, -2.0],
+      attributes: None,
+    });
+    // Cut out to 3 mm in every direction and no farther
+    for (x, y) in [(153.0, 40.0), (147.0, 40.0), (150.0, 43.0), (150.0, 37.0), (152.0, 42.0)] {
+      assert_eq!(board.height_at(x, y), Some(-2.0), "at ({}, {})", x, y);
     }
-    safe_zs.push(max_required_z);
+    for (x, y) in [(154.0, 40.0), (150.0, 43.25), (150.0, 36.75), (153.0, 42.0)] {
+      assert_eq!(board.height_at(x, y), Some(0.0), "at ({}, {})", x, y);
+    }
+    // The safe z over the hole agrees with what was cut
+    let zs = generate_safe_toolpath(&board, "flat", 6.0, 0.0, &[(150.0, 40.0), (150.0, 41.0)]);
+    assert_eq!(zs, vec![-2.0, 0.0]);
+  }
+
+  #[test]
+  fn bench_rust_simulate_material_removal() {
+    let mut heightmap = Heightmap::new(500, 500, 1.0, 1.0, 0.0, 0.0, 50.0);
+    let toolpath = vec![250.0, 250.0, 10.0]; // single point in center
+    let start = Instant::now();
+    simulate_material_removal(SimulateMaterialRemovalParams {
+      heightmap: &mut heightmap,
+      tool_type: "flat",
+      cutter_diameter: 20.0,
+      v_angle_deg: 0.0,
+      toolpath: &toolpath,
+      attributes: None,
+    });
+    let elapsed = start.elapsed();
+    println!("Rust simulate_material_removal 500x500, 20mm tool: {:?}", elapsed);
+  }
+
+
+  #[test]
+  fn test_generate_safe_toolpath_ball_over_bump() {
+    // Ball tool, diameter 2, over a bump at center
+    let mut heightmap = Heightmap::new(5, 5, 1.0, 1.0, 0.0, 0.0, 1.0);
+    heightmap.set(2, 2, 5.0); // bump at center
+    let tool_type = "ball";
+    let cutter_diameter = 2.0;
+    let v_angle_deg = 0.0;
+    // Toolpath: just one point at the bump
+    let toolpath_xy = vec![(2.0, 2.0)];
+    // Should return a Z such that the ball just touches the bump, not lower
+    let safe_zs = generate_safe_toolpath(
+      &heightmap,
+      tool_type,
+      cutter_diameter,
+      v_angle_deg,
+      &toolpath_xy,
+    );
+    // The safe Z should be >= 5.0 - r (ball tip just touches bump)
+    let r = cutter_diameter / 2.0;
+    assert!(safe_zs.len() == 1);
+    assert!(safe_zs[0] >= 5.0 - r - 1e-6, "Safe Z is too low: {}", safe_zs[0]);
+  }
+
+  #[test]
+  fn cuts_stamp_only_the_nodes_they_lower() {
+    let mut heightmap = Heightmap::new(21, 11, 1.0, 1.0, 0.0, 0.0, 5.0);
+    let mut layers = NodeAttributes::new(&heightmap, true, false, true);
+    // Operation 1 cuts at x = 5 down to 3, operation 2 at x = 6 down to 4: its tool only
+    // lowers the nodes operation 1 left above 4
+    for (operation_id, x, z) in [(1, 5.0, 3.0), (2, 6.0, 4.0)] {
+      simulate_material_removal(SimulateMaterialRemovalParams {
+        heightmap: &mut heightmap,
+        tool_type: "flat",
+        cutter_diameter: 3.0,
+        v_angle_deg: 0.0,
+        toolpath: &[x, 5.0, z],
+        attributes: Some(CutStamp { layers: &mut layers, operation_id, tool_id: 7, time: operation_id as f32 * 10.0 }),
+      });
+    }
+    let operation = layers.operation_id.as_ref().unwrap();
+    let time = layers.cut_time.as_ref().unwrap();
+    assert!(layers.tool_id.is_none());
+    let k = |ix: usize| heightmap.index(ix, 5);
+    assert_eq!((operation[k(5)], time[k(5)], heightmap.get(5, 5)), (1, 10.0, 3.0));
+    assert_eq!((operation[k(7)], time[k(7)], heightmap.get(7, 5)), (2, 20.0, 4.0));
+    assert_eq!((operation[k(12)], time[k(12)]), (0, -1.0));
   }
-  safe_zs
 }
@@ -0,0 +1,290 @@
+// Level-of-detail stock: min-preserving mip levels of the heightmap stock, meshed per level
+// All units are mm unless otherwise noted
+//
+// Zoomed out, the viewer doesn't need every cell. Level k has about half the nodes of level
+// k - 1 along each axis and spans the same extent; each of its nodes takes the lowest of the
+// full-resolution nodes nearest to it, so a cut narrower than a coarse cell still shows at its
+// full depth instead of being averaged away. Levels are always computed from the full
+// resolution stock, which stays authoritative; update_mip_levels refreshes only the nodes
+// under the dirty rects simulate_material_removal returns. From JS: build the levels once
+// with heightmap_mip_levels_wasm, refresh them with update_mip_levels_wasm after each
+// simulation, and mesh the level the zoom asks for with heightmap_lod_mesh_wasm.
+//
+// Assumptions:
+// - Level 0 is the stock itself; coarser levels stop shrinking at 2 nodes per axis
+// - A level's origin is the stock's, its cell size is stretched so its last node stays on the
+//   stock's last node (cells need not be a power of two of the original)
+// - Coarse surfaces can sit below the true surface by up to a coarse cell's relief, never above
+
+use crate::heightmap::{Heightmap, NodeRect};
+use crate::heightmap_to_solid_mesh::{solid_mesh_rect, Mesh};
+
+/// Nodes along one axis at a level, starting from n at level 0
+fn level_nodes(n: usize, level: usize) -> usize {
+  (0..level).fold(n, |m, _| if m <= 2 { m } else { (m - 1).div_ceil(2) + 1 })
+}
+
+/// Coarse node of fine node i, the nearest of m coarse nodes spread over the same n - 1 cells
+fn coarse_of(i: usize, n: usize, m: usize) -> usize {
+  if n < 2 {
+    return 0;
+  }
+  (2 * i * (m - 1) + (n - 1)) / (2 * (n - 1))
+}
+
+/// Fine nodes lo..hi (end exclusive) nearest to coarse node c
+fn fine_range(c: usize, n: usize, m: usize) -> (usize, usize) {
+  if m < 2 {
+    return (0, n);
+  }
+  // First i with coarse_of(i) >= c, i.e. 2 * i * (m - 1) >= (2 * c - 1) * (n - 1)
+  let first = |c: usize| if c == 0 { 0 } else { ((2 * c - 1) * (n - 1)).div_ceil(2 * (m - 1)).min(n) };
+  (first(c), if c + 1 >= m { n } else { first(c + 1) })
+}
+
+fn fill_level(stock: &Heightmap, level: &mut Heightmap, coarse: NodeRect) {
+  for cx in coarse.ix0..coarse.ix1 {
+    let (fx0, fx1) = fine_range(cx, stock.nx, level.nx);
+    for cy in coarse.iy0..coarse.iy1 {
+      let (fy0, fy1) = fine_range(cy, stock.ny, level.ny);
+      let mut z = f32::INFINITY;
+      for fx in fx0..fx1 {
+        for fy in fy0..fy1 {
+          z = z.min(stock.get(fx, fy));
+        }
+      }
+      level.set(cx, cy, z);
+    }
+  }
+}
+
+/// Grid of a mip level of the stock, heights all 0
+fn level_grid(stock: &Heightmap, level: usize) -> Heightmap {
+  let (nx, ny) = (level_nodes(stock.nx, level), level_nodes(stock.ny, level));
+  let size = |width: f32, n: usize, grid_size: f32| if n < 2 { grid_size } else { width / (n - 1) as f32 };
+  Heightmap::new(
+    nx,
+    ny,
+    size(stock.width(), nx, stock.grid_size_x),
+    size(stock.depth(), ny, stock.grid_size_y),
+    stock.origin_x,
+    stock.origin_y,
+    0.0,
+  )
+}
+
+/// Mip level of the stock (level 0 is a copy of the stock)
+pub fn mip_level(stock: &Heightmap, level: usize) -> Heightmap {
+  let mut map = level_grid(stock, level);
+  let all = NodeRect { ix0: 0, iy0: 0, ix1: map.nx, iy1: map.ny };
+  fill_level(stock, &mut map, all);
+  map
+}
+
+/// Levels 1..=count of the stock, fewer if the grid stops shrinking
+pub fn heightmap_mip_levels(stock: &Heightmap, count: usize) -> Vec<Heightmap> {
+  let mut levels: Vec<Heightmap> = Vec::new();
+  for level in 1..=count {
+    let (nx, ny) = (level_nodes(stock.nx, level), level_nodes(stock.ny, level));
+    let (prev_nx, prev_ny) = levels.last().map_or((stock.nx, stock.ny), |l| (l.nx, l.ny));
+    if (nx, ny) == (prev_nx, prev_ny) {
+      break;
+    }
+    levels.push(mip_level(stock, level));
+  }
+  levels
+}
+
+/// Recompute the nodes of every level that cover the dirty rects of the stock
+pub fn update_mip_levels(stock: &Heightmap, levels: &mut [Heightmap], dirty: &[NodeRect]) {
+  for level in levels.iter_mut() {
+    for rect in dirty {
+      if rect.ix1 <= rect.ix0 || rect.iy1 <= rect.iy0 {
+        continue;
+      }
+      let coarse = NodeRect {
+        ix0: coarse_of(rect.ix0, stock.nx, level.nx),
+        iy0: coarse_of(rect.iy0, stock.ny, level.ny),
+        ix1: coarse_of(rect.ix1 - 1, stock.nx, level.nx) + 1,
+        iy1: coarse_of(rect.iy1 - 1, stock.ny, level.ny) + 1,
+      };
+      fill_level(stock, level, coarse);
+    }
+  }
+}
+
+/// Nodes of a heightmap covering x in [min_x, max_x], y in [min_y, max_y], at least one cell
+pub fn region_rect(map: &Heightmap, min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> NodeRect {
+  let span = |min: f32, max: f32, origin: f32, grid_size: f32, n: usize| {
+    let lo = (((min - origin) / grid_size).floor().max(0.0) as usize).min(n.saturating_sub(2));
+    let hi = ((((max - origin) / grid_size).ceil().max(0.0) as usize) + 1).clamp(lo + 2, n.max(lo + 2));
+    (lo, hi.min(n))
+  };
+  let (ix0, ix1) = span(min_x, max_x, map.origin_x, map.grid_size_x, map.nx);
+  let (iy0, iy1) = span(min_y, max_y, map.origin_y, map.grid_size_y, map.ny);
+  NodeRect { ix0, iy0, ix1, iy1 }
+}
+
+/// Solid mesh of a region of one level: 0 is the stock, k is levels[k - 1] (or the coarsest built)
+pub fn heightmap_lod_mesh(stock: &Heightmap, levels: &[Heightmap], level: usize, min_z: f32, region: [f32; 4]) -> Mesh {
+  let map = match level {
+    0 => stock,
+    k => levels.get(k - 1).or(levels.last()).unwrap_or(stock),
+  };
+  solid_mesh_rect(map, min_z, region_rect(map, region[0], region[1], region[2], region[3]))
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Array, Float32Array, Object, Uint32Array};
+
+/// Returns [{ heights, nx, ny, grid_size_x, grid_size_y }] for levels 1..=count; origins are the stock's
+#[wasm_bindgen]
+pub fn heightmap_mip_levels_wasm(
+  heights: &Float32Array,
+  nx: usize,
+  ny: usize,
+  grid_size_x: f32,
+  grid_size_y: f32,
+  origin_x: f32,
+  origin_y: f32,
+  count: usize,
+) -> Array {
+  let stock = Heightmap::from_vec(nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, heights.to_vec());
+  let result = Array::new();
+  for level in heightmap_mip_levels(&stock, count) {
+    let entry = Object::new();
+    js_sys::Reflect::set(&entry, &"heights".into(), &Float32Array::from(level.heights.as_slice())).unwrap();
+    js_sys::Reflect::set(&entry, &"nx".into(), &(level.nx as u32).into()).unwrap();
+    js_sys::Reflect::set(&entry, &"ny".into(), &(level.ny as u32).into()).unwrap();
+    js_sys::Reflect::set(&entry, &"grid_size_x".into(), &level.grid_size_x.into()).unwrap();
+    js_sys::Reflect::set(&entry, &"grid_size_y".into(), &level.grid_size_y.into()).unwrap();
+    result.push(&entry);
+  }
+  result
+}
+
+/// Refresh, in place, the heights of the levels heightmap_mip_levels_wasm returned for this
+/// stock, under the dirty rects (flat ix0, iy0, ix1, iy1) simulate_material_removal_wasm returned
+#[wasm_bindgen]
+pub fn update_mip_levels_wasm(
+  heights: &Float32Array,
+  nx: usize,
+  ny: usize,
+  grid_size_x: f32,
+  grid_size_y: f32,
+  origin_x: f32,
+  origin_y: f32,
+  levels_js: &Array,
+  dirty_js: &Uint32Array,
+) {
+  let stock = Heightmap::from_vec(nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, heights.to_vec());
+  let dirty: Vec<NodeRect> = dirty_js
+    .to_vec()
+    .chunks_exact(4)
+    .map(|r| NodeRect { ix0: r[0] as usize, iy0: r[1] as usize, ix1: r[2] as usize, iy1: r[3] as usize })
+    .collect();
+  let levels_heights: Vec<Float32Array> = levels_js
+    .iter()
+    .map(|entry| {
+      js_sys::Reflect::get(&entry, &"heights".into())
+        .ok()
+        .and_then(|heights| heights.dyn_into::<Float32Array>().ok())
+        .expect("mip level has no heights Float32Array")
+    })
+    .collect();
+  let mut levels: Vec<Heightmap> = levels_heights
+    .iter()
+    .enumerate()
+    .map(|(i, heights)| level_grid(&stock, i + 1).with_heights(heights.to_vec()))
+    .collect();
+  update_mip_levels(&stock, &mut levels, &dirty);
+  for (js, level) in levels_heights.iter().zip(&levels) {
+    js.copy_from(&level.heights);
+  }
+}
+
+/// Returns { positions, indices, normals } of the region min_x..max_x, min_y..max_y of one
+/// level: the stock itself or an entry of heightmap_mip_levels_wasm (origins are the stock's)
+#[wasm_bindgen]
+pub fn heightmap_lod_mesh_wasm(
+  heights: &Float32Array,
+  nx: usize,
+  ny: usize,
+  grid_size_x: f32,
+  grid_size_y: f32,
+  origin_x: f32,
+  origin_y: f32,
+  min_z: f32,
+  min_x: f32,
+  min_y: f32,
+  max_x: f32,
+  max_y: f32,
+) -> Object {
+  let map = Heightmap::from_vec(nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, heights.to_vec());
+  let mesh = solid_mesh_rect(&map, min_z, region_rect(&map, min_x, min_y, max_x, max_y));
+  let result = Object::new();
+  js_sys::Reflect::set(&result, &"positions".into(), &Float32Array::from(mesh.positions.as_slice())).unwrap();
+  js_sys::Reflect::set(&result, &"indices".into(), &Uint32Array::from(mesh.indices.as_slice())).unwrap();
+  js_sys::Reflect::set(&result, &"normals".into(), &Float32Array::from(mesh.normals.as_slice())).unwrap();
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::simulate_material_removal::{simulate_material_removal, SimulateMaterialRemovalParams};
+
+  // Straight cut from a to b in 0.1 mm steps (the simulation only cuts at toolpath points)
+  fn cut(stock: &mut Heightmap, a: [f32; 3], b: [f32; 3]) -> Vec<NodeRect> {
+    let steps = ((b[0] - a[0]).hypot(b[1] - a[1]) / 0.1).ceil() as usize;
+    let toolpath: Vec<f32> = (0..=steps)
+      .flat_map(|i| {
+        let t = i as f32 / steps as f32;
+        [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t]
+      })
+      .collect();
+    simulate_material_removal(SimulateMaterialRemovalParams {
+      heightmap: stock,
+      tool_type: "flat",
+      cutter_diameter: 0.5,
+      v_angle_deg: 0.0,
+      toolpath: &toolpath,
+      attributes: None,
+    })
+  }
+
+  #[test]
+  fn narrow_cut_stays_visible_at_every_level() {
+    let mut stock = Heightmap::new(201, 81, 0.5, 0.25, 10.0, -5.0, 5.0);
+    // A one-cell groove 2 mm deep along y at x = 35.5
+    cut(&mut stock, [35.5, -5.0, 3.0], [35.5, 15.0, 3.0]);
+    let levels = heightmap_mip_levels(&stock, 20);
+    assert_eq!((levels[0].nx, levels[0].ny), (101, 41));
+    assert_eq!((levels.last().unwrap().nx, levels.last().unwrap().ny), (2, 2));
+    for level in &levels {
+      // Same extent as the stock
+      assert!((level.x(level.nx - 1) - stock.x(stock.nx - 1)).abs() < 1e-3);
+      assert!((level.y(level.ny - 1) - stock.y(stock.ny - 1)).abs() < 1e-3);
+      // The groove is still there at full depth, and nothing is lower
+      let lowest = level.heights.iter().cloned().fold(f32::INFINITY, f32::min);
+      assert_eq!(lowest, 3.0);
+      let (ix, iy) = level.nearest_clamped(35.5, 5.0);
+      assert_eq!(level.get(ix, iy), 3.0, "{} x {}", level.nx, level.ny);
+    }
+    // A coarse region mesh is a fraction of the full one
+    let region = [10.0, -5.0, 60.0, 5.0];
+    let full = heightmap_lod_mesh(&stock, &levels, 0, 0.0, region);
+    let coarse = heightmap_lod_mesh(&stock, &levels, 2, 0.0, region);
+    assert_eq!(full.positions.len(), 3 * 3 * 101 * 41);
+    assert!(coarse.indices.len() * 10 < full.indices.len());
+  }
+
+  #[test]
+  fn dirty_update_matches_a_rebuild() {
+    let mut stock = Heightmap::new(97, 65, 0.5, 0.5, 0.0, 0.0, 5.0);
+    let mut levels = heightmap_mip_levels(&stock, 4);
+    let dirty = cut(&mut stock, [10.0, 10.0, 4.0], [30.0, 20.0, 2.5]);
+    update_mip_levels(&stock, &mut levels, &dirty);
+    assert_eq!(levels, heightmap_mip_levels(&stock, 4));
+  }
+}
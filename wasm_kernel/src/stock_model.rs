@@ -0,0 +1,149 @@
+// Stock representation abstraction
+// All units are mm unless otherwise noted
+//
+// Simulation, classification and display only need a few things from the stock: cut away
+// the volume swept by a tool, ask how high the material is at (x, y), mesh it, and measure
+// how much is left. StockModel captures those, so a different representation can replace
+// the heightmap without touching the callers: rest machining, pencil, drilling and the safe
+// tool height all take any StockModel.
+//
+// Assumptions:
+// - Tool positions are the center of the tip, as in simulate_material_removal
+// - height_at is the top of the material at (x, y), None off the stock (or where nothing is left)
+// - to_mesh and volume only count material above min_z
+// - The heightmap implementation samples the sweep every half of its smaller cell size
+// - ToolShape::profile is the one cutter shape every stock and the tool footprint use; an
+//   unknown tool type or a vbit without an angle has no profile (cuts nothing)
+
+use crate::heightmap::Heightmap;
+use crate::heightmap_to_solid_mesh::{heightmap_to_solid_mesh, Mesh};
+use crate::simulate_material_removal::{simulate_material_removal, SimulateMaterialRemovalParams};
+use crate::toolpath::{densify_path, flatten_points};
+
+#[derive(Clone, Copy, Debug)]
+pub struct ToolShape<'a> {
+  pub tool_type: &'a str, // "flat", "ball" or "vbit"
+  pub cutter_diameter: f32,
+  pub v_angle_deg: f32, // only used for vbit
+}
+
+impl ToolShape<'_> {
+  /// Height of the cutter surface above the tip at distance d from the axis, None outside the
+  /// cutter or for a tool with no shape
+  pub fn profile(&self, d: f32) -> Option<f32> {
+    let r = self.cutter_diameter / 2.0;
+    if d > r + 1e-6 {
+      return None;
+    }
+    match self.tool_type {
+      "flat" => Some(0.0),
+      "ball" => Some(r - (r * r - d * d).max(0.0).sqrt()),
+      "vbit" => {
+        let tan_half_angle = (self.v_angle_deg.to_radians() / 2.0).tan();
+        (tan_half_angle > 1e-8).then(|| d / tan_half_angle)
+      }
+      _ => None,
+    }
+  }
+}
+
+pub trait StockModel {
+  /// Remove the material swept by the tool moving through the toolpath points
+  fn remove_swept_tool(&mut self, tool: &ToolShape, toolpath: &[[f32; 3]]);
+  /// Top of the material at (x, y), None off the stock
+  fn height_at(&self, x: f32, y: f32) -> Option<f32>;
+  /// Closed triangle mesh of the material above min_z
+  fn to_mesh(&self, min_z: f32) -> Mesh;
+  /// Volume of the material above min_z in mm^3
+  fn volume(&self, min_z: f32) -> f32;
+  /// Spacing of the stored samples along x and y, the finest detail the stock holds
+  fn cell_size(&self) -> (f32, f32);
+
+  /// Top of the material at every node of grid, -inf where there is none
+  fn heights_on(&self, grid: &Heightmap) -> Heightmap {
+    let heights = (0..grid.nx * grid.ny)
+      .map(|k| {
+        let (ix, iy) = grid.node(k);
+        self.height_at(grid.x(ix), grid.y(iy)).unwrap_or(f32::NEG_INFINITY)
+      })
+      .collect();
+    grid.with_heights(heights)
+  }
+}
+
+impl StockModel for Heightmap {
+  fn remove_swept_tool(&mut self, tool: &ToolShape, toolpath: &[[f32; 3]]) {
+    let dense = densify_path(toolpath, self.grid_size_x.min(self.grid_size_y) / 2.0);
+    simulate_material_removal(SimulateMaterialRemovalParams {
+      heightmap: self,
+      tool_type: tool.tool_type,
+      cutter_diameter: tool.cutter_diameter,
+      v_angle_deg: tool.v_angle_deg,
+      toolpath: &flatten_points(&dense),
+      attributes: None,
+    });
+  }
+
+  fn height_at(&self, x: f32, y: f32) -> Option<f32> {
+    Heightmap::height_at(self, x, y)
+  }
+
+  fn to_mesh(&self, min_z: f32) -> Mesh {
+    heightmap_to_solid_mesh(self, min_z)
+  }
+
+  fn volume(&self, min_z: f32) -> f32 {
+    // Trapezoid rule over the nodes: edge nodes cover half a cell, corners a quarter
+    let (nx, ny) = (self.nx, self.ny);
+    let weight = |i: usize, n: usize| if n > 1 && (i == 0 || i == n - 1) { 0.5 } else { 1.0 };
+    let mut sum = 0.0;
+    for ix in 0..nx {
+      for iy in 0..ny {
+        let h = (self.get(ix, iy) - min_z).max(0.0);
+        sum += h * weight(ix, nx) * weight(iy, ny);
+      }
+    }
+    sum * self.grid_size_x * self.grid_size_y
+  }
+
+  fn cell_size(&self) -> (f32, f32) {
+    (self.grid_size_x, self.grid_size_y)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::heightmap_to_solid_mesh::create_heightmap_stock;
+
+  const FLAT: ToolShape = ToolShape { tool_type: "flat", cutter_diameter: 2.0, v_angle_deg: 0.0 };
+
+  #[test]
+  fn heightmap_stock_cuts_a_slot() {
+    let mut stock = create_heightmap_stock(20.0, 10.0, 81, 41, 5.0, 0.0, 0.0);
+    assert!((stock.volume(0.0) - 20.0 * 10.0 * 5.0).abs() < 1e-2);
+    // Two points are enough: the sweep between them is cut too
+    stock.remove_swept_tool(&FLAT, &[[4.0, 5.0, 3.0], [16.0, 5.0, 3.0]]);
+    assert_eq!(stock.height_at(10.0, 5.0), Some(3.0));
+    assert_eq!(stock.height_at(10.0, 7.0), Some(5.0));
+    assert_eq!(stock.height_at(-1.0, 5.0), None);
+    // 12 mm slot, 2 mm wide and deep, plus the round ends
+    let removed = 20.0 * 10.0 * 5.0 - stock.volume(0.0);
+    let expected = 12.0 * 2.0 * 2.0 + std::f32::consts::PI * 2.0;
+    assert!((removed - expected).abs() < expected * 0.15, "removed {} expected {}", removed, expected);
+  }
+
+  #[test]
+  fn callers_work_through_the_trait() {
+    fn face(stock: &mut dyn StockModel, z: f32) {
+      let rows: Vec<[f32; 3]> = (0..=10).flat_map(|i| [[0.0, i as f32, z], [10.0, i as f32, z]]).collect();
+      stock.remove_swept_tool(&FLAT, &rows);
+    }
+    let mut stock = create_heightmap_stock(10.0, 10.0, 21, 21, 5.0, 0.0, 0.0);
+    face(&mut stock, 4.0);
+    assert!((stock.volume(0.0) - 400.0).abs() < 1e-2);
+    let mesh = stock.to_mesh(0.0);
+    assert_eq!(mesh.positions, heightmap_to_solid_mesh(&stock, 0.0).positions);
+    assert!(mesh.positions.chunks(3).all(|p| p[2] == 4.0 || p[2] == 0.0));
+  }
+}
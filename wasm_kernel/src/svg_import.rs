@@ -0,0 +1,799 @@
+// SVG import into 2D geometry for carving (outlines) and pocketing (filled regions)
+// All output units are mm, with +y up (the SVG y axis is flipped)
+//
+// Supported: path (M L H V C S Q T A Z, absolute and relative), rect (with rx/ry), circle,
+// ellipse, line, polyline, polygon, nested groups and the transform attribute.
+// Curves and arcs are flattened so the chord error stays below `tolerance` mm.
+//
+// Assumptions:
+// - Root width/height with units (mm, cm, in, pt, pc, px) and viewBox set the mm scale;
+//   bare numbers and missing sizes are CSS px (96 per inch)
+// - Every subpath is an outline; closed or closable geometry is also a fill region unless its
+//   fill paint (attribute or style, inherited, black by default) is none, so stroke-only
+//   shapes are carved but never pocketed. fill-rule (likewise inherited) decides the holes
+// - Subpaths are assumed not to cross each other
+// - defs, clipPath, mask, symbol, pattern, marker and display:none content is skipped; use is not supported
+
+use crate::geometry_2d::{fill_regions, FillRule, Point2, Polyline2, Region};
+
+/// Geometry imported from a vector file
+#[derive(Clone, Debug, Default)]
+pub struct VectorGeometry {
+  pub outlines: Vec<Polyline2>, // every subpath, for carving along the outline
+  pub regions: Vec<Region>,     // filled areas with holes, for pocketing
+}
+
+// 2D affine transform [a, b, c, d, e, f]: x' = a x + c y + e, y' = b x + d y + f
+type Matrix = [f32; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+fn multiply(m: &Matrix, n: &Matrix) -> Matrix {
+  [
+    m[0] * n[0] + m[2] * n[1],
+    m[1] * n[0] + m[3] * n[1],
+    m[0] * n[2] + m[2] * n[3],
+    m[1] * n[2] + m[3] * n[3],
+    m[0] * n[4] + m[2] * n[5] + m[4],
+    m[1] * n[4] + m[3] * n[5] + m[5],
+  ]
+}
+
+fn apply(m: &Matrix, p: Point2) -> Point2 {
+  [m[0] * p[0] + m[2] * p[1] + m[4], m[1] * p[0] + m[3] * p[1] + m[5]]
+}
+
+// Largest stretch factor of the transform, to convert the mm tolerance to user units
+fn max_scale(m: &Matrix) -> f32 {
+  let (a, b, c, d) = (m[0], m[1], m[2], m[3]);
+  let s1 = a * a + b * b + c * c + d * d;
+  let s2 = ((a * a + b * b - c * c - d * d).powi(2) + 4.0 * (a * c + b * d).powi(2)).sqrt();
+  ((s1 + s2) / 2.0).sqrt().max(1e-9)
+}
+
+// ---- Minimal XML tokenizer ----
+
+struct XmlStart<'a> {
+  name: &'a str,
+  attrs: Vec<(&'a str, String)>,
+  self_closing: bool,
+}
+
+enum XmlToken<'a> {
+  Start(XmlStart<'a>),
+  End,
+}
+
+fn decode_entities(s: &str) -> String {
+  if !s.contains('&') {
+    return s.to_string();
+  }
+  let mut out = String::with_capacity(s.len());
+  let mut rest = s;
+  while let Some(i) = rest.find('&') {
+    out.push_str(&rest[..i]);
+    rest = &rest[i..];
+    let Some(j) = rest.find(';') else { break };
+    let entity = &rest[1..j];
+    let decoded = match entity {
+      "amp" => Some('&'),
+      "lt" => Some('<'),
+      "gt" => Some('>'),
+      "quot" => Some('"'),
+      "apos" => Some('\''),
+      _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16).ok().and_then(char::from_u32),
+      _ if entity.starts_with('#') => entity[1..].parse::<u32>().ok().and_then(char::from_u32),
+      _ => None,
+    };
+    match decoded {
+      Some(c) => {
+        out.push(c);
+        rest = &rest[j + 1..];
+      }
+      None => {
+        out.push('&');
+        rest = &rest[1..];
+      }
+    }
+  }
+  out.push_str(rest);
+  out
+}
+
+fn xml_tokens(text: &str) -> Result<Vec<XmlToken<'_>>, String> {
+  let mut tokens = Vec::new();
+  let mut pos = 0;
+  while let Some(offset) = text[pos..].find('<') {
+    pos += offset;
+    let rest = &text[pos..];
+    let skip_to = |end: &str| rest.find(end).map(|i| pos + i + end.len()).ok_or_else(|| format!("unterminated markup at byte {}", pos));
+    if rest.starts_with("<!--") {
+      pos = skip_to("-->")?;
+    } else if rest.starts_with("<?") {
+      pos = skip_to("?>")?;
+    } else if rest.starts_with("<![CDATA[") {
+      pos = skip_to("]]>")?;
+    } else if rest.starts_with("<!") {
+      // DOCTYPE, possibly with an internal subset in brackets
+      let mut depth = 0;
+      let mut end = None;
+      for (i, c) in rest.char_indices() {
+        match c {
+          '[' => depth += 1,
+          ']' => depth -= 1,
+          '>' if depth == 0 => {
+            end = Some(i);
+            break;
+          }
+          _ => {}
+        }
+      }
+      pos += end.ok_or("unterminated DOCTYPE")? + 1;
+    } else if rest.starts_with("</") {
+      pos = skip_to(">")?;
+      tokens.push(XmlToken::End);
+    } else {
+      let bytes = rest.as_bytes();
+      let mut i = 1;
+      while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'/' && bytes[i] != b'>' {
+        i += 1;
+      }
+      let name = &rest[1..i];
+      let mut attrs = Vec::new();
+      loop {
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+          i += 1;
+        }
+        if i >= bytes.len() {
+          return Err(format!("unterminated tag <{}>", name));
+        }
+        if bytes[i] == b'>' {
+          tokens.push(XmlToken::Start(XmlStart { name, attrs, self_closing: false }));
+          pos += i + 1;
+          break;
+        }
+        if bytes[i] == b'/' {
+          tokens.push(XmlToken::Start(XmlStart { name, attrs, self_closing: true }));
+          pos += rest[i..].find('>').ok_or("unterminated tag")? + i + 1;
+          break;
+        }
+        let key_start = i;
+        while i < bytes.len() && bytes[i] != b'=' && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' {
+          i += 1;
+        }
+        let key = &rest[key_start..i];
+        while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'=') {
+          i += 1;
+        }
+        if i < bytes.len() && (bytes[i] == b'"' || bytes[i] == b'\'') {
+          let quote = bytes[i] as char;
+          let end = rest[i + 1..].find(quote).ok_or_else(|| format!("unterminated attribute {}", key))?;
+          attrs.push((key, decode_entities(&rest[i + 1..i + 1 + end])));
+          i += end + 2;
+        } else {
+          attrs.push((key, String::new()));
+        }
+      }
+    }
+  }
+  Ok(tokens)
+}
+
+// ---- Attribute parsing ----
+
+fn attr<'b>(el: &'b XmlStart, key: &str) -> Option<&'b str> {
+  el.attrs.iter().find(|(k, _)| *k == key).map(|(_, v)| v.as_str())
+}
+
+// Presentation attribute, or the same property inside style="..."
+fn property(el: &XmlStart, key: &str) -> Option<String> {
+  if let Some(style) = attr(el, "style") {
+    for decl in style.split(';') {
+      if let Some((k, v)) = decl.split_once(':') {
+        if k.trim() == key {
+          return Some(v.trim().to_string());
+        }
+      }
+    }
+  }
+  attr(el, key).map(|v| v.trim().to_string())
+}
+
+// Numbers in attribute lists and path data: "1.5.5" is 1.5 then .5, "1e-3", "-2-3"
+struct NumberScanner<'a> {
+  s: &'a [u8],
+  i: usize,
+}
+
+impl<'a> NumberScanner<'a> {
+  fn new(s: &'a str) -> NumberScanner<'a> {
+    NumberScanner { s: s.as_bytes(), i: 0 }
+  }
+
+  fn skip_separators(&mut self) {
+    while self.i < self.s.len() && (self.s[self.i].is_ascii_whitespace() || self.s[self.i] == b',') {
+      self.i += 1;
+    }
+  }
+
+  fn peek(&mut self) -> Option<u8> {
+    self.skip_separators();
+    self.s.get(self.i).cloned()
+  }
+
+  fn at_number(&mut self) -> bool {
+    matches!(self.peek(), Some(c) if c.is_ascii_digit() || c == b'-' || c == b'+' || c == b'.')
+  }
+
+  fn number(&mut self) -> Option<f32> {
+    self.skip_separators();
+    let start = self.i;
+    let s = self.s;
+    let mut i = self.i;
+    if i < s.len() && (s[i] == b'-' || s[i] == b'+') {
+      i += 1;
+    }
+    let mut seen_dot = false;
+    let mut digits = 0;
+    while i < s.len() && (s[i].is_ascii_digit() || (s[i] == b'.' && !seen_dot)) {
+      if s[i] == b'.' {
+        seen_dot = true;
+      } else {
+        digits += 1;
+      }
+      i += 1;
+    }
+    if digits == 0 {
+      return None;
+    }
+    if i < s.len() && (s[i] == b'e' || s[i] == b'E') {
+      let mut j = i + 1;
+      if j < s.len() && (s[j] == b'-' || s[j] == b'+') {
+        j += 1;
+      }
+      if j < s.len() && s[j].is_ascii_digit() {
+        while j < s.len() && s[j].is_ascii_digit() {
+          j += 1;
+        }
+        i = j;
+      }
+    }
+    self.i = i;
+    std::str::from_utf8(&s[start..i]).ok()?.parse().ok()
+  }
+
+  // Arc flags may be written without separators: "a1 1 0 00 1 1"
+  fn flag(&mut self) -> Option<bool> {
+    match self.peek() {
+      Some(b'0') => {
+        self.i += 1;
+        Some(false)
+      }
+      Some(b'1') => {
+        self.i += 1;
+        Some(true)
+      }
+      _ => None,
+    }
+  }
+}
+
+fn numbers(s: &str) -> Vec<f32> {
+  let mut scanner = NumberScanner::new(s);
+  let mut values = Vec::new();
+  while let Some(v) = scanner.number() {
+    values.push(v);
+  }
+  values
+}
+
+// Length in user units; unit suffixes other than px are ignored (treated as user units)
+fn length(el: &XmlStart, key: &str) -> f32 {
+  attr(el, key).and_then(|v| NumberScanner::new(v).number()).unwrap_or(0.0)
+}
+
+// Length in mm, for the root width/height
+fn length_mm(value: &str) -> Option<f32> {
+  let v = value.trim();
+  let number = NumberScanner::new(v).number()?;
+  let unit = v.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.' || c == '-' || c == '+' || c == 'e' || c == 'E').trim();
+  let mm_per_unit = match unit {
+    "mm" => 1.0,
+    "cm" => 10.0,
+    "in" => 25.4,
+    "pt" => 25.4 / 72.0,
+    "pc" => 25.4 / 6.0,
+    "" | "px" => 25.4 / 96.0,
+    _ => return None, // percentages etc.
+  };
+  Some(number * mm_per_unit)
+}
+
+fn parse_transform(s: &str) -> Matrix {
+  let mut m = IDENTITY;
+  let mut rest = s;
+  while let Some(open) = rest.find('(') {
+    let name = rest[..open].trim().trim_start_matches(',').trim();
+    let Some(close) = rest[open..].find(')') else { break };
+    let args = numbers(&rest[open + 1..open + close]);
+    let arg = |i: usize, default: f32| args.get(i).cloned().unwrap_or(default);
+    let t: Matrix = match name {
+      "matrix" if args.len() == 6 => [args[0], args[1], args[2], args[3], args[4], args[5]],
+      "translate" => [1.0, 0.0, 0.0, 1.0, arg(0, 0.0), arg(1, 0.0)],
+      "scale" => [arg(0, 1.0), 0.0, 0.0, arg(1, arg(0, 1.0)), 0.0, 0.0],
+      "rotate" => {
+        let (s, c) = arg(0, 0.0).to_radians().sin_cos();
+        let (cx, cy) = (arg(1, 0.0), arg(2, 0.0));
+        // translate(cx, cy) rotate translate(-cx, -cy)
+        [c, s, -s, c, cx - c * cx + s * cy, cy - s * cx - c * cy]
+      }
+      "skewX" => [1.0, 0.0, arg(0, 0.0).to_radians().tan(), 1.0, 0.0, 0.0],
+      "skewY" => [1.0, arg(0, 0.0).to_radians().tan(), 0.0, 1.0, 0.0, 0.0],
+      _ => IDENTITY,
+    };
+    m = multiply(&m, &t);
+    rest = &rest[open + close + 1..];
+  }
+  m
+}
+
+// ---- Geometry building ----
+
+// Subpaths of one element in output (mm) coordinates
+struct PathBuilder {
+  m: Matrix,
+  tolerance: f32, // mm
+  subpaths: Vec<Polyline2>,
+  current: Vec<Point2>,
+}
+
+impl PathBuilder {
+  fn new(m: Matrix, tolerance: f32) -> PathBuilder {
+    PathBuilder { m, tolerance, subpaths: Vec::new(), current: Vec::new() }
+  }
+
+  fn finish(&mut self, closed: bool) {
+    let mut points = std::mem::take(&mut self.current);
+    if closed && points.len() > 1 && points.first() == points.last() {
+      points.pop();
+    }
+    if points.len() > 1 {
+      self.subpaths.push(Polyline2 { points, closed });
+    }
+  }
+
+  fn move_to(&mut self, p: Point2) {
+    self.finish(false);
+    self.current.push(apply(&self.m, p));
+  }
+
+  fn line_to(&mut self, p: Point2) {
+    let q = apply(&self.m, p);
+    if self.current.last() != Some(&q) {
+      self.current.push(q);
+    }
+  }
+
+  // Bezier through control points given in user units; flattened in output units
+  fn bezier_to(&mut self, ctrl: &[Point2]) {
+    let c: Vec<Point2> = ctrl.iter().map(|&p| apply(&self.m, p)).collect();
+    // Max second difference bounds the chord error: n >= sqrt(M / (8 tolerance))
+    let second = |a: Point2, b: Point2, d: Point2| (a[0] - 2.0 * b[0] + d[0]).hypot(a[1] - 2.0 * b[1] + d[1]);
+    let m = if c.len() == 4 {
+      6.0 * second(c[0], c[1], c[2]).max(second(c[1], c[2], c[3]))
+    } else {
+      2.0 * second(c[0], c[1], c[2])
+    };
+    let n = ((m / (8.0 * self.tolerance)).sqrt().ceil() as usize).clamp(1, 1000);
+    for i in 1..=n {
+      let t = i as f32 / n as f32;
+      let u = 1.0 - t;
+      let p = if c.len() == 4 {
+        let (w0, w1, w2, w3) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+        [w0 * c[0][0] + w1 * c[1][0] + w2 * c[2][0] + w3 * c[3][0], w0 * c[0][1] + w1 * c[1][1] + w2 * c[2][1] + w3 * c[3][1]]
+      } else {
+        let (w0, w1, w2) = (u * u, 2.0 * u * t, t * t);
+        [w0 * c[0][0] + w1 * c[1][0] + w2 * c[2][0], w0 * c[0][1] + w1 * c[1][1] + w2 * c[2][1]]
+      };
+      self.current.push(p);
+    }
+  }
+
+  // Elliptical arc in user units (SVG endpoint parameterization, spec section B.2.4)
+  fn arc_to(&mut self, from: Point2, rx: f32, ry: f32, rotation_deg: f32, large_arc: bool, sweep: bool, to: Point2) {
+    let (mut rx, mut ry) = (rx.abs(), ry.abs());
+    if rx < 1e-9 || ry < 1e-9 || from == to {
+      self.line_to(to);
+      return;
+    }
+    let (sin_phi, cos_phi) = rotation_deg.to_radians().sin_cos();
+    let dx = (from[0] - to[0]) / 2.0;
+    let dy = (from[1] - to[1]) / 2.0;
+    let x1 = cos_phi * dx + sin_phi * dy;
+    let y1 = -sin_phi * dx + cos_phi * dy;
+    // Scale up radii that are too small to span the endpoints
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+      rx *= lambda.sqrt();
+      ry *= lambda.sqrt();
+    }
+    let num = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let den = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let mut coef = (num / den).max(0.0).sqrt();
+    if large_arc == sweep {
+      coef = -coef;
+    }
+    let cx1 = coef * rx * y1 / ry;
+    let cy1 = -coef * ry * x1 / rx;
+    let cx = cos_phi * cx1 - sin_phi * cy1 + (from[0] + to[0]) / 2.0;
+    let cy = sin_phi * cx1 + cos_phi * cy1 + (from[1] + to[1]) / 2.0;
+    let angle = |ux: f32, uy: f32| uy.atan2(ux);
+    let theta1 = angle((x1 - cx1) / rx, (y1 - cy1) / ry);
+    let mut delta = angle((-x1 - cx1) / rx, (-y1 - cy1) / ry) - theta1;
+    let tau = std::f32::consts::TAU;
+    if sweep && delta < 0.0 {
+      delta += tau;
+    } else if !sweep && delta > 0.0 {
+      delta -= tau;
+    }
+    // Segment count from the sagitta of the largest output radius
+    let r = rx.max(ry) * max_scale(&self.m);
+    let step = 2.0 * (1.0 - self.tolerance / r).clamp(-1.0, 1.0).acos();
+    let n = ((delta.abs() / step.max(1e-3)).ceil() as usize).clamp(1, 1000);
+    for i in 1..=n {
+      let t = theta1 + delta * i as f32 / n as f32;
+      let (st, ct) = t.sin_cos();
+      let p = if i == n {
+        to
+      } else {
+        [cx + rx * ct * cos_phi - ry * st * sin_phi, cy + rx * ct * sin_phi + ry * st * cos_phi]
+      };
+      self.line_to(p);
+    }
+  }
+
+  fn ellipse(&mut self, cx: f32, cy: f32, rx: f32, ry: f32) {
+    if rx <= 0.0 || ry <= 0.0 {
+      return;
+    }
+    let start = [cx + rx, cy];
+    let mid = [cx - rx, cy];
+    self.move_to(start);
+    self.arc_to(start, rx, ry, 0.0, false, true, mid);
+    self.arc_to(mid, rx, ry, 0.0, false, true, start);
+    self.finish(true);
+  }
+}
+
+fn parse_path_data(d: &str, b: &mut PathBuilder) {
+  let mut scanner = NumberScanner::new(d);
+  let mut command = b' ';
+  let mut current = [0.0f32, 0.0];
+  let mut start = [0.0f32, 0.0];
+  // Reflected control point for S/T; only valid right after C/S or Q/T
+  let mut last_cubic: Option<Point2> = None;
+  let mut last_quad: Option<Point2> = None;
+  while let Some(c) = scanner.peek() {
+    if c.is_ascii_alphabetic() {
+      command = c;
+      scanner.i += 1;
+      if command == b'Z' || command == b'z' {
+        b.finish(true);
+        current = start;
+        last_cubic = None;
+        last_quad = None;
+        continue;
+      }
+    } else if !scanner.at_number() || command == b' ' {
+      break;
+    }
+    let relative = command.is_ascii_lowercase();
+    let base = if relative { current } else { [0.0, 0.0] };
+    let point = |s: &mut NumberScanner| -> Option<Point2> { Some([base[0] + s.number()?, base[1] + s.number()?]) };
+    if b.current.is_empty() && !command.eq_ignore_ascii_case(&b'M') {
+      // Drawing right after Z starts a new subpath at the previous start point
+      b.move_to(current);
+    }
+    let mut next_cubic = None;
+    let mut next_quad = None;
+    match command.to_ascii_uppercase() {
+      b'M' => {
+        let Some(p) = point(&mut scanner) else { break };
+        // If the previous subpath was closed with Z, the builder already finished it
+        b.move_to(p);
+        current = p;
+        start = p;
+        // Further pairs after a moveto are implicit linetos
+        command = if relative { b'l' } else { b'L' };
+      }
+      b'L' => {
+        let Some(p) = point(&mut scanner) else { break };
+        b.line_to(p);
+        current = p;
+      }
+      b'H' => {
+        let Some(x) = scanner.number() else { break };
+        current = [if relative { current[0] + x } else { x }, current[1]];
+        b.line_to(current);
+      }
+      b'V' => {
+        let Some(y) = scanner.number() else { break };
+        current = [current[0], if relative { current[1] + y } else { y }];
+        b.line_to(current);
+      }
+      b'C' | b'S' => {
+        let c1 = if command.eq_ignore_ascii_case(&b'C') {
+          let Some(p) = point(&mut scanner) else { break };
+          p
+        } else {
+          last_cubic.map(|q| [2.0 * current[0] - q[0], 2.0 * current[1] - q[1]]).unwrap_or(current)
+        };
+        let (Some(c2), Some(p)) = (point(&mut scanner), point(&mut scanner)) else { break };
+        b.bezier_to(&[current, c1, c2, p]);
+        next_cubic = Some(c2);
+        current = p;
+      }
+      b'Q' | b'T' => {
+        let c1 = if command.eq_ignore_ascii_case(&b'Q') {
+          let Some(p) = point(&mut scanner) else { break };
+          p
+        } else {
+          last_quad.map(|q| [2.0 * current[0] - q[0], 2.0 * current[1] - q[1]]).unwrap_or(current)
+        };
+        let Some(p) = point(&mut scanner) else { break };
+        b.bezier_to(&[current, c1, p]);
+        next_quad = Some(c1);
+        current = p;
+      }
+      b'A' => {
+        let (Some(rx), Some(ry), Some(rotation)) = (scanner.number(), scanner.number(), scanner.number()) else { break };
+        let (Some(large_arc), Some(sweep)) = (scanner.flag(), scanner.flag()) else { break };
+        let Some(p) = point(&mut scanner) else { break };
+        b.arc_to(current, rx, ry, rotation, large_arc, sweep, p);
+        current = p;
+      }
+      _ => break,
+    }
+    last_cubic = next_cubic;
+    last_quad = next_quad;
+  }
+  b.finish(false);
+}
+
+// Geometry of a shape element in its own user units, or None if it isn't a shape
+fn shape_subpaths(el: &XmlStart, m: Matrix, tolerance: f32) -> Option<Vec<Polyline2>> {
+  let mut b = PathBuilder::new(m, tolerance);
+  match el.name {
+    "path" => parse_path_data(attr(el, "d").unwrap_or(""), &mut b),
+    "rect" => {
+      let (x, y, w, h) = (length(el, "x"), length(el, "y"), length(el, "width"), length(el, "height"));
+      if w <= 0.0 || h <= 0.0 {
+        return Some(Vec::new());
+      }
+      // A missing rx or ry defaults to the other one
+      let (rx_attr, ry_attr) = (attr(el, "rx").map(|_| length(el, "rx")), attr(el, "ry").map(|_| length(el, "ry")));
+      let rx = rx_attr.or(ry_attr).unwrap_or(0.0).clamp(0.0, w / 2.0);
+      let ry = ry_attr.or(rx_attr).unwrap_or(0.0).clamp(0.0, h / 2.0);
+      if rx > 0.0 && ry > 0.0 {
+        b.move_to([x + rx, y]);
+        b.line_to([x + w - rx, y]);
+        b.arc_to([x + w - rx, y], rx, ry, 0.0, false, true, [x + w, y + ry]);
+        b.line_to([x + w, y + h - ry]);
+        b.arc_to([x + w, y + h - ry], rx, ry, 0.0, false, true, [x + w - rx, y + h]);
+        b.line_to([x + rx, y + h]);
+        b.arc_to([x + rx, y + h], rx, ry, 0.0, false, true, [x, y + h - ry]);
+        b.line_to([x, y + ry]);
+        b.arc_to([x, y + ry], rx, ry, 0.0, false, true, [x + rx, y]);
+      } else {
+        b.move_to([x, y]);
+        b.line_to([x + w, y]);
+        b.line_to([x + w, y + h]);
+        b.line_to([x, y + h]);
+      }
+      b.finish(true);
+    }
+    "circle" => {
+      let r = length(el, "r");
+      b.ellipse(length(el, "cx"), length(el, "cy"), r, r);
+    }
+    "ellipse" => b.ellipse(length(el, "cx"), length(el, "cy"), length(el, "rx"), length(el, "ry")),
+    "line" => {
+      b.move_to([length(el, "x1"), length(el, "y1")]);
+      b.line_to([length(el, "x2"), length(el, "y2")]);
+      b.finish(false);
+    }
+    "polyline" | "polygon" => {
+      let values = numbers(attr(el, "points").unwrap_or(""));
+      for (i, pair) in values.chunks_exact(2).enumerate() {
+        if i == 0 {
+          b.move_to([pair[0], pair[1]]);
+        } else {
+          b.line_to([pair[0], pair[1]]);
+        }
+      }
+      b.finish(el.name == "polygon");
+    }
+    _ => return None,
+  }
+  Some(b.subpaths)
+}
+
+// Transform from root user units to mm with +y up
+fn root_transform(el: &XmlStart, flip_y: bool) -> Matrix {
+  let px = 25.4 / 96.0;
+  let view_box = attr(el, "viewBox").map(numbers).filter(|v| v.len() == 4 && v[2] > 0.0 && v[3] > 0.0);
+  let width_mm = attr(el, "width").and_then(length_mm);
+  let height_mm = attr(el, "height").and_then(length_mm);
+  let (min_x, min_y, sx, sy, doc_height) = match view_box {
+    Some(vb) => {
+      let sx = width_mm.map(|w| w / vb[2]).unwrap_or(px);
+      let sy = height_mm.map(|h| h / vb[3]).unwrap_or(sx);
+      (vb[0], vb[1], sx, sy, vb[3] * sy)
+    }
+    None => (0.0, 0.0, px, px, height_mm.unwrap_or(0.0)),
+  };
+  let scale: Matrix = [sx, 0.0, 0.0, sy, -min_x * sx, -min_y * sy];
+  if flip_y {
+    multiply(&[1.0, 0.0, 0.0, -1.0, 0.0, doc_height], &scale)
+  } else {
+    scale
+  }
+}
+
+/// Parse an SVG document. tolerance is the max chord error of flattened curves in mm.
+/// flip_y maps the SVG y-down page to +y up with the bottom edge of the page at y = 0.
+pub fn import_svg(text: &str, tolerance: f32, flip_y: bool) -> Result<VectorGeometry, String> {
+  let tolerance = tolerance.max(1e-4);
+  let tokens = xml_tokens(text)?;
+  let mut geometry = VectorGeometry::default();
+  // (transform, fill rule, filled, skipped) for each open element
+  let mut stack: Vec<(Matrix, FillRule, bool, bool)> = Vec::new();
+  let mut seen_root = false;
+  for token in tokens {
+    let el = match token {
+      XmlToken::End => {
+        stack.pop();
+        continue;
+      }
+      XmlToken::Start(el) => el,
+    };
+    let (parent_m, parent_rule, parent_filled, parent_skip) = stack.last().cloned().unwrap_or((IDENTITY, FillRule::NonZero, true, false));
+    let mut m = parent_m;
+    if el.name == "svg" && !seen_root {
+      seen_root = true;
+      m = root_transform(&el, flip_y);
+    }
+    if let Some(t) = attr(&el, "transform") {
+      m = multiply(&m, &parse_transform(t));
+    }
+    let rule = match property(&el, "fill-rule").as_deref() {
+      Some("evenodd") => FillRule::EvenOdd,
+      Some("nonzero") => FillRule::NonZero,
+      _ => parent_rule,
+    };
+    let filled = match property(&el, "fill").as_deref() {
+      Some("none") => false,
+      Some("inherit") | None => parent_filled,
+      Some(_) => true,
+    };
+    let skip = parent_skip
+      || matches!(el.name, "defs" | "clipPath" | "mask" | "symbol" | "pattern" | "marker" | "metadata" | "title" | "desc" | "style")
+      || property(&el, "display").as_deref() == Some("none");
+    if !skip {
+      if let Some(subpaths) = shape_subpaths(&el, m, tolerance) {
+        if filled {
+          // Open subpaths are implicitly closed for filling
+          let loops: Vec<Vec<Point2>> = subpaths.iter().filter(|s| s.points.len() > 2).map(|s| s.points.clone()).collect();
+          geometry.regions.extend(fill_regions(&loops, rule));
+        }
+        geometry.outlines.extend(subpaths);
+      }
+    }
+    if !el.self_closing {
+      stack.push((m, rule, filled, skip));
+    }
+  }
+  Ok(geometry)
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::Object;
+use crate::geometry_2d::{polylines_to_js, regions_to_js};
+
+/// Returns { outlines: { points, counts, closed }, regions: { points, counts, regions } }
+#[wasm_bindgen]
+pub fn import_svg_wasm(text: &str, tolerance: f32, flip_y: bool) -> Result<Object, JsValue> {
+  let geometry = import_svg(text, tolerance, flip_y).map_err(|e| JsValue::from_str(&e))?;
+  let result = Object::new();
+  js_sys::Reflect::set(&result, &"outlines".into(), &polylines_to_js(&geometry.outlines)).unwrap();
+  js_sys::Reflect::set(&result, &"regions".into(), &regions_to_js(&geometry.regions)).unwrap();
+  Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::geometry_2d::signed_area;
+
+  fn region_area(r: &Region) -> f32 {
+    signed_area(&r.outer) + r.holes.iter().map(|h| signed_area(h)).sum::<f32>()
+  }
+
+  #[test]
+  fn mm_document_with_rect_and_circle() {
+    let svg = r#"<?xml version="1.0"?>
+      <!-- test drawing -->
+      <svg xmlns="http://www.w3.org/2000/svg" width="100mm" height="50mm" viewBox="0 0 200 100">
+        <g transform="translate(10, 10)">
+          <rect x="0" y="0" width="20" height="10"/>
+          <circle cx="100" cy="40" r="20" fill="green"/>
+          <circle cx="150" cy="40" r="20" style="fill:none;stroke:#000"/>
+          <g fill="none"><rect x="0" y="50" width="20" height="10"/></g>
+        </g>
+      </svg>"#;
+    let g = import_svg(svg, 0.01, true).unwrap();
+    // Stroke-only shapes, set directly or inherited, are outlines but not pockets
+    assert_eq!(g.outlines.len(), 4);
+    assert_eq!(g.regions.len(), 2);
+    // 20x10 user units at 0.5 mm per unit
+    assert!((region_area(&g.regions[0]) - 50.0).abs() < 1e-3);
+    // Rect top-left (10, 10) user -> (5, 45) mm with +y up
+    let ys: Vec<f32> = g.regions[0].outer.iter().map(|p| p[1]).collect();
+    assert!(ys.iter().any(|&y| (y - 45.0).abs() < 1e-4));
+    let circle = std::f32::consts::PI * 10.0 * 10.0;
+    // Inscribed polygon: relative area error about 2 * tolerance / r
+    assert!((region_area(&g.regions[1]) - circle).abs() / circle < 2e-3);
+  }
+
+  #[test]
+  fn path_commands_and_fill_rules() {
+    // Outer square and a same-direction inner square: a hole only under evenodd
+    let d = "M0 0 H10 V10 H0 Z m3 3 h4 v4 h-4 z";
+    for (rule, holes) in [("nonzero", 0), ("evenodd", 1)] {
+      let svg = format!(r#"<svg width="10" height="10" viewBox="0 0 10 10"><path fill-rule="{}" d="{}"/></svg>"#, rule, d);
+      let g = import_svg(&svg, 0.01, false).unwrap();
+      assert_eq!(g.outlines.len(), 2);
+      assert_eq!(g.regions.len(), 1);
+      assert_eq!(g.regions[0].holes.len(), holes, "{}", rule);
+    }
+  }
+
+  #[test]
+  fn curves_stay_within_tolerance() {
+    // Quarter circle as an arc and as cubic, both radius 50 about the origin
+    let k = 0.552_284_8 * 50.0;
+    let svg = format!(
+      r#"<svg width="100mm" height="100mm" viewBox="0 0 100 100">
+        <path d="M50,0 A50,50 0 0,1 0,50"/>
+        <path d="M50,0 C50,{k} {k},50 0,50"/>
+        <path d="M0,0 Q10,20 20,0 T40,0 S50,10 60,0 s5,5 10,0"/>
+        <polyline points="0,0 1,1 2,0"/>
+      </svg>"#
+    );
+    let g = import_svg(&svg, 0.05, false).unwrap();
+    assert_eq!(g.outlines.len(), 4);
+    for outline in &g.outlines[..2] {
+      assert!(!outline.closed);
+      assert!(outline.points.len() > 4);
+      for p in &outline.points {
+        let r = p[0].hypot(p[1]);
+        assert!((r - 50.0).abs() < 0.06, "point {:?} is {} from the center", p, r);
+      }
+    }
+    let last = g.outlines[2].points.last().unwrap();
+    assert!((last[0] - 70.0).abs() < 1e-4 && last[1].abs() < 1e-4);
+  }
+
+  #[test]
+  fn skips_defs_and_hidden_content() {
+    let svg = r#"<svg viewBox="0 0 10 10" width="10mm" height="10mm">
+      <defs><rect width="5" height="5"/></defs>
+      <g style="display:none"><rect width="5" height="5"/></g>
+      <rect x="1" y="1" width="2" height="2" rx="0.5"/>
+    </svg>"#;
+    let g = import_svg(svg, 0.001, false).unwrap();
+    assert_eq!(g.outlines.len(), 1);
+    let rounded = 4.0 - 0.25 * (4.0 - std::f32::consts::PI);
+    assert!((region_area(&g.regions[0]) - rounded).abs() < 0.01, "area {}", region_area(&g.regions[0]));
+  }
+}
@@ -0,0 +1,378 @@
+// Single-stroke text for engraving, in the style of the Hershey simplex fonts
+// All units are mm unless otherwise noted
+//
+// Glyphs are center-line strokes, so an engraving tool following them draws the letters
+// directly (no outlines to fill). Text is laid out left to right on a straight baseline,
+// or along a baseline curve with each glyph turned to the curve direction.
+//
+// Assumptions:
+// - Font units: baseline at y = 0, cap height 8, x-height 5, descenders to -3; size is the cap height
+// - Printable ASCII only; characters without a glyph are drawn as '?'
+// - Lines are separated by '\n' and spaced 1.6 sizes apart on a straight baseline;
+//   on a curve the text is one line and newlines are treated as spaces
+// - Glyphs are placed rigidly on a curve (rotated, not bent), at their center's position along it
+
+use crate::geometry_2d::{rotate, Point2, Polyline2};
+use crate::toolpath::{order_open_passes, passes_to_js, Pass};
+
+// Font units between glyphs, before extra letter spacing
+const GLYPH_GAP: f32 = 2.0;
+const SPACE_WIDTH: f32 = 4.0;
+const CAP_HEIGHT: f32 = 8.0;
+const LINE_HEIGHT: f32 = 1.6;
+
+// (char, width, strokes). Strokes are separated by '|'; each stroke is a list of "x,y" points
+// and "(cx,cy,rx,ry,a0,a1)" elliptical arcs from angle a0 to a1 degrees (a1 < a0 is clockwise)
+const GLYPHS: &[(char, f32, &str)] = &[
+  ('A', 6.0, "0,0 3,8 6,0|1.1,3 4.9,3"),
+  ('B', 5.0, "0,0 0,8 3.5,8 (3.5,6,1.5,2,90,-90) 0,4|3.5,4 (3.5,2,1.5,2,90,-90) 0,0"),
+  ('C', 6.0, "(3,4,3,4,40,320)"),
+  ('D', 5.5, "0,0 0,8 2,8 (2,4,3.5,4,90,-90) 0,0"),
+  ('E', 5.0, "5,8 0,8 0,0 5,0|0,4 3.5,4"),
+  ('F', 5.0, "5,8 0,8 0,0|0,4 3.5,4"),
+  ('G', 6.0, "(3,4,3,4,45,360) 3.5,4"),
+  ('H', 5.0, "0,0 0,8|5,0 5,8|0,4 5,4"),
+  ('I', 0.0, "0,0 0,8"),
+  ('J', 4.0, "4,8 4,2 (2,2,2,2,0,-180)"),
+  ('K', 5.0, "0,0 0,8|5,8 0,3|1.6,4.6 5,0"),
+  ('L', 4.5, "0,8 0,0 4.5,0"),
+  ('M', 6.0, "0,0 0,8 3,2 6,8 6,0"),
+  ('N', 5.0, "0,0 0,8 5,0 5,8"),
+  ('O', 6.0, "(3,4,3,4,0,360)"),
+  ('P', 5.0, "0,0 0,8 3,8 (3,6,2,2,90,-90) 0,4"),
+  ('Q', 6.0, "(3,4,3,4,0,360)|3.5,2 6,-0.5"),
+  ('R', 5.0, "0,0 0,8 3,8 (3,6,2,2,90,-90) 0,4|2.5,4 5,0"),
+  ('S', 5.0, "(2.5,6,2.5,2,20,270) (2.5,2,2.5,2,90,-160)"),
+  ('T', 6.0, "0,8 6,8|3,8 3,0"),
+  ('U', 5.0, "0,8 0,2.5 (2.5,2.5,2.5,2.5,180,360) 5,8"),
+  ('V', 6.0, "0,8 3,0 6,8"),
+  ('W', 8.0, "0,8 2,0 4,6 6,0 8,8"),
+  ('X', 5.0, "0,8 5,0|0,0 5,8"),
+  ('Y', 6.0, "0,8 3,4 6,8|3,4 3,0"),
+  ('Z', 5.0, "0,8 5,8 0,0 5,0"),
+  ('a', 4.0, "(2,2.5,2,2.5,0,360)|4,5 4,0"),
+  ('b', 4.0, "0,8 0,0|(2,2.5,2,2.5,0,360)"),
+  ('c', 4.0, "(2,2.5,2,2.5,40,320)"),
+  ('d', 4.0, "(2,2.5,2,2.5,0,360)|4,8 4,0"),
+  ('e', 4.0, "0,2.5 4,2.5 (2,2.5,2,2.5,0,320)"),
+  ('f', 3.0, "3,8 (3,6.5,1.5,1.5,90,180) 1.5,0|0,5 3,5"),
+  ('g', 4.0, "(2,2.5,2,2.5,0,360)|4,5 4,-1 (2,-1,2,2,0,-160)"),
+  ('h', 4.0, "0,8 0,0|0,3 (2,3,2,2,180,0) 4,0"),
+  ('i', 0.0, "0,0 0,5|0,6.8 0,7.2"),
+  ('j', 2.5, "2.5,5 2.5,-1.5 (1,-1.5,1.5,1.5,0,-120)|2.5,6.8 2.5,7.2"),
+  ('k', 3.5, "0,0 0,8|3.5,5 0,2|1.2,3 3.5,0"),
+  ('l', 0.0, "0,8 0,0"),
+  ('m', 6.0, "0,0 0,5|0,3.5 (1.5,3.5,1.5,1.5,180,0) 3,0|3,3.5 (4.5,3.5,1.5,1.5,180,0) 6,0"),
+  ('n', 4.0, "0,0 0,5|0,3 (2,3,2,2,180,0) 4,0"),
+  ('o', 4.0, "(2,2.5,2,2.5,0,360)"),
+  ('p', 4.0, "0,5 0,-3|(2,2.5,2,2.5,0,360)"),
+  ('q', 4.0, "(2,2.5,2,2.5,0,360)|4,5 4,-3"),
+  ('r', 4.0, "0,0 0,5|0,3 (2.5,3,2.5,2,180,60)"),
+  ('s', 4.0, "(2,3.75,1.8,1.25,20,270) (2,1.25,2,1.25,90,-160)"),
+  ('t', 3.5, "1.5,8 1.5,1 (2.5,1,1,1,180,270) 3.5,0|0,5 3,5"),
+  ('u', 4.0, "0,5 0,2 (2,2,2,2,180,360)|4,5 4,0"),
+  ('v', 4.0, "0,5 2,0 4,5"),
+  ('w', 6.0, "0,5 1.5,0 3,4 4.5,0 6,5"),
+  ('x', 4.0, "0,5 4,0|0,0 4,5"),
+  ('y', 4.0, "0,5 2,0|4,5 0.8,-3"),
+  ('z', 4.0, "0,5 4,5 0,0 4,0"),
+  ('0', 5.0, "(2.5,4,2.5,4,0,360)"),
+  ('1', 3.5, "0.5,6.5 2,8 2,0|0.5,0 3.5,0"),
+  ('2', 5.0, "(2.5,5.5,2.5,2.5,160,-30) 0,0 5,0"),
+  ('3', 5.0, "(2.5,6,2.5,2,150,-90)|(2.5,2,2.5,2,90,-150)"),
+  ('4', 5.0, "3.5,0 3.5,8 0,2.5 5,2.5"),
+  ('5', 5.0, "4.5,8 0.8,8 0.4,4.4 (2.5,2.5,2.5,2.5,120,-150)"),
+  ('6', 5.0, "(2.5,2.5,2.5,2.5,0,360)|(3.5,2.5,3.5,5.5,180,70)"),
+  ('7', 5.0, "0,8 5,8 1.5,0"),
+  ('8', 5.0, "(2.5,6,2.2,2,0,360)|(2.5,2,2.5,2,0,360)"),
+  ('9', 5.0, "(2.5,5.5,2.5,2.5,0,360)|(1.5,5.5,3.5,5.5,0,-110)"),
+  ('.', 0.0, "0,0 0,0.4"),
+  (',', 0.6, "0.6,0.4 0.6,0 0,-1.2"),
+  ('!', 0.0, "0,8 0,2.5|0,0 0,0.4"),
+  ('?', 4.0, "(2,6,2,2,160,-90) 2,2.5|2,0 2,0.4"),
+  (':', 0.0, "0,5 0,4.6|0,0 0,0.4"),
+  (';', 0.6, "0.6,5 0.6,4.6|0.6,0.4 0.6,0 0,-1.2"),
+  ('\'', 0.0, "0,8 0,6"),
+  ('"', 1.5, "0,8 0,6|1.5,8 1.5,6"),
+  ('-', 3.0, "0,3.5 3,3.5"),
+  ('_', 5.0, "0,-1 5,-1"),
+  ('+', 4.0, "0,3.5 4,3.5|2,1.5 2,5.5"),
+  ('=', 4.0, "0,2.5 4,2.5|0,4.5 4,4.5"),
+  ('*', 3.6, "1.8,2 1.8,6|0.1,3 3.5,5|0.1,5 3.5,3"),
+  ('/', 4.0, "0,-1 4,9"),
+  ('<', 4.0, "4,6 0,3.5 4,1"),
+  ('>', 4.0, "0,6 4,3.5 0,1"),
+  ('(', 1.5, "(3,3.5,3,5.5,120,240)"),
+  (')', 1.5, "(-1.5,3.5,3,5.5,60,-60)"),
+  ('#', 5.5, "1.5,0 2,8|3.5,0 4,8|0,2.5 5,2.5|0.5,5.5 5.5,5.5"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextAlign {
+  Left,   // text starts at the origin (or the start of the baseline curve)
+  Center, // text is centered on the origin (or the middle of the curve)
+  Right,  // text ends at the origin (or the end of the curve)
+}
+
+pub struct TextParams<'a> {
+  pub text: &'a str,
+  pub size: f32,         // cap height
+  pub spacing: f32,      // extra space between letters
+  pub align: TextAlign,
+  pub rotation_deg: f32, // counter-clockwise about the origin, straight baseline only
+  pub origin: Point2,    // start of the first baseline before alignment
+  pub baseline: Option<&'a [Point2]>, // baseline curve, overrides origin and rotation
+}
+
+// Glyph strokes in font units; arcs are flattened every 10 degrees
+fn glyph(c: char) -> (f32, Vec<Vec<Point2>>) {
+  let Some(&(_, width, strokes)) = GLYPHS.iter().find(|g| g.0 == c).or_else(|| GLYPHS.iter().find(|g| g.0 == '?')) else {
+    return (0.0, Vec::new());
+  };
+  let number = |s: &str| s.trim().parse::<f32>().unwrap_or(0.0);
+  let strokes = strokes
+    .split('|')
+    .map(|stroke| {
+      let mut points: Vec<Point2> = Vec::new();
+      let mut rest = stroke.trim();
+      while !rest.is_empty() {
+        if let Some(arc) = rest.strip_prefix('(') {
+          let end = arc.find(')').unwrap_or(arc.len());
+          let v: Vec<f32> = arc[..end].split(',').map(number).collect();
+          let (a0, a1) = (v[4].to_radians(), v[5].to_radians());
+          let steps = ((a1 - a0).abs() / 10f32.to_radians()).ceil().max(1.0) as usize;
+          for i in 0..=steps {
+            let a = a0 + (a1 - a0) * i as f32 / steps as f32;
+            points.push([v[0] + v[2] * a.cos(), v[1] + v[3] * a.sin()]);
+          }
+          rest = arc[(end + 1).min(arc.len())..].trim_start();
+        } else {
+          let end = rest.find(' ').unwrap_or(rest.len());
+          let mut xy = rest[..end].split(',').map(number);
+          points.push([xy.next().unwrap_or(0.0), xy.next().unwrap_or(0.0)]);
+          rest = rest[end..].trim_start();
+        }
+      }
+      points.dedup();
+      points
+    })
+    .collect();
+  (width, strokes)
+}
+
+fn char_width(c: char) -> f32 {
+  if c == ' ' { SPACE_WIDTH } else { glyph(c).0 }
+}
+
+// Width of a line of text in mm, without trailing spacing
+fn line_width(line: &str, scale: f32, spacing: f32) -> f32 {
+  let n = line.chars().count();
+  if n == 0 {
+    return 0.0;
+  }
+  line.chars().map(|c| char_width(c) * scale).sum::<f32>() + (n - 1) as f32 * (GLYPH_GAP * scale + spacing)
+}
+
+// Point and unit direction at arc length s along a polyline, extrapolating past the ends
+fn point_along(curve: &[Point2], s: f32) -> (Point2, Point2) {
+  let mut start = 0.0;
+  for (i, w) in curve.windows(2).enumerate() {
+    let d = [w[1][0] - w[0][0], w[1][1] - w[0][1]];
+    let len = d[0].hypot(d[1]);
+    if len <= 0.0 {
+      continue;
+    }
+    let last = i + 2 == curve.len();
+    if s <= start + len || last {
+      let t = (s - start) / len;
+      let dir = [d[0] / len, d[1] / len];
+      return ([w[0][0] + d[0] * t, w[0][1] + d[1] * t], dir);
+    }
+    start += len;
+  }
+  (curve.first().cloned().unwrap_or([0.0, 0.0]), [1.0, 0.0])
+}
+
+/// Lay out text as single-stroke polylines
+pub fn layout_text(params: &TextParams) -> Vec<Polyline2> {
+  let scale = params.size / CAP_HEIGHT;
+  let gap = GLYPH_GAP * scale + params.spacing;
+  let mut polylines = Vec::new();
+  // Place glyph c with its local origin at pen x on a baseline, then map to the output
+  let mut place = |c: char, to_world: &dyn Fn(Point2) -> Point2| {
+    for stroke in glyph(c).1 {
+      let points: Vec<Point2> = stroke.iter().map(|p| to_world([p[0] * scale, p[1] * scale])).collect();
+      polylines.push(Polyline2 { points, closed: false });
+    }
+  };
+
+  if let Some(curve) = params.baseline.filter(|c| c.len() > 1) {
+    let text: String = params.text.chars().map(|c| if c == '\n' { ' ' } else { c }).collect();
+    let width = line_width(&text, scale, params.spacing);
+    let length: f32 = curve.windows(2).map(|w| (w[1][0] - w[0][0]).hypot(w[1][1] - w[0][1])).sum();
+    let mut pen = match params.align {
+      TextAlign::Left => 0.0,
+      TextAlign::Center => (length - width) / 2.0,
+      TextAlign::Right => length - width,
+    };
+    for c in text.chars() {
+      let w = char_width(c) * scale;
+      if c != ' ' {
+        // Glyph center sits on the curve, turned to the curve direction there
+        let (at, dir) = point_along(curve, pen + w / 2.0);
+        let angle = dir[1].atan2(dir[0]);
+        let to_world = |p: Point2| {
+          let r = rotate([p[0] - w / 2.0, p[1]], angle);
+          [at[0] + r[0], at[1] + r[1]]
+        };
+        place(c, &to_world);
+      }
+      pen += w + gap;
+    }
+    return polylines;
+  }
+
+  let angle = params.rotation_deg.to_radians();
+  for (row, line) in params.text.split('\n').enumerate() {
+    let width = line_width(line, scale, params.spacing);
+    let mut pen = match params.align {
+      TextAlign::Left => 0.0,
+      TextAlign::Center => -width / 2.0,
+      TextAlign::Right => -width,
+    };
+    let y = -(row as f32) * LINE_HEIGHT * params.size;
+    for c in line.chars() {
+      if c != ' ' {
+        let x = pen;
+        let to_world = |p: Point2| {
+          let r = rotate([p[0] + x, p[1] + y], angle);
+          [params.origin[0] + r[0], params.origin[1] + r[1]]
+        };
+        place(c, &to_world);
+      }
+      pen += char_width(c) * scale + gap;
+    }
+  }
+  polylines
+}
+
+/// Engraving passes along polylines at a fixed depth, ordered to shorten travel
+pub fn engrave_passes(polylines: &[Polyline2], z: f32) -> Vec<Pass> {
+  let passes = polylines
+    .iter()
+    .filter(|p| !p.points.is_empty())
+    .map(|p| {
+      let mut pass: Pass = p.points.iter().map(|q| [q[0], q[1], z]).collect();
+      if p.closed {
+        pass.push([p.points[0][0], p.points[0][1], z]);
+      }
+      pass
+    })
+    .collect();
+  order_open_passes(passes)
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Float32Array, Object};
+use crate::geometry_2d::polylines_to_js;
+
+/// Returns { polylines: { points, counts, closed }, toolpath: { passes, linked } }
+#[wasm_bindgen]
+pub fn layout_text_wasm(
+  text: &str,
+  size: f32,
+  spacing: f32,
+  align: &str, // "left", "center" or "right"
+  rotation_deg: f32,
+  origin_x: f32,
+  origin_y: f32,
+  baseline_js: Option<Float32Array>, // flat x, y of a baseline curve, or undefined for a straight baseline
+  engrave_z: f32,
+  clearance_z: f32,
+) -> Object {
+  let baseline: Option<Vec<Point2>> = baseline_js.map(|b| b.to_vec().chunks_exact(2).map(|p| [p[0], p[1]]).collect());
+  let align = match align {
+    "center" => TextAlign::Center,
+    "right" => TextAlign::Right,
+    _ => TextAlign::Left,
+  };
+  let polylines = layout_text(&TextParams {
+    text,
+    size,
+    spacing,
+    align,
+    rotation_deg,
+    origin: [origin_x, origin_y],
+    baseline: baseline.as_deref(),
+  });
+  let passes = engrave_passes(&polylines, engrave_z);
+  let result = Object::new();
+  js_sys::Reflect::set(&result, &"polylines".into(), &polylines_to_js(&polylines)).unwrap();
+  js_sys::Reflect::set(&result, &"toolpath".into(), &passes_to_js(&passes, clearance_z.max(engrave_z))).unwrap();
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn params(text: &str) -> TextParams<'_> {
+    TextParams { text, size: 8.0, spacing: 0.0, align: TextAlign::Left, rotation_deg: 0.0, origin: [0.0, 0.0], baseline: None }
+  }
+
+  fn bounds(polylines: &[Polyline2]) -> [f32; 4] {
+    let mut b = [f32::INFINITY, f32::INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY];
+    for p in polylines.iter().flat_map(|p| p.points.iter()) {
+      b = [b[0].min(p[0]), b[1].min(p[1]), b[2].max(p[0]), b[3].max(p[1])];
+    }
+    b
+  }
+
+  #[test]
+  fn every_glyph_stays_in_the_font_box() {
+    for &(c, width, _) in GLYPHS {
+      let (_, strokes) = glyph(c);
+      assert!(!strokes.is_empty() && strokes.iter().all(|s| s.len() > 1), "glyph {:?}", c);
+      for p in strokes.iter().flatten() {
+        assert!(p[0] > -0.1 && p[0] < width + 0.1 && p[1] > -3.1 && p[1] < 9.1, "glyph {:?} point {:?}", c, p);
+      }
+    }
+  }
+
+  #[test]
+  fn alignment_spacing_and_rotation() {
+    // H (5 wide) + gap 2 + I (0 wide) at cap height 8 is 7 mm wide
+    let b = bounds(&layout_text(&params("HI")));
+    assert!((b[0] - 0.0).abs() < 1e-4 && (b[2] - 7.0).abs() < 1e-4 && (b[3] - 8.0).abs() < 1e-4);
+    let b = bounds(&layout_text(&TextParams { align: TextAlign::Center, spacing: 1.0, size: 16.0, ..params("HI") }));
+    // Twice the size plus 1 mm letter spacing: 15 mm wide, centered
+    assert!((b[0] + 7.5).abs() < 1e-4 && (b[2] - 7.5).abs() < 1e-4);
+    let b = bounds(&layout_text(&TextParams { rotation_deg: 90.0, origin: [10.0, 0.0], ..params("HI") }));
+    // Rotated a quarter turn: reads upward, glyph tops point to -x
+    assert!((b[0] - 2.0).abs() < 1e-4 && (b[2] - 10.0).abs() < 1e-4 && (b[3] - 7.0).abs() < 1e-4);
+    // Second line is 1.6 sizes down; missing glyphs draw as '?'
+    let b = bounds(&layout_text(&params("I\n\u{e9}")));
+    assert!((b[1] + 12.8).abs() < 1e-4);
+  }
+
+  #[test]
+  fn text_follows_baseline_curve() {
+    // Half circle of radius 30 around the origin, counter-clockwise from the right
+    let curve: Vec<Point2> = (0..=90).map(|i| {
+      let a = i as f32 * std::f32::consts::PI / 90.0;
+      [30.0 * a.cos(), 30.0 * a.sin()]
+    }).collect();
+    let p = TextParams { align: TextAlign::Center, baseline: Some(&curve), ..params("L L") };
+    let polylines = layout_text(&p);
+    // The foot of each L lies on the baseline, so its ends are about 30 from the center
+    let feet: Vec<&Polyline2> = polylines.iter().filter(|l| l.points.len() == 3).collect();
+    assert_eq!(feet.len(), 2);
+    for foot in feet {
+      for q in [foot.points[1], foot.points[2]] {
+        assert!((q[0].hypot(q[1]) - 30.0).abs() < 0.5, "{:?}", q);
+      }
+    }
+    let passes = engrave_passes(&polylines, -0.3);
+    assert!(passes.iter().flatten().all(|q| q[2] == -0.3));
+  }
+}
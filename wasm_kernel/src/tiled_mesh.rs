@@ -0,0 +1,286 @@
+// Chunked stock meshes: the heightmap stock split into fixed-size tiles, each its own mesh
+// All units are mm unless otherwise noted
+//
+// One mesh of a large, fine stock runs to millions of vertices in a single buffer. Tiles of
+// tile_size by tile_size cells keep every buffer small (well inside u16 indices for tiles up to
+// 128 cells), let the viewer cull tiles by their bounding boxes, and let a cut re-mesh only the
+// tiles it touched (tiles_for_rects with the dirty rects simulate_material_removal returns).
+//
+// Assumptions:
+// - Tile (tx, ty) covers cells tx * tile_size .. (tx + 1) * tile_size along x, likewise y;
+//   neighboring tiles share their boundary nodes, so together they close without gaps
+// - Each tile has the top and bottom over its cells and side walls only where it reaches the
+//   edge of the stock
+// - Tiles are meshed with one node of margin and cropped, so positions and normals at the
+//   seams are exactly those of heightmap_to_solid_mesh and neighbors shade seamlessly
+// - tile_size 0 is taken as 1 everywhere it is used
+
+use crate::heightmap::{grid_node, Heightmap, NodeRect};
+use crate::heightmap_to_solid_mesh::{solid_mesh_rect, Mesh};
+
+pub struct MeshTile {
+  pub tx: usize,
+  pub ty: usize,
+  pub rect: NodeRect, // nodes covered, end exclusive
+  pub mesh: Mesh,
+  pub bbox_min: [f32; 3],
+  pub bbox_max: [f32; 3],
+}
+
+/// Number of tiles along x and y
+pub fn tile_counts(stock: &Heightmap, tile_size: usize) -> (usize, usize) {
+  let cells = |n: usize| n.saturating_sub(1).div_ceil(tile_size.max(1));
+  (cells(stock.nx), cells(stock.ny))
+}
+
+/// Nodes of tile (tx, ty)
+pub fn tile_rect(stock: &Heightmap, tile_size: usize, tx: usize, ty: usize) -> NodeRect {
+  let tile_size = tile_size.max(1);
+  NodeRect {
+    ix0: tx * tile_size,
+    iy0: ty * tile_size,
+    ix1: ((tx + 1) * tile_size + 1).min(stock.nx),
+    iy1: ((ty + 1) * tile_size + 1).min(stock.ny),
+  }
+}
+
+/// Mesh one tile of the stock
+pub fn heightmap_tile_mesh(stock: &Heightmap, min_z: f32, tile_size: usize, tx: usize, ty: usize) -> MeshTile {
+  let rect = tile_rect(stock, tile_size, tx, ty);
+  let margin = NodeRect {
+    ix0: rect.ix0.saturating_sub(1),
+    iy0: rect.iy0.saturating_sub(1),
+    ix1: (rect.ix1 + 1).min(stock.nx),
+    iy1: (rect.iy1 + 1).min(stock.ny),
+  };
+  let full = solid_mesh_rect(stock, min_z, margin);
+  // Node of each vertex; every block of the margin mesh lays nodes out the same way
+  let ny = margin.iy1 - margin.iy0;
+  let block = (margin.ix1 - margin.ix0) * ny;
+  let inside = |v: usize| {
+    let k = v % block;
+    let (ix, iy) = grid_node(ny, k);
+    let (ix, iy) = (margin.ix0 + ix, margin.iy0 + iy);
+    ix >= rect.ix0 && ix < rect.ix1 && iy >= rect.iy0 && iy < rect.iy1
+  };
+
+  // Keep triangles entirely on the tile's nodes, and only the vertices they use
+  let mut remap = vec![u32::MAX; full.positions.len() / 3];
+  let mut mesh = Mesh { positions: Vec::new(), indices: Vec::new(), normals: Vec::new() };
+  for tri in full.indices.chunks(3) {
+    if !tri.iter().all(|&v| inside(v as usize)) {
+      continue;
+    }
+    for &v in tri {
+      let v = v as usize;
+      if remap[v] == u32::MAX {
+        remap[v] = (mesh.positions.len() / 3) as u32;
+        mesh.positions.extend_from_slice(&full.positions[v * 3..v * 3 + 3]);
+        mesh.normals.extend_from_slice(&full.normals[v * 3..v * 3 + 3]);
+      }
+      mesh.indices.push(remap[v]);
+    }
+  }
+
+  let mut bbox_min = [f32::INFINITY; 3];
+  let mut bbox_max = [f32::NEG_INFINITY; 3];
+  for p in mesh.positions.chunks(3) {
+    for c in 0..3 {
+      bbox_min[c] = bbox_min[c].min(p[c]);
+      bbox_max[c] = bbox_max[c].max(p[c]);
+    }
+  }
+  MeshTile { tx, ty, rect, mesh, bbox_min, bbox_max }
+}
+
+/// Mesh every tile of the stock
+pub fn heightmap_to_tiled_mesh(stock: &Heightmap, min_z: f32, tile_size: usize) -> Vec<MeshTile> {
+  let (tiles_x, tiles_y) = tile_counts(stock, tile_size);
+  (0..tiles_x)
+    .flat_map(|tx| (0..tiles_y).map(move |ty| (tx, ty)))
+    .map(|(tx, ty)| heightmap_tile_mesh(stock, min_z, tile_size, tx, ty))
+    .collect()
+}
+
+/// Tiles whose mesh changes when the nodes in rects change (normals reach one node further)
+pub fn tiles_for_rects(stock: &Heightmap, tile_size: usize, rects: &[NodeRect]) -> Vec<(usize, usize)> {
+  let (tiles_x, tiles_y) = tile_counts(stock, tile_size);
+  let tile_size = tile_size.max(1);
+  // Node i is in the tiles before and after it when it sits on their shared boundary
+  let span = |lo: usize, hi: usize, count: usize| {
+    (lo.saturating_sub(2) / tile_size).min(count.saturating_sub(1))..=(hi / tile_size).min(count.saturating_sub(1))
+  };
+  let mut tiles: Vec<(usize, usize)> = rects
+    .iter()
+    .filter(|r| r.area() > 0)
+    .flat_map(|r| {
+      let ys = span(r.iy0, r.iy1, tiles_y);
+      span(r.ix0, r.ix1, tiles_x).flat_map(move |tx| ys.clone().map(move |ty| (tx, ty)))
+    })
+    .collect();
+  tiles.sort_unstable();
+  tiles.dedup();
+  tiles
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Array, Float32Array, Object, Uint32Array};
+
+fn tile_to_js(tile: &MeshTile) -> Object {
+  let result = Object::new();
+  js_sys::Reflect::set(&result, &"tx".into(), &(tile.tx as u32).into()).unwrap();
+  js_sys::Reflect::set(&result, &"ty".into(), &(tile.ty as u32).into()).unwrap();
+  js_sys::Reflect::set(&result, &"positions".into(), &Float32Array::from(tile.mesh.positions.as_slice())).unwrap();
+  js_sys::Reflect::set(&result, &"indices".into(), &Uint32Array::from(tile.mesh.indices.as_slice())).unwrap();
+  js_sys::Reflect::set(&result, &"normals".into(), &Float32Array::from(tile.mesh.normals.as_slice())).unwrap();
+  js_sys::Reflect::set(&result, &"bbox_min".into(), &Float32Array::from(&tile.bbox_min[..])).unwrap();
+  js_sys::Reflect::set(&result, &"bbox_max".into(), &Float32Array::from(&tile.bbox_max[..])).unwrap();
+  result
+}
+
+/// Array of { tx, ty, positions, indices, normals, bbox_min, bbox_max }, one per tile.
+/// With dirty_js (flat ix0, iy0, ix1, iy1 from simulate_material_removal_wasm) only the tiles
+/// those rects touch are meshed; without it, all of them.
+#[wasm_bindgen]
+pub fn heightmap_to_tiled_mesh_wasm(
+  heights: &Float32Array,
+  nx: usize,
+  ny: usize,
+  grid_size_x: f32,
+  grid_size_y: f32,
+  origin_x: f32,
+  origin_y: f32,
+  min_z: f32,
+  tile_size: usize,
+  dirty_js: Option<Uint32Array>,
+) -> Array {
+  let stock = Heightmap::from_vec(nx, ny, grid_size_x, grid_size_y, origin_x, origin_y, heights.to_vec());
+  let tiles = match dirty_js {
+    Some(dirty_js) => {
+      let dirty: Vec<NodeRect> = dirty_js
+        .to_vec()
+        .chunks_exact(4)
+        .map(|r| NodeRect { ix0: r[0] as usize, iy0: r[1] as usize, ix1: r[2] as usize, iy1: r[3] as usize })
+        .collect();
+      tiles_for_rects(&stock, tile_size, &dirty)
+        .into_iter()
+        .map(|(tx, ty)| heightmap_tile_mesh(&stock, min_z, tile_size, tx, ty))
+        .collect()
+    }
+    None => heightmap_to_tiled_mesh(&stock, min_z, tile_size),
+  };
+  let result = Array::new();
+  for tile in &tiles {
+    result.push(&tile_to_js(tile));
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::heightmap_to_solid_mesh::heightmap_to_solid_mesh;
+  use crate::simulate_material_removal::{simulate_material_removal, SimulateMaterialRemovalParams};
+
+  // Every triangle as its three (position, normal) corners, in a canonical order
+  fn triangles(mesh: &Mesh) -> Vec<[[u32; 6]; 3]> {
+    let corner = |v: usize| {
+      let mut c = [0; 6];
+      for i in 0..3 {
+        c[i] = mesh.positions[v * 3 + i].to_bits();
+        c[i + 3] = mesh.normals[v * 3 + i].to_bits();
+      }
+      c
+    };
+    let mut tris: Vec<[[u32; 6]; 3]> = mesh
+      .indices
+      .chunks(3)
+      .map(|t| {
+        let mut tri = [corner(t[0] as usize), corner(t[1] as usize), corner(t[2] as usize)];
+        // Rotate (keeping the winding) so the smallest corner is first
+        let first = (0..3).min_by_key(|&i| tri[i]).unwrap();
+        tri.rotate_left(first);
+        tri
+      })
+      .collect();
+    tris.sort_unstable();
+    tris
+  }
+
+  fn cut_stock() -> Heightmap {
+    let mut stock = Heightmap::new(41, 23, 0.5, 0.5, -10.0, 0.0, 5.0);
+    simulate_material_removal(SimulateMaterialRemovalParams {
+      heightmap: &mut stock,
+      tool_type: "ball",
+      cutter_diameter: 4.0,
+      v_angle_deg: 0.0,
+      toolpath: &[-8.0, 1.0, 3.0, 2.0, 8.0, 3.0],
+      attributes: None,
+    });
+    stock
+  }
+
+  #[test]
+  fn tiles_together_are_the_solid_mesh() {
+    let stock = cut_stock();
+    let tiles = heightmap_to_tiled_mesh(&stock, 0.0, 8);
+    assert_eq!(tile_counts(&stock, 8), (5, 3));
+    assert_eq!(tiles.len(), 15);
+    // Same triangles with the same positions and normals, seams included
+    let mut all = Mesh { positions: Vec::new(), indices: Vec::new(), normals: Vec::new() };
+    for tile in &tiles {
+      let base = (all.positions.len() / 3) as u32;
+      all.positions.extend_from_slice(&tile.mesh.positions);
+      all.normals.extend_from_slice(&tile.mesh.normals);
+      all.indices.extend(tile.mesh.indices.iter().map(|i| i + base));
+    }
+    assert_eq!(triangles(&all), triangles(&heightmap_to_solid_mesh(&stock, 0.0)));
+    // Boxes hold their tile, the cut one dips below the stock top
+    let corner = &tiles[0];
+    assert_eq!(corner.bbox_min, [-10.0, 0.0, 0.0]);
+    assert_eq!(corner.bbox_max, [-6.0, 4.0, 5.0]);
+    assert!(tiles.iter().any(|t| t.bbox_min[2] == 0.0 && t.mesh.positions.chunks(3).any(|p| p[2] > 0.0 && p[2] < 5.0)));
+  }
+
+  #[test]
+  fn zero_tile_size_is_one_cell_tiles() {
+    let stock = cut_stock();
+    let zero = heightmap_to_tiled_mesh(&stock, 0.0, 0);
+    let one = heightmap_to_tiled_mesh(&stock, 0.0, 1);
+    assert_eq!(zero.len(), 40 * 22);
+    assert!(zero.iter().zip(&one).all(|(a, b)| a.rect == b.rect && a.mesh.positions == b.mesh.positions));
+  }
+
+  #[test]
+  fn dirty_rects_pick_the_tiles_to_redo() {
+    let mut stock = cut_stock();
+    let before = heightmap_to_tiled_mesh(&stock, 0.0, 8);
+    let dirty = simulate_material_removal(SimulateMaterialRemovalParams {
+      heightmap: &mut stock,
+      tool_type: "flat",
+      cutter_diameter: 0.5,
+      v_angle_deg: 0.0,
+      toolpath: &[0.0, 4.0, 1.0],
+      attributes: None,
+    });
+    // Node (20, 8) is inside tile 2 along x and on the boundary between tiles 0 and 1 along y
+    assert_eq!(dirty, vec![NodeRect { ix0: 20, iy0: 8, ix1: 21, iy1: 9 }]);
+    let redo = tiles_for_rects(&stock, 8, &dirty);
+    let after = heightmap_to_tiled_mesh(&stock, 0.0, 8);
+    for (old, new) in before.iter().zip(&after) {
+      let changed = old.mesh.positions != new.mesh.positions || old.mesh.normals != new.mesh.normals;
+      if changed {
+        assert!(redo.contains(&(new.tx, new.ty)), "tile {:?} changed but was not listed", (new.tx, new.ty));
+      }
+    }
+    assert_eq!(redo, vec![(2, 0), (2, 1)]);
+  }
+
+  #[test]
+  fn tiles_of_a_large_grid_fit_u16_indices() {
+    let stock = Heightmap::new(1001, 1001, 0.1, 0.1, 0.0, 0.0, 5.0);
+    let tiles = heightmap_to_tiled_mesh(&stock, 0.0, 128);
+    assert_eq!(tiles.len(), 64);
+    assert!(tiles.iter().all(|t| t.mesh.positions.len() / 3 < 65536));
+  }
+}
@@ -0,0 +1,185 @@
+// Shared toolpath helpers for the Rust toolpath generators
+// All units are mm unless otherwise noted
+//
+// A pass is a continuous cutting move of (x, y, z) points. Passes are joined by
+// links: retract straight up to the clearance height, traverse, then plunge.
+// The linked, flattened form (x0, y0, z0, x1, ...) is what simulate_material_removal expects.
+
+use js_sys::{Array, Float32Array, Object};
+
+pub type Pass = Vec<[f32; 3]>;
+
+/// Join passes into one continuous path, moving between them at clearance_z
+pub fn link_passes(passes: &[Pass], clearance_z: f32) -> Vec<[f32; 3]> {
+  let mut points = Vec::new();
+  for pass in passes.iter().filter(|p| !p.is_empty()) {
+    let first = pass[0];
+    if let Some(&last) = points.last() {
+      let last: [f32; 3] = last;
+      points.push([last[0], last[1], clearance_z.max(last[2])]);
+    }
+    points.push([first[0], first[1], clearance_z.max(first[2])]);
+    points.extend_from_slice(pass);
+  }
+  if let Some(&last) = points.last() {
+    let last: [f32; 3] = last;
+    points.push([last[0], last[1], clearance_z.max(last[2])]);
+  }
+  points
+}
+
+/// Flatten points to x0, y0, z0, x1, y1, z1, ...
+pub fn flatten_points(points: &[[f32; 3]]) -> Vec<f32> {
+  points.iter().flat_map(|p| p.iter().cloned()).collect()
+}
+
+/// Highest finite height in a heightmap; a flat traverse above this can't touch the surface
+pub fn surface_max(heightmap: &[f32]) -> f32 {
+  heightmap.iter().cloned().filter(|z| z.is_finite()).fold(f32::NEG_INFINITY, f32::max)
+}
+
+/// Douglas-Peucker simplification of an open path in 3D, keeping both ends
+pub fn simplify_path(points: &[[f32; 3]], tolerance: f32) -> Pass {
+  fn distance(p: [f32; 3], a: [f32; 3], b: [f32; 3]) -> f32 {
+    let ab = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let ap = [p[0] - a[0], p[1] - a[1], p[2] - a[2]];
+    let len2 = ab[0] * ab[0] + ab[1] * ab[1] + ab[2] * ab[2];
+    let t = if len2 > 0.0 { ((ap[0] * ab[0] + ap[1] * ab[1] + ap[2] * ab[2]) / len2).clamp(0.0, 1.0) } else { 0.0 };
+    let d = [ap[0] - ab[0] * t, ap[1] - ab[1] * t, ap[2] - ab[2] * t];
+    (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()
+  }
+  let n = points.len();
+  if n < 3 {
+    return points.to_vec();
+  }
+  let mut keep = vec![false; n];
+  keep[0] = true;
+  keep[n - 1] = true;
+  let mut stack = vec![(0, n - 1)];
+  while let Some((i0, i1)) = stack.pop() {
+    let mut worst = (0.0, 0);
+    for i in i0 + 1..i1 {
+      let d = distance(points[i], points[i0], points[i1]);
+      if d > worst.0 {
+        worst = (d, i);
+      }
+    }
+    if worst.0 > tolerance {
+      keep[worst.1] = true;
+      stack.push((i0, worst.1));
+      stack.push((worst.1, i1));
+    }
+  }
+  points.iter().zip(keep.iter()).filter(|(_, &k)| k).map(|(p, _)| *p).collect()
+}
+
+/// Insert points so no step along the path is longer than max_step (3D distance)
+pub fn densify_path(points: &[[f32; 3]], max_step: f32) -> Pass {
+  let mut dense = Pass::with_capacity(points.len());
+  for w in points.windows(2) {
+    let (a, b) = (w[0], w[1]);
+    let length = ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2) + (b[2] - a[2]).powi(2)).sqrt();
+    let steps = if max_step > 0.0 { (length / max_step).ceil().max(1.0) as usize } else { 1 };
+    for i in 0..steps {
+      let t = i as f32 / steps as f32;
+      dense.push([a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t]);
+    }
+  }
+  dense.extend(points.last());
+  dense
+}
+
+/// Order open passes nearest-first, reversing a pass when its far end is closer
+pub fn order_open_passes(mut passes: Vec<Pass>) -> Vec<Pass> {
+  passes.retain(|p| !p.is_empty());
+  let mut ordered: Vec<Pass> = Vec::with_capacity(passes.len());
+  let mut at = [0.0f32, 0.0];
+  while !passes.is_empty() {
+    let d2 = |p: &[f32; 3]| (p[0] - at[0]).powi(2) + (p[1] - at[1]).powi(2);
+    let mut best = (f32::INFINITY, 0, false);
+    for (i, pass) in passes.iter().enumerate() {
+      let (ds, de) = (d2(&pass[0]), d2(pass.last().unwrap()));
+      if ds < best.0 {
+        best = (ds, i, false);
+      }
+      if de < best.0 {
+        best = (de, i, true);
+      }
+    }
+    let mut pass = passes.swap_remove(best.1);
+    if best.2 {
+      pass.reverse();
+    }
+    let last = pass.last().unwrap();
+    at = [last[0], last[1]];
+    ordered.push(pass);
+  }
+  ordered
+}
+
+/// Split flat (x, y) or (x, y, z) points into polylines using per-polyline point counts; counts
+/// asking for more points than there are (bad input from JS) give no polylines instead of a panic
+pub fn split_polylines<const N: usize>(points: &[f32], counts: &[u32]) -> Vec<Vec<[f32; N]>> {
+  let total = counts.iter().try_fold(0usize, |sum, &count| sum.checked_add(count as usize));
+  if total.is_none_or(|total| total.saturating_mul(N) > points.len()) {
+    return Vec::new();
+  }
+  let mut polylines = Vec::with_capacity(counts.len());
+  let mut offset = 0;
+  for &count in counts {
+    let count = count as usize;
+    let mut polyline = Vec::with_capacity(count);
+    for i in offset..offset + count {
+      let mut p = [0.0; N];
+      p.copy_from_slice(&points[i * N..i * N + N]);
+      polyline.push(p);
+    }
+    offset += count;
+    polylines.push(polyline);
+  }
+  polylines
+}
+
+/// Convert passes to a JS object: { passes: Float32Array[] (one per pass), linked: Float32Array }
+pub fn passes_to_js(passes: &[Pass], clearance_z: f32) -> Object {
+  let js_passes = Array::new();
+  for pass in passes {
+    js_passes.push(&Float32Array::from(flatten_points(pass).as_slice()));
+  }
+  let linked = Float32Array::from(flatten_points(&link_passes(passes, clearance_z)).as_slice());
+  let result = Object::new();
+  js_sys::Reflect::set(&result, &"passes".into(), &js_passes).unwrap();
+  js_sys::Reflect::set(&result, &"linked".into(), &linked).unwrap();
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn link_passes_retracts_between_passes() {
+    let passes = vec![
+      vec![[0.0, 0.0, 1.0], [5.0, 0.0, 1.0]],
+      vec![[5.0, 5.0, 2.0], [0.0, 5.0, 2.0]],
+    ];
+    let linked = link_passes(&passes, 10.0);
+    assert_eq!(linked.len(), 8);
+    assert_eq!(linked[0], [0.0, 0.0, 10.0]);
+    assert_eq!(linked[3], [5.0, 0.0, 10.0]);
+    assert_eq!(linked[4], [5.0, 5.0, 10.0]);
+    assert_eq!(linked[7], [0.0, 5.0, 10.0]);
+  }
+
+  #[test]
+  fn split_polylines_uses_counts() {
+    let points = vec![0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 5.0, 5.0];
+    let polylines = split_polylines::<2>(&points, &[3, 1]);
+    assert_eq!(polylines.len(), 2);
+    assert_eq!(polylines[0][2], [1.0, 1.0]);
+    assert_eq!(polylines[1][0], [5.0, 5.0]);
+    // Counts past the end of the points give nothing rather than a panic
+    assert!(split_polylines::<2>(&points, &[3, 2]).is_empty());
+    assert!(split_polylines::<3>(&points, &[u32::MAX, u32::MAX]).is_empty());
+  }
+}
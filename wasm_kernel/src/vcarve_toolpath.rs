@@ -0,0 +1,375 @@
+// V-carving of filled outlines along their medial axis
+// All units are mm unless otherwise noted
+//
+// A V-bit whose tip follows the medial axis at depth d / tan(angle / 2), where d is the
+// distance to the nearest outline, just touches the outline on both sides, so the carve
+// fills the shape with sharp walls and corners. Where that would go deeper than max_depth
+// the V-bit stops at max_depth and also traces the outline inset to where the wall meets
+// the floor; the flat floor inside is cleared by an optional flat endmill.
+//
+// Assumptions:
+// - outlines are closed (x, y) loops, even-odd filled (nested loops are holes)
+// - The medial axis is extracted on a grid of `resolution` mm cells: nodes whose nearest outline
+//   points are far apart along the outline, thinned to one cell wide, then traced into paths
+// - Every V-bit point is at most as deep as its distance to the outline allows, so the
+//   carve never cuts outside the shape even where the axis is approximate
+// - The clearing tool pockets the floor in rings inset by the wall width plus its radius, then
+//   further by stepover (at most one diameter), with a last ring a radius inside the innermost
+//   one when the center is still out of reach; the innermost ring is cut first so each ring
+//   cuts outward into uncut material, and rings follow the global cut direction
+// - Clearing goes down to max_depth in equal levels no deeper than clearing_stepdown (one
+//   level when <= 0), repeating the rings on each level like cutout and flatten passes
+
+use crate::cut_direction::loop_needs_reverse;
+use crate::geometry_2d::{even_odd_grid, offset_loops, signed_area, thin_mask, trace_skeleton, Point2};
+use crate::heightmap::{grid_index, grid_node};
+use crate::toolpath::{order_open_passes, passes_to_js, simplify_path, split_polylines, Pass};
+
+pub struct VCarveParams<'a> {
+  pub outlines: &'a [Vec<Point2>],
+  pub v_angle_deg: f32,
+  pub top_z: f32,              // stock surface the outlines are carved into
+  pub max_depth: f32,          // deepest V-bit cut below top_z, <= 0 for unlimited
+  pub clearing_diameter: f32,  // flat endmill for the floor of wide areas, <= 0 for none
+  pub clearing_stepover: f32,  // fraction of the clearing tool diameter
+  pub clearing_stepdown: f32,  // max depth per clearing level, <= 0 for a single level
+  pub resolution: f32,         // medial axis grid cell size
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct VCarveToolpaths {
+  pub vbit: Vec<Pass>,
+  pub clearing: Vec<Pass>,
+}
+
+// Distance to the outline and the nearest outline point (loop, arc length, point), for grid nodes
+struct OutlineDistance {
+  dist: Vec<f32>,
+  nearest: Vec<(usize, f32, Point2)>,
+  perimeters: Vec<f32>,
+}
+
+fn outline_distance(loops: &[Vec<Point2>], origin: Point2, cell: f32, nx: usize, ny: usize, band: f32) -> OutlineDistance {
+  let mut dist = vec![f32::INFINITY; nx * ny];
+  let mut nearest = vec![(0, 0.0, [0.0, 0.0]); nx * ny];
+  let mut perimeters = Vec::with_capacity(loops.len());
+  for (l, points) in loops.iter().enumerate() {
+    let n = points.len();
+    let mut s = 0.0;
+    for i in 0..n {
+      let a = points[i];
+      let b = points[(i + 1) % n];
+      let ab = [b[0] - a[0], b[1] - a[1]];
+      let len2 = ab[0] * ab[0] + ab[1] * ab[1];
+      let len = len2.sqrt();
+      let ix0 = ((a[0].min(b[0]) - band - origin[0]) / cell).floor().max(0.0) as usize;
+      let ix1 = (((a[0].max(b[0]) + band - origin[0]) / cell).ceil().max(0.0) as usize).min(nx - 1);
+      let iy0 = ((a[1].min(b[1]) - band - origin[1]) / cell).floor().max(0.0) as usize;
+      let iy1 = (((a[1].max(b[1]) + band - origin[1]) / cell).ceil().max(0.0) as usize).min(ny - 1);
+      for ix in ix0..=ix1 {
+        for iy in iy0..=iy1 {
+          let p = [origin[0] + ix as f32 * cell, origin[1] + iy as f32 * cell];
+          let t = if len2 > 0.0 { (((p[0] - a[0]) * ab[0] + (p[1] - a[1]) * ab[1]) / len2).clamp(0.0, 1.0) } else { 0.0 };
+          let d = (p[0] - a[0] - ab[0] * t).hypot(p[1] - a[1] - ab[1] * t);
+          let k = grid_index(ny, ix, iy);
+          if d < dist[k] {
+            dist[k] = d;
+            nearest[k] = (l, s + t * len, [a[0] + ab[0] * t, a[1] + ab[1] * t]);
+          }
+        }
+      }
+      s += len;
+    }
+    perimeters.push(s);
+  }
+  OutlineDistance { dist, nearest, perimeters }
+}
+
+/// Generate V-bit passes along the medial axis (and the floor outline when depth limited),
+/// plus flat clearing passes for floors wider than the clearing tool
+pub fn generate_vcarve_toolpath(params: &VCarveParams) -> VCarveToolpaths {
+  let loops: Vec<Vec<Point2>> = params.outlines.iter().filter(|l| l.len() > 2).cloned().collect();
+  let all: Vec<Point2> = loops.iter().flatten().cloned().collect();
+  let mut result = VCarveToolpaths::default();
+  if all.is_empty() || params.resolution <= 0.0 {
+    return result;
+  }
+  let tan_half = (params.v_angle_deg.to_radians() / 2.0).tan().max(1e-6);
+  let limited = params.max_depth > 0.0;
+  // Distance from the outline where the V walls reach max_depth
+  let wall = if limited { params.max_depth * tan_half } else { f32::INFINITY };
+  let floor_z = params.top_z - params.max_depth;
+
+  let cell = params.resolution;
+  let min = [all.iter().map(|p| p[0]).fold(f32::INFINITY, f32::min) - cell, all.iter().map(|p| p[1]).fold(f32::INFINITY, f32::min) - cell];
+  let max = [all.iter().map(|p| p[0]).fold(f32::NEG_INFINITY, f32::max) + cell, all.iter().map(|p| p[1]).fold(f32::NEG_INFINITY, f32::max) + cell];
+  let nx = ((max[0] - min[0]) / cell).ceil() as usize + 1;
+  let ny = ((max[1] - min[1]) / cell).ceil() as usize + 1;
+  // No inside point is further from the outline than half the smaller extent
+  let band = (max[0] - min[0]).min(max[1] - min[1]) / 2.0 + cell;
+  let inside = even_odd_grid(&loops, min, cell, cell, nx, ny);
+  let field = outline_distance(&loops, min, cell, nx, ny, band);
+
+  // Medial nodes: a neighbor's nearest outline point is far away along the outline, and
+  // leaving the node out would leave a sliver deeper than half a cell between the two
+  let separation = |a: (usize, f32, Point2), b: (usize, f32, Point2)| {
+    if a.0 != b.0 {
+      return f32::INFINITY;
+    }
+    let d = (a.1 - b.1).abs();
+    d.min(field.perimeters[a.0] - d)
+  };
+  let sliver = |p: Point2, d: f32, a: Point2, b: Point2| {
+    let (u, v) = ([a[0] - p[0], a[1] - p[1]], [b[0] - p[0], b[1] - p[1]]);
+    let lengths = u[0].hypot(u[1]) * v[0].hypot(v[1]);
+    if lengths < 1e-12 {
+      return 0.0;
+    }
+    let half_angle = ((u[0] * v[0] + u[1] * v[1]) / lengths).clamp(-1.0, 1.0).acos() / 2.0;
+    d * (1.0 - half_angle.cos())
+  };
+  let threshold = 3.0 * cell;
+  let mut mask = vec![false; nx * ny];
+  for ix in 0..nx {
+    for iy in 0..ny {
+      let k = grid_index(ny, ix, iy);
+      if !inside[k] {
+        continue;
+      }
+      for (jx, jy) in [(ix + 1, iy), (ix, iy + 1)] {
+        if jx >= nx || jy >= ny {
+          continue;
+        }
+        let j = grid_index(ny, jx, jy);
+        let p = [min[0] + ix as f32 * cell, min[1] + iy as f32 * cell];
+        let (a, b) = (field.nearest[k], field.nearest[j]);
+        if inside[j] && separation(a, b) > threshold && sliver(p, field.dist[k], a.2, b.2) > cell * 0.5 {
+          mask[k] = true;
+          mask[j] = true;
+        }
+      }
+    }
+  }
+  thin_mask(&mut mask, nx, ny);
+
+  let mut vbit = Vec::new();
+  for path in trace_skeleton(&mask, nx, ny) {
+    let points: Pass = path
+      .iter()
+      .map(|&k| {
+        let (ix, iy) = grid_node(ny, k);
+        let depth = field.dist[k].min(wall) / tan_half;
+        [min[0] + ix as f32 * cell, min[1] + iy as f32 * cell, params.top_z - depth]
+      })
+      .collect();
+    vbit.push(simplify_path(&points, cell * 0.5));
+  }
+  let mut vbit = order_open_passes(vbit);
+
+  if limited {
+    let closed = |l: &Vec<Point2>, z: f32| -> Pass {
+      l.iter().chain(l.first()).map(|p| [p[0], p[1], z]).collect()
+    };
+    // Wall bottoms: the outline inset to where the walls reach max_depth
+    vbit.extend(offset_loops(&loops, -wall, cell).iter().map(|l| closed(l, floor_z)));
+    if params.clearing_diameter > 0.0 {
+      let r = params.clearing_diameter / 2.0;
+      // Rings more than a diameter apart would leave strips between them
+      let step = (params.clearing_diameter * params.clearing_stepover).min(params.clearing_diameter).max(cell);
+      let mut rings = Vec::new();
+      let mut inset = wall + r;
+      let mut last_inset = None;
+      loop {
+        let ring = offset_loops(&loops, -inset, cell);
+        if ring.is_empty() {
+          // Past the center: with a step wider than the radius, the core may still be more than
+          // a radius inside the last ring, so try one more ring a radius in from it
+          match last_inset {
+            Some(last) if inset > last + r => {
+              inset = last + r;
+              continue;
+            }
+            _ => break,
+          }
+        }
+        rings.push(ring);
+        last_inset = Some(inset);
+        inset += step;
+      }
+      let levels = if params.clearing_stepdown > 0.0 { (params.max_depth / params.clearing_stepdown).ceil().max(1.0) as usize } else { 1 };
+      for level in 1..=levels {
+        let z = if level == levels { floor_z } else { params.top_z - params.max_depth * level as f32 / levels as f32 };
+        // Innermost ring first, so each ring cuts outward into uncut material
+        for ring in rings.iter().rev() {
+          for l in ring {
+            // Uncut material is outside outer rings and inside rings around islands (clockwise)
+            let mut l = l.clone();
+            if loop_needs_reverse(&l, signed_area(&l) < 0.0) {
+              l.reverse();
+            }
+            result.clearing.push(closed(&l, z));
+          }
+        }
+      }
+    }
+  }
+  result.vbit = vbit;
+  result
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Float32Array, Object, Uint32Array};
+
+/// Returns { vbit: { passes, linked }, clearing: { passes, linked } }
+#[wasm_bindgen]
+pub fn generate_vcarve_toolpath_wasm(
+  outline_points_js: &Float32Array, // flat x, y for all loops
+  outline_counts_js: &Uint32Array,  // number of points in each loop
+  v_angle_deg: f32,
+  top_z: f32,
+  max_depth: f32,
+  clearing_diameter: f32,
+  clearing_stepover: f32,
+  clearing_stepdown: f32,
+  resolution: f32,
+  clearance_z: f32,
+) -> Object {
+  let outlines = split_polylines::<2>(&outline_points_js.to_vec(), &outline_counts_js.to_vec());
+  let toolpaths = generate_vcarve_toolpath(&VCarveParams {
+    outlines: &outlines,
+    v_angle_deg,
+    top_z,
+    max_depth,
+    clearing_diameter,
+    clearing_stepover,
+    clearing_stepdown,
+    resolution,
+  });
+  let result = Object::new();
+  js_sys::Reflect::set(&result, &"vbit".into(), &passes_to_js(&toolpaths.vbit, clearance_z.max(top_z))).unwrap();
+  js_sys::Reflect::set(&result, &"clearing".into(), &passes_to_js(&toolpaths.clearing, clearance_z.max(top_z))).unwrap();
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::geometry_2d::point_segment_distance;
+  use crate::heightmap::Heightmap;
+  use crate::simulate_material_removal::{simulate_material_removal, SimulateMaterialRemovalParams};
+
+  const CELL: f32 = 0.1;
+
+  // Simulate passes on flat stock over [-1, 31] x [-1, 11], sampling moves every half cell
+  fn carve(stock: &mut Heightmap, passes: &[Pass], tool_type: &str, diameter: f32) {
+    let mut path = Vec::new();
+    for pass in passes {
+      for w in pass.windows(2) {
+        let steps = ((w[1][0] - w[0][0]).hypot(w[1][1] - w[0][1]) / (CELL / 2.0)).ceil().max(1.0) as usize;
+        for i in 0..=steps {
+          let t = i as f32 / steps as f32;
+          path.extend_from_slice(&[0, 1, 2].map(|c| w[0][c] + (w[1][c] - w[0][c]) * t));
+        }
+      }
+    }
+    simulate_material_removal(SimulateMaterialRemovalParams {
+      heightmap: stock,
+      tool_type,
+      cutter_diameter: diameter,
+      v_angle_deg: 90.0,
+      toolpath: &path,
+      attributes: None,
+    });
+  }
+
+  // Distance from p to the edge of the 30 x 10 rectangle, negative outside
+  fn rect_distance(p: Point2) -> f32 {
+    let edges = [[0.0, 0.0], [30.0, 0.0], [30.0, 10.0], [0.0, 10.0]];
+    let d = (0..4).map(|i| point_segment_distance(p, edges[i], edges[(i + 1) % 4])).fold(f32::INFINITY, f32::min);
+    if p[0] > 0.0 && p[0] < 30.0 && p[1] > 0.0 && p[1] < 10.0 { d } else { -d }
+  }
+
+  fn params(outlines: &[Vec<Point2>]) -> VCarveParams<'_> {
+    VCarveParams { outlines, v_angle_deg: 90.0, top_z: 0.0, max_depth: 0.0, clearing_diameter: 0.0, clearing_stepover: 0.4, clearing_stepdown: 0.0, resolution: CELL }
+  }
+
+  #[test]
+  fn vcarve_follows_medial_axis() {
+    let outlines = vec![vec![[0.0, 0.0], [30.0, 0.0], [30.0, 10.0], [0.0, 10.0]]];
+    let toolpaths = generate_vcarve_toolpath(&params(&outlines));
+    assert!(toolpaths.clearing.is_empty());
+    // Deepest point is the middle of the 10 mm wide rectangle under a 90 degree bit
+    let deepest = toolpaths.vbit.iter().flatten().map(|p| p[2]).fold(f32::INFINITY, f32::min);
+    assert!((deepest + 5.0).abs() < 0.1, "deepest {}", deepest);
+
+    let mut stock = Heightmap::new(321, 121, CELL, CELL, -1.0, -1.0, 0.0);
+    carve(&mut stock, &toolpaths.vbit, "vbit", 12.0);
+    for ix in 0..321 {
+      for iy in 0..121 {
+        let p = [-1.0 + ix as f32 * CELL, -1.0 + iy as f32 * CELL];
+        let d = rect_distance(p);
+        let z = stock.get(ix, iy);
+        // Never cuts outside, and the carved surface is the V of the walls
+        assert!(z >= -d.max(0.0) - 0.15, "gouge at {:?}: {} for distance {}", p, z, d);
+        if d > 0.5 {
+          assert!(z <= -d + 0.3, "uncut at {:?}: {} for distance {}", p, z, d);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn depth_limit_with_flat_clearing() {
+    let outlines = vec![vec![[0.0, 0.0], [30.0, 0.0], [30.0, 10.0], [0.0, 10.0]]];
+    let toolpaths = generate_vcarve_toolpath(&VCarveParams { max_depth: 2.0, clearing_diameter: 3.0, ..params(&outlines) });
+    assert!(toolpaths.vbit.iter().flatten().all(|p| p[2] >= -2.0 - 1e-4));
+    assert!(!toolpaths.clearing.is_empty());
+
+    let mut stock = Heightmap::new(321, 121, CELL, CELL, -1.0, -1.0, 0.0);
+    carve(&mut stock, &toolpaths.vbit, "vbit", 6.0);
+    carve(&mut stock, &toolpaths.clearing, "flat", 3.0);
+    for ix in 0..321 {
+      for iy in 0..121 {
+        let p = [-1.0 + ix as f32 * CELL, -1.0 + iy as f32 * CELL];
+        let d = rect_distance(p);
+        let z = stock.get(ix, iy);
+        assert!(z >= -d.clamp(0.0, 2.0) - 0.15, "gouge at {:?}: {} for distance {}", p, z, d);
+        if d > 0.5 && d < 1.8 {
+          assert!(z <= -d + 0.3, "wall uncut at {:?}: {}", p, z);
+        }
+        // Floor, away from the inside corners the round clearing tool can't reach
+        if p[0] > 3.0 && p[0] < 27.0 && p[1] > 3.0 && p[1] < 7.0 {
+          assert!((z + 2.0).abs() < 0.01, "floor uncut at {:?}: {}", p, z);
+        }
+      }
+    }
+
+    // Default is one level at the floor; a stepdown repeats the same rings on equal levels
+    let level_zs = |passes: &[Pass]| {
+      let mut zs: Vec<f32> = passes.iter().map(|p| p[0][2]).collect();
+      zs.dedup();
+      zs
+    };
+    assert_eq!(level_zs(&toolpaths.clearing), vec![-2.0]);
+    let stepped = generate_vcarve_toolpath(&VCarveParams { max_depth: 2.0, clearing_diameter: 3.0, clearing_stepdown: 0.75, ..params(&outlines) });
+    let zs = level_zs(&stepped.clearing);
+    assert_eq!(zs.len(), 3);
+    assert!((zs[0] + 2.0 / 3.0).abs() < 1e-5 && zs[2] == -2.0);
+    assert_eq!(stepped.clearing.len(), 3 * toolpaths.clearing.len());
+  }
+
+  #[test]
+  fn wide_clearing_stepover_leaves_no_island() {
+    // Floor 7 mm across after 2 mm walls: rings 2.7 mm apart would stop at inset 3.5, whose
+    // tool edge reaches only 5 mm in from the outline, short of the 5.5 mm center line
+    let outlines = vec![vec![[0.0, 0.0], [30.0, 0.0], [30.0, 11.0], [0.0, 11.0]]];
+    let toolpaths = generate_vcarve_toolpath(&VCarveParams { max_depth: 2.0, clearing_diameter: 3.0, clearing_stepover: 0.9, ..params(&outlines) });
+    let mut stock = Heightmap::new(321, 131, CELL, CELL, -1.0, -1.0, 0.0);
+    carve(&mut stock, &toolpaths.clearing, "flat", 3.0);
+    for ix in 40..=280 {
+      for iy in 40..=90 {
+        assert!((stock.get(ix, iy) + 2.0).abs() < 0.01, "floor uncut at {:?}", (stock.x(ix), stock.y(iy)));
+      }
+    }
+  }
+}
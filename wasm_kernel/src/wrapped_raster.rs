@@ -0,0 +1,335 @@
+// Wrapped raster toolpaths for a 4th (A) axis, from a mesh rasterized around the X axis
+// All units are mm unless otherwise noted
+//
+// The part mesh is rasterized radially: for every node of a RotaryStock-style grid, the
+// farthest hit of the ray from the axis is the part radius there. Passes are then laid over
+// the unrolled cylinder, either as rows along X at evenly spaced A angles (zig-zag) or as a
+// helix that turns A continuously while X advances one stepover per turn. The tool tip height
+// at each sample is the lowest z where the vertical tool clears every part node under it.
+//
+// Assumptions:
+// - Toolpath points are (x, a degrees, z) as in rotary_stock; link_passes retracts in z, so
+//   the linked output can go straight to simulate_rotary_removal_wasm
+// - Radii layout ix * na + ia, node angles ia * 360 / na degrees from +Z toward +Y, A = 0 being
+//   the mesh as given; rays that miss the mesh get radius 0
+// - The tool never goes below the axis (z >= 0)
+// - Row spacing is the stepover measured on the largest part radius, so smaller radii get
+//   closer rows; samples along a row are sample_step apart (<= 0 for half a cell)
+// - Where z changes by more than a sample step between samples (next to a wall, where the
+//   tool's vertical side makes the safe height jump), a corner point is added so the tool
+//   crosses at the higher z and goes straight up or down, instead of cutting into the wall
+// - Rows follow the global cut direction like a flat raster on the unrolled (x, a) plane with
+//   A in place of y: climb rows run toward -x, conventional toward +x, either zig-zags
+
+use crate::cut_direction::raster_row_reversed;
+use crate::heightmap_from_mesh::MeshRust;
+use crate::stock_model::ToolShape;
+use crate::toolpath::Pass;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WrapPattern {
+  Rows,
+  Helix,
+}
+
+pub struct WrappedRasterParams<'a> {
+  pub radii: &'a [f32], // part radius per node (ix * na + ia)
+  pub nx: usize,
+  pub na: usize,
+  pub cell_x: f32,
+  pub origin_x: f32,
+  pub tool_type: &'a str,
+  pub cutter_diameter: f32,
+  pub v_angle_deg: f32,
+  pub pattern: WrapPattern,
+  pub stepover: f32,
+  pub sample_step: f32,
+}
+
+// Distance along the ray o + t d to triangle abc, if it hits
+fn ray_triangle(o: [f32; 3], d: [f32; 3], a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> Option<f32> {
+  let sub = |p: [f32; 3], q: [f32; 3]| [p[0] - q[0], p[1] - q[1], p[2] - q[2]];
+  let cross = |p: [f32; 3], q: [f32; 3]| [p[1] * q[2] - p[2] * q[1], p[2] * q[0] - p[0] * q[2], p[0] * q[1] - p[1] * q[0]];
+  let dot = |p: [f32; 3], q: [f32; 3]| p[0] * q[0] + p[1] * q[1] + p[2] * q[2];
+  let (e1, e2) = (sub(b, a), sub(c, a));
+  let p = cross(d, e2);
+  let det = dot(e1, p);
+  if det.abs() < 1e-12 {
+    return None;
+  }
+  let s = sub(o, a);
+  let u = dot(s, p) / det;
+  let q = cross(s, e1);
+  let v = dot(d, q) / det;
+  let eps = 1e-6;
+  if u < -eps || v < -eps || u + v > 1.0 + eps {
+    return None;
+  }
+  let t = dot(e2, q) / det;
+  if t >= 0.0 { Some(t) } else { None }
+}
+
+/// Part radius at every node: the farthest point of the mesh along each ray from the X axis
+pub fn radial_heightmap_from_mesh(mesh: &MeshRust, origin_x: f32, nx: usize, cell_x: f32, na: usize) -> Vec<f32> {
+  let mut radii = vec![0.0f32; nx * na];
+  if nx == 0 || na == 0 {
+    return radii;
+  }
+  let cell_a = std::f32::consts::TAU / na as f32;
+  let vertex = |i: usize| [mesh.positions[i * 3], mesh.positions[i * 3 + 1], mesh.positions[i * 3 + 2]];
+  let triangles: Vec<[usize; 3]> = match &mesh.indices {
+    Some(indices) => indices.chunks_exact(3).map(|t| [t[0] as usize, t[1] as usize, t[2] as usize]).collect(),
+    None => (0..mesh.positions.len() / 9).map(|t| [t * 3, t * 3 + 1, t * 3 + 2]).collect(),
+  };
+  for t in triangles {
+    let (a, b, c) = (vertex(t[0]), vertex(t[1]), vertex(t[2]));
+    let ix0 = ((a[0].min(b[0]).min(c[0]) - origin_x) / cell_x).ceil().max(0.0) as usize;
+    let ix1 = ((a[0].max(b[0]).max(c[0]) - origin_x) / cell_x).floor();
+    if ix1 < 0.0 || ix0 >= nx {
+      continue;
+    }
+    // Angles the triangle spans, unwrapped around its first corner; a triangle around the axis
+    // spans more than half a turn and is checked against every ray
+    let angle = |p: [f32; 3]| p[1].atan2(p[2]);
+    let a0 = angle(a);
+    let wrap = |d: f32| (d + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI;
+    let (d1, d2) = (wrap(angle(b) - a0), wrap(angle(c) - a0));
+    let (lo, hi) = (a0 + d1.min(d2).min(0.0), a0 + d1.max(d2).max(0.0));
+    let on_axis = [a, b, c].iter().any(|p| p[1].hypot(p[2]) < 1e-6);
+    let (ia0, ia1) = if on_axis || hi - lo > std::f32::consts::PI {
+      (0, na as isize - 1)
+    } else {
+      ((lo / cell_a - 1e-4).ceil() as isize, (hi / cell_a + 1e-4).floor() as isize)
+    };
+    for ix in ix0..=(ix1 as usize).min(nx - 1) {
+      let x = origin_x + ix as f32 * cell_x;
+      for ia in ia0..=ia1 {
+        let k = ix * na + ia.rem_euclid(na as isize) as usize;
+        let (s, co) = (ia as f32 * cell_a).sin_cos();
+        if let Some(t) = ray_triangle([x, 0.0, 0.0], [0.0, s, co], a, b, c) {
+          radii[k] = radii[k].max(t);
+        }
+      }
+    }
+  }
+  radii
+}
+
+/// Lowest tip height above the axis where the tool at (x, a degrees) clears every part node
+pub fn rotary_safe_z(params: &WrappedRasterParams, x: f32, a_deg: f32) -> f32 {
+  let tool = ToolShape { tool_type: params.tool_type, cutter_diameter: params.cutter_diameter, v_angle_deg: params.v_angle_deg };
+  let r = params.cutter_diameter / 2.0;
+  let cell_a = std::f32::consts::TAU / params.na as f32;
+  let ix0 = ((x - r - params.origin_x) / params.cell_x).ceil().max(0.0) as usize;
+  let ix1 = ((x + r - params.origin_x) / params.cell_x).floor();
+  let mut z = 0.0f32;
+  if ix1 < 0.0 {
+    return z;
+  }
+  for ix in ix0..=(ix1 as usize).min(params.nx.saturating_sub(1)) {
+    let dx = params.origin_x + ix as f32 * params.cell_x - x;
+    for ia in 0..params.na {
+      let (s, c) = (ia as f32 * cell_a - a_deg.to_radians()).sin_cos();
+      let rho = params.radii[ix * params.na + ia];
+      // Node position with the stock turned to a, and the tool surface height above its tip there
+      if let Some(lift) = tool.profile(dx.hypot(rho * s)) {
+        z = z.max(rho * c - lift);
+      }
+    }
+  }
+  z
+}
+
+// Add corners at steep steps so the move between two samples never dips below either
+fn step_corners(pass: Pass, max_rise: f32) -> Pass {
+  let mut result = Pass::with_capacity(pass.len());
+  for p in pass {
+    if let Some(&q) = result.last() {
+      let q: [f32; 3] = q;
+      if p[2] < q[2] - max_rise {
+        result.push([p[0], p[1], q[2]]);
+      } else if p[2] > q[2] + max_rise {
+        result.push([q[0], q[1], p[2]]);
+      }
+    }
+    result.push(p);
+  }
+  result
+}
+
+/// Generate wrapped raster passes of (x, a degrees, z) points
+pub fn generate_wrapped_raster(params: &WrappedRasterParams) -> Vec<Pass> {
+  let (nx, na) = (params.nx, params.na);
+  if nx == 0 || na == 0 || params.radii.len() != nx * na || params.stepover <= 0.0 {
+    return Vec::new();
+  }
+  let length = (nx - 1) as f32 * params.cell_x;
+  let max_radius = params.radii.iter().cloned().fold(0.0f32, f32::max).max(1e-3);
+  let step = if params.sample_step > 0.0 { params.sample_step } else { params.cell_x / 2.0 };
+  let sample = |x: f32, a: f32| [x, a, rotary_safe_z(params, x, a)];
+  match params.pattern {
+    WrapPattern::Rows => {
+      let rows = (std::f32::consts::TAU * max_radius / params.stepover).ceil().max(1.0) as usize;
+      let columns = (length / step).ceil().max(1.0) as usize;
+      (0..rows)
+        .map(|row| {
+          let a = row as f32 * 360.0 / rows as f32;
+          let mut pass: Pass = (0..=columns).map(|i| sample(params.origin_x + length * i as f32 / columns as f32, a)).collect();
+          if raster_row_reversed().unwrap_or(row % 2 == 1) {
+            pass.reverse();
+          }
+          step_corners(pass, step)
+        })
+        .collect()
+    }
+    WrapPattern::Helix => {
+      // One stepover along x per turn, samples spaced by step on the largest radius
+      let turns = (length / params.stepover).ceil().max(1.0);
+      let per_turn = (std::f32::consts::TAU * max_radius / step).ceil().max(4.0);
+      let count = (turns * per_turn) as usize;
+      let pass = (0..=count)
+        .map(|i| {
+          let t = i as f32 / count as f32;
+          sample(params.origin_x + length * t, 360.0 * turns * t)
+        })
+        .collect();
+      vec![step_corners(pass, step)]
+    }
+  }
+}
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Float32Array, Object, Uint32Array};
+use crate::toolpath::passes_to_js;
+
+#[wasm_bindgen]
+pub fn radial_heightmap_from_mesh_wasm(
+  positions: &Float32Array,
+  indices: Option<Uint32Array>,
+  origin_x: f32,
+  nx: usize,
+  cell_x: f32,
+  na: usize,
+) -> Float32Array {
+  let mesh = MeshRust { positions: positions.to_vec(), indices: indices.map(|i| i.to_vec()) };
+  Float32Array::from(radial_heightmap_from_mesh(&mesh, origin_x, nx, cell_x, na).as_slice())
+}
+
+/// pattern: "helix" or anything else for rows. Returns { passes, linked } of x, a, z points.
+#[wasm_bindgen]
+pub fn generate_wrapped_raster_wasm(
+  radii_js: &Float32Array,
+  nx: usize,
+  na: usize,
+  cell_x: f32,
+  origin_x: f32,
+  tool_type: &str,
+  cutter_diameter: f32,
+  v_angle_deg: f32,
+  pattern: &str,
+  stepover: f32,
+  sample_step: f32,
+  clearance_z: f32,
+) -> Object {
+  let radii = radii_js.to_vec();
+  let passes = generate_wrapped_raster(&WrappedRasterParams {
+    radii: &radii,
+    nx,
+    na,
+    cell_x,
+    origin_x,
+    tool_type,
+    cutter_diameter,
+    v_angle_deg,
+    pattern: if pattern == "helix" { WrapPattern::Helix } else { WrapPattern::Rows },
+    stepover,
+    sample_step,
+  });
+  passes_to_js(&passes, clearance_z.max(radii.iter().cloned().fold(0.0, f32::max)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cut_direction::{set_cut_direction, CutDirection};
+  use crate::rotary_stock::RotaryStock;
+  use crate::toolpath::link_passes;
+
+  // 20 mm bar of radius 10 with a groove 2 deep over x in [5, 15], 0.5 mm by 2 degree cells
+  fn grooved() -> RotaryStock {
+    let mut part = RotaryStock::new_cylinder(0.0, 20.0, 41, 180, 10.0);
+    for ix in 10..=30 {
+      part.radii[ix * 180..(ix + 1) * 180].fill(8.0);
+    }
+    part
+  }
+
+  fn params(part: &RotaryStock, pattern: WrapPattern) -> WrappedRasterParams<'_> {
+    WrappedRasterParams {
+      radii: &part.radii,
+      nx: part.nx,
+      na: part.na,
+      cell_x: part.cell_x,
+      origin_x: part.origin_x,
+      tool_type: "ball",
+      cutter_diameter: 2.0,
+      v_angle_deg: 0.0,
+      pattern,
+      stepover: 0.5,
+      sample_step: 0.0,
+    }
+  }
+
+  #[test]
+  fn radial_raster_recovers_radii() {
+    let mut part = RotaryStock::new_cylinder(-5.0, 10.0, 21, 36, 6.0);
+    for (k, r) in part.radii.iter_mut().enumerate() {
+      *r += (k % 36) as f32 / 36.0 + (k / 36) as f32 * 0.1;
+    }
+    let mesh = part.to_mesh();
+    let radii = radial_heightmap_from_mesh(&MeshRust { positions: mesh.positions, indices: Some(mesh.indices) }, -5.0, 21, 0.5, 36);
+    for (k, (got, want)) in radii.iter().zip(&part.radii).enumerate() {
+      assert!((got - want).abs() < 1e-3, "node {}: {} vs {}", k, got, want);
+    }
+  }
+
+  #[test]
+  fn rows_finish_groove_without_gouging() {
+    let part = grooved();
+    let passes = generate_wrapped_raster(&params(&part, WrapPattern::Rows));
+    // Rows every stepover on the largest radius, alternating direction along x
+    assert_eq!(passes.len(), (std::f32::consts::TAU * 10.0 / 0.5).ceil() as usize);
+    assert!(passes[0][0][0] < passes[1][0][0]);
+    let mut stock = RotaryStock::new_cylinder(0.0, 20.0, 41, 180, 12.0);
+    stock.remove_swept_tool(&ToolShape { tool_type: "ball", cutter_diameter: 2.0, v_angle_deg: 0.0 }, &link_passes(&passes, 14.0));
+    for (k, (s, p)) in stock.radii.iter().zip(&part.radii).enumerate() {
+      assert!(s >= &(p - 1e-3), "gouged node {}: {} below {}", k, s, p);
+    }
+    // The middle of the groove is cut down to the part
+    assert!((10 * 180..31 * 180).filter(|&k| (12..29).contains(&(k / 180))).all(|k| stock.radii[k] - part.radii[k] < 0.1));
+  }
+
+  #[test]
+  fn rows_follow_cut_direction() {
+    let part = grooved();
+    for (direction, toward_minus_x) in [(CutDirection::Climb, true), (CutDirection::Conventional, false)] {
+      set_cut_direction(direction);
+      let passes = generate_wrapped_raster(&params(&part, WrapPattern::Rows));
+      assert!(passes.iter().all(|p| (p[p.len() - 1][0] < p[0][0]) == toward_minus_x), "{:?}", direction);
+    }
+    set_cut_direction(CutDirection::Either);
+  }
+
+  #[test]
+  fn helix_turns_once_per_stepover() {
+    let part = grooved();
+    let passes = generate_wrapped_raster(&WrappedRasterParams { stepover: 2.0, ..params(&part, WrapPattern::Helix) });
+    assert_eq!(passes.len(), 1);
+    let (first, last) = (passes[0][0], passes[0][passes[0].len() - 1]);
+    assert_eq!((first[0], first[1]), (0.0, 0.0));
+    assert!((last[0] - 20.0).abs() < 1e-4 && (last[1] - 3600.0).abs() < 1e-2);
+    // Over the groove the ball tip rides on radius 8
+    let mid = passes[0].iter().find(|p| (p[0] - 10.0).abs() < 0.05).unwrap();
+    assert!((mid[2] - 8.0).abs() < 0.02, "{:?}", mid);
+  }
+}